@@ -0,0 +1,131 @@
+//! Renaming the members of a zip or tar archive with `--in-archive`, without extracting it.
+//! The member names are edited like file names, and the archive is rewritten with the renamed
+//! members on confirmation. Directory entries are not listed, they are kept as they are.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+/// The supported archive formats, determined by the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Zip,
+    Tar,
+}
+
+impl Format {
+    fn of(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("zip") => Ok(Format::Zip),
+            Some("tar") => Ok(Format::Tar),
+            _ => anyhow::bail!(
+                "{} is not a .zip or .tar archive. Compressed tar archives are not supported.",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// The names of the members of the archive that are not directories, in the order of the archive
+pub fn member_names(path: &Path) -> Result<Vec<String>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open the archive {}", path.display()))?;
+    let mut names = Vec::new();
+    match Format::of(path)? {
+        Format::Zip => {
+            let mut archive = zip::ZipArchive::new(BufReader::new(file))
+                .with_context(|| format!("Failed to read the archive {}", path.display()))?;
+            for i in 0..archive.len() {
+                let member = archive.by_index_raw(i)?;
+                if !member.is_dir() {
+                    names.push(member.name().to_string());
+                }
+            }
+        }
+        Format::Tar => {
+            let mut archive = tar::Archive::new(BufReader::new(file));
+            for entry in archive
+                .entries()
+                .with_context(|| format!("Failed to read the archive {}", path.display()))?
+            {
+                let entry = entry?;
+                if entry.header().entry_type().is_dir() {
+                    continue;
+                }
+                let name = entry.path()?;
+                names.push(
+                    name.to_str()
+                        .with_context(|| {
+                            format!(
+                                "The member name {} is not valid UTF-8.",
+                                name.to_string_lossy()
+                            )
+                        })?
+                        .to_string(),
+                );
+            }
+        }
+    }
+    if let Some(name) = names.iter().find(|name| name.contains('\n')) {
+        anyhow::bail!("The member name {:?} contains a line break.", name);
+    }
+    Ok(names)
+}
+
+/// Rewrite the archive with the members renamed according to `renamings` of old to new names.
+/// The archive is written next to the original and replaces it once it is complete,
+/// so the original stays intact if rewriting fails.
+pub fn rename_members(path: &Path, renamings: &HashMap<&str, &str>) -> Result<()> {
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let temp_file = NamedTempFile::new_in(directory).with_context(|| {
+        format!(
+            "Failed to create a temporary file in {}",
+            directory.display()
+        )
+    })?;
+    let source = File::open(path)
+        .with_context(|| format!("Failed to open the archive {}", path.display()))?;
+    let target = BufWriter::new(temp_file.as_file());
+    let rename = |name: &str| renamings.get(name).copied().unwrap_or(name).to_string();
+    match Format::of(path)? {
+        Format::Zip => {
+            let mut archive = zip::ZipArchive::new(BufReader::new(source))?;
+            let mut writer = zip::ZipWriter::new(target);
+            writer.set_raw_comment(archive.comment().to_vec());
+            for i in 0..archive.len() {
+                let member = archive.by_index_raw(i)?;
+                let name = rename(member.name());
+                // the compressed data is copied as it is
+                writer.raw_copy_file_rename(member, name)?;
+            }
+            writer.finish()?.flush()?;
+        }
+        Format::Tar => {
+            let mut archive = tar::Archive::new(BufReader::new(source));
+            let mut builder = tar::Builder::new(target);
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let mut header = entry.header().clone();
+                let name = entry.path()?.to_string_lossy().to_string();
+                // long names are written as extensions of the header by the builder
+                builder.append_data(&mut header, rename(&name), &mut entry)?;
+            }
+            builder.into_inner()?.flush()?;
+        }
+    }
+    let permissions = fs::metadata(path)?.permissions();
+    fs::set_permissions(temp_file.path(), permissions)?;
+    temp_file
+        .persist(path)
+        .with_context(|| format!("Failed to replace the archive {}", path.display()))?;
+    Ok(())
+}
@@ -0,0 +1,184 @@
+//! Selecting files by their git attributes with `--attr-filter`, e.g. only the files marked as
+//! assets in `.gitattributes`. Attributes are read from the `.gitattributes` files from the root
+//! of the repository down to the directory of each file, and from `.git/info/attributes`, with
+//! the same precedence as in git. Macro attributes like `binary` are not expanded.
+
+use anyhow::Result;
+use globset::{GlobBuilder, GlobMatcher};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The state of an attribute of a path, written like in `.gitattributes`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum State {
+    /// `attr`
+    Set,
+    /// `-attr`
+    Unset,
+    /// `attr=value`
+    Value(String),
+    /// `!attr`, or not mentioned for the path at all
+    Unspecified,
+}
+
+/// A condition on an attribute given with `--attr-filter`, like `export-ignore`, `-diff`,
+/// `kind=asset` or `!linguist-generated`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    name: String,
+    state: State,
+}
+
+/// Parse an attribute as written in `.gitattributes`
+fn parse_attribute(attribute: &str) -> (String, State) {
+    if let Some(name) = attribute.strip_prefix('-') {
+        (name.to_string(), State::Unset)
+    } else if let Some(name) = attribute.strip_prefix('!') {
+        (name.to_string(), State::Unspecified)
+    } else if let Some((name, value)) = attribute.split_once('=') {
+        (name.to_string(), State::Value(value.to_string()))
+    } else {
+        (attribute.to_string(), State::Set)
+    }
+}
+
+/// Parse a condition given with `--attr-filter`
+pub fn parse_condition(condition: &str) -> Result<Condition> {
+    let (name, state) = parse_attribute(condition);
+    let valid_name = !name.is_empty()
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    anyhow::ensure!(
+        valid_name,
+        "Invalid attribute filter {}, expected e.g. attr, -attr, !attr or attr=value",
+        condition
+    );
+    Ok(Condition { name, state })
+}
+
+/// A line of an attributes file
+#[derive(Debug)]
+struct Rule {
+    glob: GlobMatcher,
+    /// Whether the pattern has no `/` and matches the file name at any depth
+    by_name: bool,
+    attributes: Vec<(String, State)>,
+}
+
+impl Rule {
+    fn matches(&self, relative_path: &Path) -> bool {
+        if self.by_name {
+            relative_path
+                .file_name()
+                .is_some_and(|name| self.glob.is_match(name))
+        } else {
+            self.glob.is_match(relative_path)
+        }
+    }
+}
+
+/// Parse the lines of an attributes file, skipping invalid patterns and macro definitions
+fn parse_rules(content: &str) -> Vec<Rule> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pattern = fields.next()?;
+            // comments, macro definitions, and negative patterns, which git rejects
+            if pattern.starts_with('#') || pattern.starts_with("[attr]") || pattern.starts_with('!')
+            {
+                return None;
+            }
+            let by_name = !pattern.trim_end_matches('/').contains('/');
+            let glob = GlobBuilder::new(pattern.trim_start_matches('/'))
+                .literal_separator(true)
+                .build()
+                .ok()?
+                .compile_matcher();
+            Some(Rule {
+                glob,
+                by_name,
+                attributes: fields.map(parse_attribute).collect(),
+            })
+        })
+        .collect()
+}
+
+/// Selects files whose attributes meet all conditions, caching the parsed attribute files
+#[derive(Debug, Default)]
+pub struct AttrFilter {
+    conditions: Vec<Condition>,
+    /// The rules of each attributes file read so far, empty if the file does not exist
+    rules: HashMap<PathBuf, Vec<Rule>>,
+}
+
+impl AttrFilter {
+    pub fn new(conditions: &[Condition]) -> Self {
+        Self {
+            conditions: conditions.to_vec(),
+            rules: HashMap::new(),
+        }
+    }
+
+    /// Whether the attributes of the file meet all conditions
+    pub fn matches(&mut self, path: &Path) -> bool {
+        let attributes = self.attributes(path);
+        self.conditions.iter().all(|condition| {
+            attributes
+                .get(condition.name.as_str())
+                .unwrap_or(&State::Unspecified)
+                == &condition.state
+        })
+    }
+
+    /// The attributes of the file, as far as they are specified
+    fn attributes(&mut self, path: &Path) -> HashMap<String, State> {
+        let mut attributes = HashMap::new();
+        // resolve the directory, but not the file, which may be a symbolic link
+        let (Some(directory), Some(name)) = (path.parent(), path.file_name()) else {
+            return attributes;
+        };
+        let directory = if directory.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            directory
+        };
+        let Ok(directory) = fs::canonicalize(directory) else {
+            return attributes;
+        };
+        let path = directory.join(name);
+        let root = directory
+            .ancestors()
+            .find(|ancestor| ancestor.join(".git").exists())
+            .unwrap_or(&directory)
+            .to_path_buf();
+        // the attributes files of deeper directories take precedence, so they are applied last
+        let mut files: Vec<(PathBuf, PathBuf)> = directory
+            .ancestors()
+            .take_while(|ancestor| ancestor.starts_with(&root))
+            .map(|ancestor| (ancestor.to_path_buf(), ancestor.join(".gitattributes")))
+            .collect();
+        files.reverse();
+        files.push((
+            root.clone(),
+            root.join(".git").join("info").join("attributes"),
+        ));
+        for (base, file) in files {
+            let rules = self.rules.entry(file.clone()).or_insert_with(|| {
+                fs::read_to_string(&file)
+                    .map(|content| parse_rules(&content))
+                    .unwrap_or_default()
+            });
+            let relative_path = path.strip_prefix(&base).unwrap_or(&path);
+            for rule in rules.iter().filter(|rule| rule.matches(relative_path)) {
+                for (name, state) in &rule.attributes {
+                    attributes.insert(name.clone(), state.clone());
+                }
+            }
+        }
+        attributes
+    }
+}
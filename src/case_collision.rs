@@ -0,0 +1,72 @@
+//! Detection of files whose paths only differ in case. They can coexist on a case sensitive
+//! filesystem, but clash when the tree is copied to a case insensitive one like on Windows or
+//! macOS, so they can be renamed apart with `--separate-case`.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Groups of files whose paths are equal when ignoring case, in the order of the file list.
+/// Files without such a partner are left out.
+pub fn case_collisions(files: &[PathBuf]) -> Vec<Vec<&Path>> {
+    let mut groups: Vec<Vec<&Path>> = Vec::new();
+    let mut group_of_key: HashMap<String, usize> = HashMap::new();
+    for file in files {
+        let key = file.to_string_lossy().to_lowercase();
+        let index = *group_of_key.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[index].push(file);
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+/// Propose renaming all but the first file of each group of colliding files in the lines of
+/// the file list, appending a number to their file stem like `readme (2).md`.
+/// `files` are the listed files of each line.
+pub fn separation_proposal(content: &str, files: &[PathBuf]) -> String {
+    let mut taken: HashSet<String> = files
+        .iter()
+        .map(|file| file.to_string_lossy().to_lowercase())
+        .collect();
+    let mut renamed: HashMap<&Path, String> = HashMap::new();
+    for group in case_collisions(files) {
+        for file in group.into_iter().skip(1) {
+            let name = (2..)
+                .map(|n| numbered_name(file, n))
+                .find(|name| {
+                    let candidate = file.with_file_name(name);
+                    !taken.contains(&candidate.to_string_lossy().to_lowercase())
+                        && !candidate.exists()
+                })
+                .expect("there are unused numbers");
+            taken.insert(file.with_file_name(&name).to_string_lossy().to_lowercase());
+            renamed.insert(file, name);
+        }
+    }
+    content
+        .lines()
+        .zip(files)
+        .map(|(line, file)| match renamed.get(file.as_path()) {
+            Some(name) => Path::new(line)
+                .with_file_name(name)
+                .to_string_lossy()
+                .to_string(),
+            None => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The file name of `file` with the number `n` appended to its stem
+fn numbered_name(file: &Path, n: usize) -> String {
+    let stem = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    match file.extension() {
+        Some(extension) => format!("{} ({}).{}", stem, n, extension.to_string_lossy()),
+        None => format!("{} ({})", stem, n),
+    }
+}
@@ -0,0 +1,232 @@
+//! The user configuration file.
+//!
+//! The file is located at `bumv/config.toml` in the platform specific configuration directory
+//! (e.g. `~/.config/bumv/config.toml` on Linux). It uses a small subset of TOML: `[section]`
+//! headers, `key = value` pairs with strings, booleans, integers and arrays of strings, and
+//! `#` comments. Options given on the command line take precedence over the configuration file.
+//!
+//! The `[paths]` section maps directory glob patterns to default flags, e.g.
+//! `"~/Pictures" = "--recursive --preview table"`, which apply when renaming in these directories.
+
+use crate::editor::split_arguments;
+use anyhow::{Context, Result};
+use directories_next::ProjectDirs;
+use globset::GlobBuilder;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A value in the configuration file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigValue {
+    String(String),
+    Bool(bool),
+    Integer(i64),
+    Array(Vec<String>),
+}
+
+/// The parsed configuration file. Keys outside of a section belong to the section `""`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigFile {
+    sections: HashMap<String, HashMap<String, ConfigValue>>,
+}
+
+impl ConfigFile {
+    /// The default location of the configuration file, if the platform has a configuration directory
+    pub fn default_path() -> Option<PathBuf> {
+        ProjectDirs::from("", "", "bumv").map(|dirs| dirs.config_dir().join("config.toml"))
+    }
+
+    /// Load the configuration file from its default location. A missing file is not an error.
+    pub fn load() -> Result<Self> {
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load_from(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the configuration file {}", path.display()))?;
+        Self::parse(&content)
+            .with_context(|| format!("Invalid configuration file {}", path.display()))
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut config = Self::default();
+        let mut section = String::new();
+        for (line_number, line) in content.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_string();
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .with_context(|| format!("Expected `key = value` in line {}", line_number + 1))?;
+            let value = parse_value(value.trim())
+                .with_context(|| format!("Invalid value in line {}", line_number + 1))?;
+            config
+                .sections
+                .entry(section.clone())
+                .or_default()
+                .insert(unquote(key.trim()).to_string(), value);
+        }
+        Ok(config)
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&ConfigValue> {
+        self.sections.get(section)?.get(key)
+    }
+
+    /// All keys and values of a section, e.g. `[lint]`
+    pub fn section(&self, section: &str) -> impl Iterator<Item = (&String, &ConfigValue)> {
+        self.sections.get(section).into_iter().flatten()
+    }
+
+    /// Get a string value of the top level section
+    pub fn get_str(&self, key: &str) -> Result<Option<&str>> {
+        match self.get("", key) {
+            None => Ok(None),
+            Some(ConfigValue::String(value)) => Ok(Some(value)),
+            Some(_) => anyhow::bail!("The configuration option {} must be a string.", key),
+        }
+    }
+
+    /// The default flags for renaming in `directory` from the `[paths]` section. A pattern applies
+    /// to the directories it matches and everything below them. If several patterns apply, the one
+    /// matching the closest directory is used. Patterns starting with `~` are relative to `home`.
+    /// Flags are given as a string, which is split like a command line, or an array of strings.
+    pub fn path_flags(&self, directory: &Path, home: Option<&Path>) -> Result<Vec<String>> {
+        // the flags of the pattern matching the closest ancestor, and the number of ancestors
+        // between the directory and the matched one
+        let mut closest: Option<(usize, Vec<String>)> = None;
+        let mut patterns: Vec<(&String, &ConfigValue)> = self.section("paths").collect();
+        // longer patterns are more specific, and win if they match the same directory
+        patterns.sort_by_key(|(pattern, _)| std::cmp::Reverse(pattern.len()));
+        for (pattern, value) in patterns {
+            let expanded = match (pattern.strip_prefix('~'), home) {
+                (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+                    format!("{}{}", home.to_string_lossy(), rest)
+                }
+                _ => pattern.clone(),
+            };
+            let glob = GlobBuilder::new(expanded.trim_end_matches('/'))
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("Invalid path pattern {}", pattern))?
+                .compile_matcher();
+            let flags = match value {
+                ConfigValue::String(flags) => split_arguments(flags),
+                ConfigValue::Array(flags) => flags.clone(),
+                _ => anyhow::bail!(
+                    "The flags for the path {} must be a string or an array of strings.",
+                    pattern
+                ),
+            };
+            let distance = directory
+                .ancestors()
+                .position(|ancestor| glob.is_match(ancestor));
+            match (distance, &closest) {
+                (Some(distance), Some((closest_distance, _))) if distance >= *closest_distance => {}
+                (Some(distance), _) => closest = Some((distance, flags)),
+                (None, _) => {}
+            }
+        }
+        Ok(closest.map(|(_, flags)| flags).unwrap_or_default())
+    }
+
+    /// Get an array of strings of the top level section
+    pub fn get_array(&self, key: &str) -> Result<&[String]> {
+        match self.get("", key) {
+            None => Ok(&[]),
+            Some(ConfigValue::Array(values)) => Ok(values),
+            Some(_) => anyhow::bail!(
+                "The configuration option {} must be an array of strings.",
+                key
+            ),
+        }
+    }
+}
+
+/// Remove a trailing comment, ignoring `#` characters inside of strings
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn parse_string(value: &str) -> Result<String> {
+    let inner = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .with_context(|| format!("Expected a quoted string, found {}", value))?;
+    Ok(inner.replace("\\\"", "\"").replace("\\\\", "\\"))
+}
+
+/// Parse a comma separated list of quoted strings, which may contain commas themselves
+fn parse_string_list(list: &str) -> Result<Vec<String>> {
+    let mut values = Vec::new();
+    let mut rest = list.trim();
+    while !rest.is_empty() {
+        anyhow::ensure!(
+            rest.starts_with('"'),
+            "Expected a quoted string, found {}",
+            rest
+        );
+        // find the closing quote, skipping escaped characters
+        let mut end = None;
+        let mut escaped = false;
+        for (i, c) in rest.char_indices().skip(1) {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => {
+                    end = Some(i);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let end = end.context("Unterminated string")?;
+        values.push(parse_string(&rest[..=end])?);
+        rest = rest[end + 1..].trim_start();
+        rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+    }
+    Ok(values)
+}
+
+fn parse_value(value: &str) -> Result<ConfigValue> {
+    match value {
+        "true" => Ok(ConfigValue::Bool(true)),
+        "false" => Ok(ConfigValue::Bool(false)),
+        _ if value.starts_with('[') => {
+            let inner = value
+                .strip_prefix('[')
+                .and_then(|v| v.strip_suffix(']'))
+                .context("Unterminated array")?;
+            Ok(ConfigValue::Array(parse_string_list(inner)?))
+        }
+        _ if value.starts_with('"') => Ok(ConfigValue::String(parse_string(value)?)),
+        _ => value
+            .parse()
+            .map(ConfigValue::Integer)
+            .with_context(|| format!("Unsupported value {}", value)),
+    }
+}
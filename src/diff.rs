@@ -0,0 +1,155 @@
+//! Unified diffs of the editable file list.
+//!
+//! As each line of the file list corresponds to exactly one file, the original and the edited
+//! list always have the same number of lines, and changed lines stay at their position.
+//! This makes both creating and applying diffs straightforward.
+
+use anyhow::{Context, Result};
+use std::fmt;
+
+/// Number of unchanged lines shown around changes
+const CONTEXT_LINES: usize = 3;
+
+/// Name of the file list in diff headers
+const BUFFER_NAME: &str = "bumv-file-list";
+
+/// A line of a unified diff, distinguished by its kind so that frontends can color it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    /// The name of the original file list, `--- a/...`
+    OriginalHeader(String),
+    /// The name of the edited file list, `+++ b/...`
+    EditedHeader(String),
+    /// A hunk header of the lines from `start` (1-based), which is the same in both lists
+    Hunk {
+        start: usize,
+        length: usize,
+    },
+    Context(String),
+    Removed(String),
+    Added(String),
+}
+
+impl fmt::Display for DiffLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffLine::OriginalHeader(name) => write!(f, "--- a/{}", name),
+            DiffLine::EditedHeader(name) => write!(f, "+++ b/{}", name),
+            DiffLine::Hunk { start, length } => {
+                write!(f, "@@ -{},{} +{},{} @@", start, length, start, length)
+            }
+            DiffLine::Context(line) => write!(f, " {}", line),
+            DiffLine::Removed(line) => write!(f, "-{}", line),
+            DiffLine::Added(line) => write!(f, "+{}", line),
+        }
+    }
+}
+
+/// Create the lines of a unified diff between the original and the edited file list
+pub fn unified_diff_lines(original: &[String], edited: &[String]) -> Vec<DiffLine> {
+    let changed: Vec<usize> = (0..original.len().min(edited.len()))
+        .filter(|&i| original[i] != edited[i])
+        .collect();
+    let mut output = vec![
+        DiffLine::OriginalHeader(BUFFER_NAME.to_string()),
+        DiffLine::EditedHeader(BUFFER_NAME.to_string()),
+    ];
+    // group changed lines into hunks, merging changes whose context overlaps
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &i in &changed {
+        let start = i.saturating_sub(CONTEXT_LINES);
+        let end = (i + CONTEXT_LINES + 1).min(original.len());
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    for (start, end) in hunks {
+        output.push(DiffLine::Hunk {
+            start: start + 1,
+            length: end - start,
+        });
+        // within a hunk, consecutive changed lines are shown as a block of removals
+        // followed by a block of additions
+        let mut i = start;
+        while i < end {
+            if original[i] == edited[i] {
+                output.push(DiffLine::Context(original[i].clone()));
+                i += 1;
+            } else {
+                let block_end = (i..end).find(|&j| original[j] == edited[j]).unwrap_or(end);
+                output.extend((i..block_end).map(|j| DiffLine::Removed(original[j].clone())));
+                output.extend((i..block_end).map(|j| DiffLine::Added(edited[j].clone())));
+                i = block_end;
+            }
+        }
+    }
+    output
+}
+
+/// Parse a hunk header like `@@ -1,3 +1,3 @@`, returning the start line (1-based)
+/// and the number of lines of the original
+fn parse_hunk_header(line: &str) -> Option<(usize, usize)> {
+    let original_range = line.strip_prefix("@@ -")?.split_whitespace().next()?;
+    let (start, length) = match original_range.split_once(',') {
+        Some((start, length)) => (start.parse().ok()?, length.parse().ok()?),
+        None => (original_range.parse().ok()?, 1),
+    };
+    Some((start, length))
+}
+
+/// Apply a unified diff to the original file list, returning the edited file list.
+/// The diff has to match the original exactly.
+pub fn apply_unified_diff(original: &str, diff: &str) -> Result<String> {
+    let original: Vec<&str> = original.lines().collect();
+    let mut result: Vec<String> = Vec::new();
+    let mut position = 0;
+    let mut lines = diff.lines().enumerate().peekable();
+    while let Some((line_number, line)) = lines.next() {
+        if !line.starts_with("@@") {
+            // headers and other text outside of hunks
+            continue;
+        }
+        let (start, length) = parse_hunk_header(line)
+            .with_context(|| format!("Invalid hunk header in line {}", line_number + 1))?;
+        let start = start.saturating_sub(1);
+        anyhow::ensure!(
+            start >= position && start + length <= original.len(),
+            "The hunk in line {} does not match the file list.",
+            line_number + 1
+        );
+        result.extend(original[position..start].iter().map(|l| l.to_string()));
+        position = start;
+        let hunk_end = start + length;
+        while position < hunk_end {
+            let (line_number, line) = lines
+                .next()
+                .with_context(|| format!("The hunk in line {} is incomplete.", line_number + 1))?;
+            let marker = line.chars().next();
+            let content = line.get(1..).unwrap_or_default();
+            match marker {
+                Some(' ') | Some('-') => {
+                    anyhow::ensure!(
+                        original[position] == content,
+                        "Line {} of the diff does not match the file list: expected {}, found {}.",
+                        line_number + 1,
+                        original[position],
+                        content
+                    );
+                    if marker == Some(' ') {
+                        result.push(content.to_string());
+                    }
+                    position += 1;
+                }
+                Some('+') => result.push(content.to_string()),
+                _ => anyhow::bail!("Unexpected line {} in hunk: {}", line_number + 1, line),
+            }
+        }
+        // additions at the end of a hunk
+        while let Some((_, line)) = lines.next_if(|(_, line)| line.starts_with('+')) {
+            result.push(line[1..].to_string());
+        }
+    }
+    result.extend(original[position..].iter().map(|l| l.to_string()));
+    Ok(result.join("\n"))
+}
@@ -0,0 +1,224 @@
+//! Diagnosis of the environment bumv runs in, printed with `--doctor`.
+//! It answers the common questions why the editor does not open or why nothing is renamed
+//! without having to reproduce the problem.
+
+use crate::config_file::ConfigFile;
+use crate::editor::{find_executable, TempFileEditor};
+use crate::fs_capabilities::{probe_writable, FilesystemCapabilities};
+use crate::json::JsonValue;
+use crate::BumvConfiguration;
+use std::env;
+use std::io::IsTerminal;
+
+/// The result of checking one aspect of the environment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    /// What was checked, e.g. `editor`
+    pub name: &'static str,
+    /// Whether bumv works as expected in this aspect
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, ok: bool, detail: String) -> Self {
+        Self { name, ok, detail }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::object([
+            ("check", self.name.into()),
+            ("ok", self.ok.into()),
+            ("detail", self.detail.as_str().into()),
+        ])
+    }
+}
+
+/// Check the editor, the terminal, the configuration file, and the filesystem and the files
+/// of the base path
+pub fn diagnose(config: &BumvConfiguration, config_file: &ConfigFile) -> Vec<Check> {
+    let base_directory = config.base_directory();
+    let mut checks = vec![check_editor(config)];
+    let stdin_is_terminal = std::io::stdin().is_terminal();
+    let stdout_is_terminal = std::io::stdout().is_terminal();
+    checks.push(if !stdin_is_terminal && !stdout_is_terminal {
+        Check::new(
+            "terminal",
+            false,
+            "Neither stdin nor stdout is a terminal, so bumv only prints the file list instead \
+             of opening an editor. Run it directly in a terminal to rename files."
+                .to_string(),
+        )
+    } else if !stdout_is_terminal {
+        Check::new(
+            "terminal",
+            false,
+            "stdout is not a terminal, so the editor and the confirmation prompt may not be \
+             visible."
+                .to_string(),
+        )
+    } else if !stdin_is_terminal {
+        Check::new(
+            "terminal",
+            false,
+            "stdin is not a terminal, so the confirmation prompt cannot be answered.".to_string(),
+        )
+    } else {
+        Check::new(
+            "terminal",
+            true,
+            "stdin and stdout are terminals".to_string(),
+        )
+    });
+    checks.push(match ConfigFile::default_path() {
+        Some(path) if path.exists() => {
+            let home = directories_next::BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+            let directory = std::fs::canonicalize(base_directory)
+                .unwrap_or_else(|_| base_directory.to_path_buf());
+            match config_file.path_flags(&directory, home.as_deref()) {
+                Ok(flags) if flags.is_empty() => Check::new(
+                    "configuration",
+                    true,
+                    format!("loaded {}", path.to_string_lossy()),
+                ),
+                Ok(flags) => Check::new(
+                    "configuration",
+                    true,
+                    format!(
+                        "loaded {}, which adds the flags {} for this directory",
+                        path.to_string_lossy(),
+                        flags.join(" ")
+                    ),
+                ),
+                Err(e) => Check::new("configuration", false, format!("{:#}", e)),
+            }
+        }
+        Some(path) => Check::new(
+            "configuration",
+            true,
+            format!("no file at {}, using the defaults", path.to_string_lossy()),
+        ),
+        None => Check::new(
+            "configuration",
+            true,
+            "no configuration directory on this platform, using the defaults".to_string(),
+        ),
+    });
+    checks.push(match FilesystemCapabilities::probe(base_directory) {
+        Ok(capabilities) => {
+            let illegal: String = capabilities.illegal_characters.iter().collect();
+            Check::new(
+                "filesystem",
+                true,
+                format!(
+                    "{}, names of up to {} bytes, {}{}",
+                    if capabilities.case_sensitive {
+                        "case sensitive"
+                    } else if capabilities.case_preserving {
+                        "case insensitive"
+                    } else {
+                        "case insensitive without preserving case"
+                    },
+                    capabilities.max_name_length,
+                    if illegal.is_empty() {
+                        "no illegal characters".to_string()
+                    } else {
+                        format!("illegal characters {}", illegal)
+                    },
+                    match (capabilities.trailing_spaces, capabilities.trailing_dots) {
+                        (true, true) => "",
+                        (false, true) => ", spaces at the end of names are removed",
+                        (true, false) => ", dots at the end of names are removed",
+                        (false, false) => ", spaces and dots at the end of names are removed",
+                    }
+                ),
+            )
+        }
+        Err(e) => Check::new(
+            "filesystem",
+            false,
+            format!(
+                "probing {} failed: {:#}",
+                base_directory.to_string_lossy(),
+                e
+            ),
+        ),
+    });
+    checks.push(match probe_writable(base_directory) {
+        Ok(()) => Check::new(
+            "permissions",
+            true,
+            format!(
+                "files can be created in {}",
+                base_directory.to_string_lossy()
+            ),
+        ),
+        Err(e) => Check::new(
+            "permissions",
+            false,
+            format!(
+                "files cannot be created in {}: {}",
+                base_directory.to_string_lossy(),
+                e
+            ),
+        ),
+    });
+    checks.push(match config.file_list() {
+        Ok(files) if !files.is_empty() => Check::new(
+            "files",
+            true,
+            format!(
+                "{} files are listed, observing the ignore sources {}",
+                files.len(),
+                config.ignore_sources()
+            ),
+        ),
+        Ok(_) => Check::new(
+            "files",
+            false,
+            "no files are listed. Use --explain-ignores PATH to find out why a file is missing."
+                .to_string(),
+        ),
+        Err(e) => Check::new("files", false, format!("{:#}", e)),
+    });
+    checks
+}
+
+/// Check that the editor is found, as configured by the environment or --remote
+fn check_editor(config: &BumvConfiguration) -> Check {
+    let editor = if config.remote {
+        match TempFileEditor::remote_from_environment() {
+            Ok(editor) => editor,
+            Err(e) => return Check::new("editor", false, format!("{:#}", e)),
+        }
+    } else {
+        TempFileEditor::from_environment(config.use_vscode)
+    };
+    match find_executable(editor.program(), env::var_os("PATH")) {
+        Some(path) => Check::new(
+            "editor",
+            true,
+            format!("{} ({})", editor.program(), path.to_string_lossy()),
+        ),
+        None => Check::new(
+            "editor",
+            false,
+            format!(
+                "{} is not found. Set BUMV_EDITOR, VISUAL or EDITOR to an installed editor, or use --use-vscode.",
+                editor.program()
+            ),
+        ),
+    }
+}
+
+/// Render the checks as a report with a line per check
+pub fn report(checks: &[Check]) -> String {
+    checks
+        .iter()
+        .map(|check| {
+            let status = if check.ok { "ok" } else { "problem" };
+            format!("{:<8} {}: {}", status, check.name, check.detail)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,111 @@
+//! Export of the renaming plan as a Graphviz DOT graph.
+//!
+//! Nodes are the paths involved in the renaming, edges are the renaming steps labelled with their
+//! position in the plan. Requested renamings that could not be performed directly are drawn as
+//! dashed edges, and the files of cycles that had to be broken with a temporary file are red.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Create a DOT graph of the requested `mapping` and the `steps` performing it
+pub fn plan_dot(mapping: &[(PathBuf, PathBuf)], steps: &[(PathBuf, PathBuf)]) -> String {
+    let requested: HashMap<&Path, &Path> = mapping
+        .iter()
+        .map(|(old, new)| (old.as_path(), new.as_path()))
+        .collect();
+    let performed: HashSet<(&Path, &Path)> = steps
+        .iter()
+        .map(|(old, new)| (old.as_path(), new.as_path()))
+        .collect();
+    let indirect: Vec<(&Path, &Path)> = mapping
+        .iter()
+        .map(|(old, new)| (old.as_path(), new.as_path()))
+        .filter(|step| !performed.contains(step))
+        .collect();
+
+    let mut lines = vec![
+        "digraph bumv {".to_string(),
+        "  rankdir=LR;".to_string(),
+        "  node [shape=box];".to_string(),
+    ];
+    // follow the requested renamings from each indirect one to find the cycles
+    let mut in_cycle: HashSet<&Path> = HashSet::new();
+    for &(old, new) in &indirect {
+        let mut cycle = vec![old, new];
+        let mut current = new;
+        while let Some(&next) = requested.get(current) {
+            if next == old {
+                lines.push(format!(
+                    "  // cycle: {}",
+                    cycle
+                        .iter()
+                        .map(|path| path.to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(" -> ")
+                        + " -> "
+                        + &old.to_string_lossy()
+                ));
+                in_cycle.extend(cycle);
+                break;
+            }
+            if cycle.len() > requested.len() {
+                break;
+            }
+            cycle.push(next);
+            current = next;
+        }
+    }
+
+    let sources: HashSet<&Path> = requested.keys().copied().collect();
+    let targets: HashSet<&Path> = requested.values().copied().collect();
+    let nodes: BTreeSet<&Path> = steps
+        .iter()
+        .flat_map(|(old, new)| [old.as_path(), new.as_path()])
+        .chain(mapping.iter().map(|(old, _)| old.as_path()))
+        .collect();
+    for node in nodes {
+        let mut attributes = Vec::new();
+        if !sources.contains(node) && !targets.contains(node) {
+            attributes.push("style=dashed".to_string());
+            attributes.push(format!(
+                "label={}",
+                quote(&format!("{} (temporary)", node.to_string_lossy()))
+            ));
+        }
+        if in_cycle.contains(node) {
+            attributes.push("color=red".to_string());
+        }
+        lines.push(node_line(node, &attributes));
+    }
+    for (i, (old, new)) in steps.iter().enumerate() {
+        lines.push(format!(
+            "  {} -> {} [label=\"{}\"];",
+            quote(&old.to_string_lossy()),
+            quote(&new.to_string_lossy()),
+            i + 1
+        ));
+    }
+    for (old, new) in indirect {
+        lines.push(format!(
+            "  {} -> {} [style=dashed, color=gray, label=\"requested\"];",
+            quote(&old.to_string_lossy()),
+            quote(&new.to_string_lossy())
+        ));
+    }
+    lines.push("}".to_string());
+    lines.join("\n") + "\n"
+}
+
+fn node_line(node: &Path, attributes: &[String]) -> String {
+    let node = quote(&node.to_string_lossy());
+    if attributes.is_empty() {
+        format!("  {};", node)
+    } else {
+        format!("  {} [{}];", node, attributes.join(", "))
+    }
+}
+
+/// Quote an identifier or label, escaping quotes and backslashes
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
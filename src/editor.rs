@@ -0,0 +1,457 @@
+//! Editing the file list in the user's editor.
+
+use anyhow::{Context, Result};
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus};
+use std::thread;
+use std::time::Duration;
+use tempfile::TempPath;
+
+/// VS Code's command. On Windows, this resolves to the `code.cmd` shim on the PATH.
+const VS_CODE: &str = "code";
+
+/// Default prefix of the temp file name
+const TEMP_FILE_PREFIX: &str = "bumv_";
+
+/// Environment variables naming the editor, in order of precedence
+const EDITOR_VARIABLES: [&str; 3] = ["BUMV_EDITOR", "VISUAL", "EDITOR"];
+
+pub struct TempFileEditor {
+    /// The program to run, either a name to look up on the PATH or a path
+    program: String,
+    /// Arguments given in the editor command, e.g. `-w` in `subl -w`
+    args: Vec<String>,
+    /// Validates the edited content against the original content whenever the file is saved
+    validator: Option<Box<LiveValidator>>,
+    /// Prefix of the temp file name, e.g. to include the session ID
+    temp_file_prefix: String,
+    /// Path of the file to edit instead of a temp file, e.g. given by an editor plugin
+    buffer_file: Option<PathBuf>,
+}
+
+/// Validates the edited file list, given the original and the edited content,
+/// returning a message for each problem found
+type LiveValidator = dyn Fn(&str, &str) -> Vec<String>;
+
+/// Interval in which the temp file is checked for changes during live validation
+const LIVE_VALIDATION_INTERVAL: Duration = Duration::from_millis(250);
+
+impl TempFileEditor {
+    /// Determine the editor from the command line and the environment.
+    /// `BUMV_EDITOR` takes precedence over `VISUAL` and `EDITOR`, defaulting to VS Code.
+    pub fn from_environment(use_vscode: bool) -> Self {
+        Self::resolve(use_vscode, |name| env::var(name).ok())
+    }
+
+    /// Determine the running editor instance bumv was started from, so that the file list
+    /// is opened in it instead of a nested editor in its terminal
+    pub fn remote_from_environment() -> Result<Self> {
+        Self::resolve_remote(|name| env::var(name).ok())
+    }
+
+    /// Determine the running editor instance using `lookup` to read environment variables
+    fn resolve_remote(lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let (program, args) = if let Some(server) = lookup("NVIM") {
+            // nvim itself cannot wait for a remote buffer to be closed, neovim-remote can
+            (
+                "nvr",
+                vec![
+                    "--servername".to_string(),
+                    server,
+                    "--remote-wait".to_string(),
+                ],
+            )
+        } else if lookup("INSIDE_EMACS").is_some() {
+            // emacsclient waits until the buffer is closed by default
+            ("emacsclient", Vec::new())
+        } else {
+            anyhow::bail!(
+                "No running editor found. --remote works in terminals of Neovim, with \
+                 neovim-remote installed, and of Emacs."
+            );
+        };
+        Ok(Self {
+            program: program.to_string(),
+            args,
+            validator: None,
+            temp_file_prefix: TEMP_FILE_PREFIX.to_string(),
+            buffer_file: None,
+        })
+    }
+
+    /// Determine the editor using `lookup` to read environment variables
+    fn resolve(use_vscode: bool, lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let command = if use_vscode {
+            None
+        } else {
+            EDITOR_VARIABLES
+                .iter()
+                .filter_map(|name| lookup(name))
+                .find(|editor| !editor.trim().is_empty())
+        };
+        match command {
+            Some(command) => Self::parse_command(&command, |program| {
+                find_executable(program, env::var_os("PATH")).is_some()
+            }),
+            // default to VS code
+            None => Self {
+                program: VS_CODE.to_string(),
+                args: Vec::new(),
+                validator: None,
+                temp_file_prefix: TEMP_FILE_PREFIX.to_string(),
+                buffer_file: None,
+            },
+        }
+    }
+
+    /// Split an editor command into the program and its arguments.
+    /// Unquoted program paths containing spaces, as common on Windows
+    /// (e.g. `C:\Program Files\Sublime Text\subl.exe -w`), are recognized
+    /// by checking which prefix of the command `is_executable`.
+    fn parse_command(command: &str, is_executable: impl Fn(&str) -> bool) -> Self {
+        let command = command.trim();
+        if !command.starts_with(['"', '\'']) {
+            let word_ends = command
+                .char_indices()
+                .filter(|(_, c)| c.is_whitespace())
+                .map(|(i, _)| i)
+                .chain(std::iter::once(command.len()));
+            let mut prefixes: Vec<usize> = word_ends.collect();
+            prefixes.dedup();
+            if let Some(&end) = prefixes
+                .iter()
+                .rev()
+                .find(|&&end| command[..end].contains(' ') && is_executable(&command[..end]))
+            {
+                return Self {
+                    program: command[..end].to_string(),
+                    args: split_arguments(&command[end..]),
+                    validator: None,
+                    temp_file_prefix: TEMP_FILE_PREFIX.to_string(),
+                    buffer_file: None,
+                };
+            }
+        }
+        let mut words = split_arguments(command).into_iter();
+        Self {
+            program: words.next().unwrap_or_default(),
+            args: words.collect(),
+            validator: None,
+            temp_file_prefix: TEMP_FILE_PREFIX.to_string(),
+            buffer_file: None,
+        }
+    }
+
+    /// Validate the file list whenever it is saved while the editor is open. The results are
+    /// written to a companion file next to the temp file, with the extension `.errors`.
+    pub fn with_live_validation(
+        self,
+        validator: impl Fn(&str, &str) -> Vec<String> + 'static,
+    ) -> Self {
+        Self {
+            validator: Some(Box::new(validator)),
+            ..self
+        }
+    }
+
+    /// Use the given prefix for the name of the temp file
+    pub fn with_temp_file_prefix(self, temp_file_prefix: String) -> Self {
+        Self {
+            temp_file_prefix,
+            ..self
+        }
+    }
+
+    /// Edit the file list in the given file instead of a temp file. The file is overwritten
+    /// with the file list and removed after editing, like a temp file.
+    pub fn with_buffer_file(self, buffer_file: PathBuf) -> Self {
+        Self {
+            buffer_file: Some(buffer_file),
+            ..self
+        }
+    }
+
+    /// Whether the editor is VS Code, which needs the `--wait` flag
+    fn is_vscode(&self) -> bool {
+        Path::new(&self.program)
+            .file_stem()
+            .map(|stem| stem == VS_CODE)
+            .unwrap_or(false)
+    }
+
+    /// The column at which the editor breaks long lines into several lines, if it is known to.
+    /// pico always does, nano with `--breaklonglines`, at `--fill` or 8 columns less than the
+    /// terminal width.
+    pub fn wrapping_width(&self, terminal_width: usize) -> Option<usize> {
+        let stem = Path::new(&self.program).file_stem()?.to_string_lossy();
+        let has_arg = |names: &[&str]| self.args.iter().any(|arg| names.contains(&arg.as_str()));
+        let wraps = match stem.as_ref() {
+            "pico" => true,
+            "nano" => has_arg(&["-b", "--breaklonglines"]) && !has_arg(&["-w", "--nowrap"]),
+            _ => false,
+        };
+        if !wraps {
+            return None;
+        }
+        // e.g. `-r 72`, `-r72` or `--fill=72`. A negative fill is relative to the terminal width.
+        let fill = self.args.iter().enumerate().find_map(|(i, arg)| {
+            let value = match arg.as_str() {
+                "-r" | "--fill" => self.args.get(i + 1)?.as_str(),
+                _ => arg
+                    .strip_prefix("--fill=")
+                    .or_else(|| arg.strip_prefix("-r"))?,
+            };
+            value.parse::<isize>().ok()
+        });
+        Some(match fill {
+            Some(fill) if fill > 0 => fill.unsigned_abs(),
+            Some(fill) => terminal_width.saturating_sub(fill.unsigned_abs()),
+            None => terminal_width.saturating_sub(8),
+        })
+    }
+
+    /// The program run to edit the file list
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Ensure the editor can be found, so users don't edit in vain or get a raw spawn error
+    pub fn ensure_available(&self) -> Result<()> {
+        if find_executable(&self.program, env::var_os("PATH")).is_none() {
+            anyhow::bail!(
+                "Editor '{}' not found. Set BUMV_EDITOR, VISUAL or EDITOR to an installed editor, or use --use-vscode.",
+                self.program
+            );
+        }
+        Ok(())
+    }
+
+    /// Write the content of the temp file the user will edit
+    fn write_editable_temp_file(&self, content: &str) -> Result<TempPath> {
+        if let Some(buffer_file) = &self.buffer_file {
+            // the buffer file is removed after editing, so an existing file must not be used
+            let mut file = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(buffer_file)
+                .with_context(|| {
+                    format!(
+                        "Failed to create the buffer file {}, which must not exist yet",
+                        buffer_file.display()
+                    )
+                })?;
+            let temp_path = TempPath::from_path(buffer_file);
+            file.write_all(content.as_bytes()).with_context(|| {
+                format!("Failed to write the buffer file {}", buffer_file.display())
+            })?;
+            return Ok(temp_path);
+        }
+        let mut temp_file = tempfile::Builder::new()
+            .prefix(&self.temp_file_prefix)
+            .tempfile()?;
+        write!(temp_file, "{}", content)?;
+        Ok(temp_file.into_temp_path())
+    }
+
+    /// Let the user edit the temp file
+    fn let_user_edit_temp_file(&self, temp_file: &Path, original: &str) -> Result<()> {
+        let temp_path = temp_file
+            .to_str()
+            .context("Failed to convert path to string")?;
+        // spawn the resolved path, so that `.cmd` and `.bat` shims are found on Windows
+        let program = find_executable(&self.program, env::var_os("PATH"))
+            .unwrap_or_else(|| PathBuf::from(&self.program));
+        let mut command = Command::new(program);
+        command.args(&self.args);
+        // VS code needs the --wait flag to wait for the user to close the editor
+        if self.is_vscode() && !self.args.iter().any(|arg| arg == "--wait" || arg == "-w") {
+            command.arg("--wait");
+        }
+        let errors_path = errors_file_path(temp_file);
+        if self.validator.is_some() {
+            eprintln!(
+                "Validation results are written to {} whenever you save.",
+                errors_path.to_string_lossy()
+            );
+        }
+        let mut child = command
+            .arg(temp_path)
+            .spawn()
+            .with_context(|| format!("Failed to start the editor '{}'", self.program))?;
+        let status = match &self.validator {
+            Some(validator) => {
+                let result = watch_and_validate(&mut child, temp_file, &errors_path, |edited| {
+                    validator(original, edited)
+                });
+                let _ = fs::remove_file(&errors_path);
+                result?
+            }
+            None => child.wait()?,
+        };
+        anyhow::ensure!(status.success(), "Editor exited with an error");
+        Ok(())
+    }
+
+    /// Read the temp file the user edited and parse the content
+    fn read_temp_file(temp_file: &Path) -> Result<String> {
+        let mut content = String::new();
+        File::open(temp_file)?.read_to_string(&mut content)?;
+        Ok(content)
+    }
+
+    pub fn edit(&self, content: String) -> Result<String> {
+        let temp_file = self.write_editable_temp_file(&content)?;
+        self.let_user_edit_temp_file(&temp_file, &content)?;
+        Self::read_temp_file(&temp_file)
+    }
+}
+
+/// The companion file of the temp file containing live validation results
+fn errors_file_path(temp_path: &Path) -> PathBuf {
+    let mut path = temp_path.as_os_str().to_owned();
+    path.push(".errors");
+    PathBuf::from(path)
+}
+
+/// Wait for the editor to exit, validating the temp file whenever it changes
+/// and writing the results to `errors_path`
+fn watch_and_validate(
+    child: &mut Child,
+    temp_path: &Path,
+    errors_path: &Path,
+    validate: impl Fn(&str) -> Vec<String>,
+) -> Result<ExitStatus> {
+    let modification = |path: &Path| {
+        fs::metadata(path)
+            .and_then(|metadata| Ok((metadata.modified()?, metadata.len())))
+            .ok()
+    };
+    let mut last_modification = modification(temp_path);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        thread::sleep(LIVE_VALIDATION_INTERVAL);
+        let current_modification = modification(temp_path);
+        if current_modification == last_modification {
+            continue;
+        }
+        last_modification = current_modification;
+        if let Ok(edited) = fs::read_to_string(temp_path) {
+            let errors = validate(&edited);
+            let report = if errors.is_empty() {
+                "No problems found.".to_string()
+            } else {
+                errors.join("\n")
+            };
+            // validation is a convenience, so failing to write the results is not an error
+            let _ = fs::write(errors_path, report + "\n");
+        }
+    }
+}
+
+/// Split a command line into words separated by whitespace. Single or double quotes group words
+/// containing whitespace. Backslashes are kept as they are, as they separate paths on Windows.
+pub(crate) fn split_arguments(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+/// Find an executable by name in the directories of `path_variable`.
+/// Names containing a path separator are checked directly.
+pub(crate) fn find_executable(name: &str, path_variable: Option<OsString>) -> Option<PathBuf> {
+    let candidates = |path: PathBuf| -> Vec<PathBuf> {
+        // on Windows, executables can be called without their extension
+        if cfg!(windows) && path.extension().is_none() {
+            ["exe", "cmd", "bat", "com"]
+                .iter()
+                .map(|extension| path.with_extension(extension))
+                .chain(std::iter::once(path.clone()))
+                .collect()
+        } else {
+            vec![path]
+        }
+    };
+    if Path::new(name).components().count() > 1 {
+        return candidates(PathBuf::from(name))
+            .into_iter()
+            .find(|path| path.is_file());
+    }
+    env::split_paths(&path_variable?)
+        .flat_map(|directory| candidates(directory.join(name)))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::TempFileEditor;
+    use std::path::Path;
+
+    /// Resolve the editor name for the given environment
+    pub fn resolve_editor_name(use_vscode: bool, environment: &[(&str, &str)]) -> String {
+        TempFileEditor::resolve(use_vscode, |name| {
+            environment
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        })
+        .program
+    }
+
+    /// Determine the running editor instance for the given environment
+    pub fn resolve_remote_editor(
+        environment: &[(&str, &str)],
+    ) -> anyhow::Result<(String, Vec<String>)> {
+        let editor = TempFileEditor::resolve_remote(|name| {
+            environment
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        })?;
+        Ok((editor.program, editor.args))
+    }
+
+    /// Split an editor command into the program and its arguments,
+    /// treating the given paths as the only existing executables
+    pub fn parse_editor_command(command: &str, executables: &[&Path]) -> (String, Vec<String>) {
+        let editor = TempFileEditor::parse_command(command, |program| {
+            executables.contains(&Path::new(program))
+        });
+        (editor.program, editor.args)
+    }
+
+    /// The column at which the given editor command breaks long lines, if it is known to
+    pub fn editor_wrapping_width(command: &str, terminal_width: usize) -> Option<usize> {
+        TempFileEditor::parse_command(command, |_| false).wrapping_width(terminal_width)
+    }
+
+    /// Edit `content` in `buffer_file` with the given editor command
+    pub fn edit_in_buffer_file(
+        command: &str,
+        buffer_file: &Path,
+        content: &str,
+    ) -> anyhow::Result<String> {
+        TempFileEditor::parse_command(command, |_| false)
+            .with_buffer_file(buffer_file.to_path_buf())
+            .edit(content.to_string())
+    }
+}
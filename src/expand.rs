@@ -0,0 +1,77 @@
+//! Expansion of placeholders in edited file names, e.g. `{stem}_{mtime:%Y%m%d}.{ext}`.
+//!
+//! Placeholders refer to the original file of the edited line, so pasting the same pattern into
+//! many lines names each file according to its own metadata.
+
+use anyhow::{Context, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Format of `{mtime}` without an explicit format
+const DEFAULT_TIME_FORMAT: &str = "%Y%m%d";
+
+/// Whether a line contains placeholders to expand
+pub fn has_placeholders(line: &str) -> bool {
+    line.contains('{')
+}
+
+/// Expand the placeholders in `line` for the file `original`:
+/// `{name}`, `{stem}`, `{ext}` and `{mtime}` or `{mtime:FORMAT}` with a strftime format.
+pub fn expand_placeholders(line: &str, original: &Path) -> Result<String> {
+    let mut expanded = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let end = start
+            + rest[start..]
+                .find('}')
+                .with_context(|| format!("Unterminated placeholder in {}", line))?;
+        let placeholder = &rest[start + 1..end];
+        let (name, argument) = match placeholder.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (placeholder, None),
+        };
+        let lossy = |value: Option<&std::ffi::OsStr>| {
+            value
+                .map(|value| value.to_string_lossy().to_string())
+                .unwrap_or_default()
+        };
+        match (name, argument) {
+            ("name", None) => expanded.push_str(&lossy(original.file_name())),
+            ("stem", None) => expanded.push_str(&lossy(original.file_stem())),
+            ("ext", None) => expanded.push_str(&lossy(original.extension())),
+            ("mtime", format) => expanded.push_str(
+                &format_modification_time(original, format.unwrap_or(DEFAULT_TIME_FORMAT))
+                    .with_context(|| format!("Failed to expand {}", line))?,
+            ),
+            _ => anyhow::bail!("Unknown placeholder {{{}}} in {}", placeholder, line),
+        }
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Format the modification time of a file in local time with a strftime format like `%Y-%m`
+pub fn format_modification_time(path: &Path, format: &str) -> Result<String> {
+    let modified: DateTime<Local> = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| format!("Failed to read the modification time of {}", path.display()))?
+        .into();
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    anyhow::ensure!(
+        !items.contains(&Item::Error),
+        "Invalid time format {}",
+        format
+    );
+    let mut formatted = String::new();
+    write!(
+        formatted,
+        "{}",
+        modified.format_with_items(items.into_iter())
+    )?;
+    Ok(formatted)
+}
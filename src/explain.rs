@@ -0,0 +1,208 @@
+//! Explanation why a path is listed or not, similar to `git check-ignore -v`.
+//!
+//! The ignore rules are evaluated like the walker of the `ignore` crate does: `.ignore` files
+//! take precedence over `.gitignore` files, which take precedence over `.git/info/exclude` and
+//! the global gitignore file. Within each kind, the file in the deepest directory wins.
+//! Git ignore files only apply within a repository, and not across the root of a nested one.
+
+use crate::IgnoreSources;
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder, Glob};
+use ignore::Match;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// A rule of an ignore file that matched a path
+struct Rule {
+    source: PathBuf,
+    line: Option<usize>,
+    pattern: String,
+    whitelist: bool,
+}
+
+impl Rule {
+    fn from_glob(glob: &Glob) -> Self {
+        let source = glob.from().map(Path::to_path_buf).unwrap_or_default();
+        // the line number is not kept by the matcher, so it is looked up in the file
+        let line = fs::read_to_string(&source).ok().and_then(|content| {
+            content
+                .lines()
+                .position(|line| line.trim() == glob.original())
+                .map(|i| i + 1)
+        });
+        Self {
+            source,
+            line,
+            pattern: glob.original().to_string(),
+            whitelist: glob.is_whitelist(),
+        }
+    }
+}
+
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}:{}", self.source.display(), line, self.pattern),
+            None => write!(f, "{}:{}", self.source.display(), self.pattern),
+        }
+    }
+}
+
+/// The ignore files that apply to the entries of a directory
+struct IgnoreFiles {
+    /// `.ignore` files, deepest directory first
+    ignore: Vec<Gitignore>,
+    /// `.gitignore` files up to the root of the repository, deepest directory first
+    git_ignore: Vec<Gitignore>,
+    git_exclude: Option<Gitignore>,
+    git_global: Option<Gitignore>,
+}
+
+impl IgnoreFiles {
+    fn for_directory(directory: &Path, sources: IgnoreSources) -> Self {
+        let mut ignore = Vec::new();
+        let mut git_ignore = Vec::new();
+        let mut git_exclude = None;
+        let mut in_repository = false;
+        for ancestor in directory.ancestors() {
+            ignore.extend(load(ancestor, &ancestor.join(".ignore")));
+            if !in_repository {
+                git_ignore.extend(load(ancestor, &ancestor.join(".gitignore")));
+                if ancestor.join(".git").exists() {
+                    in_repository = true;
+                    git_exclude = load(ancestor, &ancestor.join(".git/info/exclude"))
+                        .filter(|_| sources.git_exclude);
+                }
+            }
+        }
+        if !in_repository {
+            // like git, the walker only observes .gitignore files within repositories
+            git_ignore.clear();
+        }
+        let git_global = (in_repository && sources.git_global)
+            .then(|| Gitignore::global().0)
+            .filter(|global| !global.is_empty());
+        Self {
+            ignore,
+            git_ignore,
+            git_exclude,
+            git_global,
+        }
+    }
+
+    /// The rule deciding whether `path` is ignored, if any
+    fn matched(&self, path: &Path, is_dir: bool) -> Option<Rule> {
+        first_match(&self.ignore, path, is_dir)
+            .or_else(|| first_match(&self.git_ignore, path, is_dir))
+            .or_else(|| first_match(&self.git_exclude, path, is_dir))
+            .or_else(|| first_match(&self.git_global, path, is_dir))
+    }
+}
+
+/// The rule of the first matcher matching `path`
+fn first_match<'a>(
+    matchers: impl IntoIterator<Item = &'a Gitignore>,
+    path: &Path,
+    is_dir: bool,
+) -> Option<Rule> {
+    matchers
+        .into_iter()
+        .find_map(|matcher| match matcher.matched(path, is_dir) {
+            Match::None => None,
+            Match::Ignore(glob) | Match::Whitelist(glob) => Some(Rule::from_glob(glob)),
+        })
+}
+
+/// Load an ignore file of `directory`, if it exists
+fn load(directory: &Path, file: &Path) -> Option<Gitignore> {
+    if !file.is_file() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(directory);
+    builder.add(file);
+    builder.build().ok()
+}
+
+/// Explain why `path` is listed when renaming in `base_path`, or why it is not
+pub fn explain_ignores(
+    path: &Path,
+    base_path: &Path,
+    sources: IgnoreSources,
+    recursive: bool,
+    include_bumv_files: bool,
+) -> Result<String> {
+    let absolute =
+        fs::canonicalize(path).with_context(|| format!("Failed to find {}", path.display()))?;
+    let base = fs::canonicalize(base_path)
+        .with_context(|| format!("Failed to find {}", base_path.display()))?;
+    let shown = path.to_string_lossy();
+    let Ok(relative) = absolute.strip_prefix(&base) else {
+        return Ok(format!(
+            "{} is not listed, as it is not below the base path {}.",
+            shown,
+            base_path.display()
+        ));
+    };
+    let components: Vec<Component> = relative.components().collect();
+    if components.is_empty() || absolute.is_dir() {
+        return Ok(format!(
+            "{} is not listed, as only files are listed, not directories.",
+            shown
+        ));
+    }
+    if !recursive && components.len() > 1 {
+        return Ok(format!(
+            "{} is not listed, as it is in a subdirectory. Use --recursive to list it.",
+            shown
+        ));
+    }
+    if !sources.ignore_files {
+        return Ok(format!(
+            "{} is listed, as ignore files are not observed (--no-ignore).",
+            shown
+        ));
+    }
+    let mut whitelisted = None;
+    let mut current = base.clone();
+    for (i, component) in components.iter().enumerate() {
+        let parent = current.clone();
+        current.push(component);
+        let is_file = i + 1 == components.len();
+        let what = if is_file {
+            shown.to_string()
+        } else {
+            format!("its directory {}", current.display())
+        };
+        if component.as_os_str().to_string_lossy().starts_with('.') {
+            return Ok(format!(
+                "{} is not listed, as {} is hidden. Use --no-ignore to list it.",
+                shown, what
+            ));
+        }
+        match IgnoreFiles::for_directory(&parent, sources).matched(&current, !is_file) {
+            Some(rule) if rule.whitelist => whitelisted = Some(rule),
+            Some(rule) => {
+                return Ok(format!(
+                    "{} is not listed, as {} is ignored by {}",
+                    shown, what, rule
+                ))
+            }
+            None => {}
+        }
+    }
+    let is_bumv_artifact = absolute
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(crate::logs::is_bumv_artifact);
+    if is_bumv_artifact && !include_bumv_files {
+        return Ok(format!(
+            "{} is not listed, as it is a log file or failure report of bumv. \
+             Use --include-bumv-files to list it.",
+            shown
+        ));
+    }
+    Ok(match whitelisted {
+        Some(rule) => format!("{} is listed, as it is re-included by {}", shown, rule),
+        None => format!("{} is listed, as no ignore rule matches it.", shown),
+    })
+}
@@ -0,0 +1,351 @@
+//! Detection of the capabilities of the filesystem bumv operates on.
+//!
+//! Instead of assuming POSIX semantics on a filesystem like ext4, bumv probes the base path
+//! before renaming, in a temporary directory that is removed again. Dry runs only inspect the
+//! existing files. The results are used to validate edited file names and to split case-only
+//! renamings.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tempfile::Builder;
+
+/// Characters that are rejected by some filesystems (e.g. FAT, NTFS, SMB shares).
+/// Each of them is probed individually.
+const CANDIDATE_ILLEGAL_CHARACTERS: [char; 8] = ['<', '>', ':', '"', '\\', '|', '?', '*'];
+
+/// Upper bound for the file name length probe. No common filesystem allows longer names.
+const MAX_PROBED_NAME_LENGTH: usize = 1024;
+
+/// Capabilities of the filesystem containing the base path of a renaming operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilesystemCapabilities {
+    /// `true` if `file` and `FILE` are distinct files.
+    pub case_sensitive: bool,
+    /// `true` if names keep the case they were created with, which case insensitive
+    /// filesystems like NTFS and APFS do, but FAT without long file names does not.
+    pub case_preserving: bool,
+    /// Maximum length of a single file name component in bytes.
+    pub max_name_length: usize,
+    /// Characters that cannot be part of a file name, in addition to the path separator.
+    pub illegal_characters: Vec<char>,
+    /// `true` if names ending in a space are kept as they are. Windows filesystems remove
+    /// trailing spaces, so such a name refers to the file without them.
+    pub trailing_spaces: bool,
+    /// `true` if names ending in a dot are kept as they are, which Windows filesystems do not.
+    pub trailing_dots: bool,
+}
+
+impl Default for FilesystemCapabilities {
+    /// The capabilities of a typical POSIX filesystem like ext4.
+    fn default() -> Self {
+        Self {
+            case_sensitive: true,
+            case_preserving: true,
+            max_name_length: 255,
+            illegal_characters: Vec::new(),
+            trailing_spaces: true,
+            trailing_dots: true,
+        }
+    }
+}
+
+impl FilesystemCapabilities {
+    /// Probe the filesystem containing `path` by creating a few small files in a temporary
+    /// directory in it, which is removed again. The result is kept for later sessions of the
+    /// same process. If no directory can be created in it, e.g. in a read-only directory, the
+    /// capabilities of a typical POSIX filesystem are assumed.
+    pub fn probe(path: &Path) -> Result<Self> {
+        static PROBED: OnceLock<Mutex<HashMap<PathBuf, FilesystemCapabilities>>> = OnceLock::new();
+        let probed = PROBED.get_or_init(Default::default);
+        if let Some(capabilities) = probed.lock().unwrap().get(path) {
+            return Ok(capabilities.clone());
+        }
+        let Ok(probe_directory) = Builder::new().prefix(".bumv-probe").tempdir_in(path) else {
+            return Ok(Self::default());
+        };
+        let dir = probe_directory.path();
+        let capabilities = Self {
+            case_sensitive: probe_case_sensitivity(dir)?,
+            case_preserving: probe_case_preservation(dir),
+            max_name_length: probe_max_name_length(dir),
+            illegal_characters: CANDIDATE_ILLEGAL_CHARACTERS
+                .iter()
+                .copied()
+                .filter(|c| !can_create(dir, &format!("probe-{}", c)))
+                .collect(),
+            trailing_spaces: probe_trailing_character(dir, ' '),
+            trailing_dots: probe_trailing_character(dir, '.'),
+        };
+        probed
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), capabilities.clone());
+        Ok(capabilities)
+    }
+
+    /// Detect the capabilities of the filesystem containing `path` without modifying it, as far
+    /// as the existing files and the operating system tell: whether a listed name can be found
+    /// with a different case, and the maximum name length. Everything else is assumed to be
+    /// like on a typical POSIX filesystem.
+    pub fn detect(path: &Path) -> Self {
+        Self {
+            case_sensitive: detect_case_sensitivity(path),
+            max_name_length: detect_max_name_length(path),
+            ..Self::default()
+        }
+    }
+
+    /// The capabilities that hold on all of the given filesystems, e.g. of the directories of
+    /// files given on the command line: names are only valid if they are valid on each of them.
+    pub fn common(all: impl IntoIterator<Item = Self>) -> Self {
+        all.into_iter()
+            .reduce(|common, other| Self {
+                case_sensitive: common.case_sensitive && other.case_sensitive,
+                case_preserving: common.case_preserving && other.case_preserving,
+                max_name_length: common.max_name_length.min(other.max_name_length),
+                illegal_characters: common
+                    .illegal_characters
+                    .iter()
+                    .chain(
+                        other
+                            .illegal_characters
+                            .iter()
+                            .filter(|c| !common.illegal_characters.contains(c)),
+                    )
+                    .copied()
+                    .collect(),
+                trailing_spaces: common.trailing_spaces && other.trailing_spaces,
+                trailing_dots: common.trailing_dots && other.trailing_dots,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Check whether a single file name (not a whole path) can be represented on this filesystem.
+    pub fn validate_file_name(&self, name: &str) -> Result<()> {
+        anyhow::ensure!(
+            name.len() <= self.max_name_length,
+            "The file name {} exceeds the maximum file name length of {} bytes.",
+            name,
+            self.max_name_length
+        );
+        anyhow::ensure!(
+            self.trailing_spaces || !name.ends_with(' '),
+            "The file name {:?} ends with a space, which this filesystem removes.",
+            name
+        );
+        anyhow::ensure!(
+            self.trailing_dots || !name.ends_with('.'),
+            "The file name {:?} ends with a dot, which this filesystem removes.",
+            name
+        );
+        if let Some(c) = name.chars().find(|c| self.illegal_characters.contains(c)) {
+            anyhow::bail!(
+                "The file name {} contains the character '{}', which is not allowed on this filesystem.",
+                name,
+                c
+            );
+        }
+        Ok(())
+    }
+
+    /// Check whether two paths refer to the same file on this filesystem,
+    /// e.g. `file.txt` and `FILE.txt` on a case insensitive filesystem.
+    pub fn paths_equivalent(&self, a: &Path, b: &Path) -> bool {
+        self.normalized(a) == self.normalized(b)
+    }
+
+    /// A representation of `path` that is equal for all paths referring to the same file.
+    pub fn normalized(&self, path: &Path) -> String {
+        if self.case_sensitive {
+            path.to_string_lossy().to_string()
+        } else {
+            path.to_string_lossy().to_lowercase()
+        }
+    }
+}
+
+/// Check whether files can be created and renamed in `dir`, which fails e.g. on read-only mounts
+/// or without write permission.
+pub fn probe_writable(dir: &Path) -> std::io::Result<()> {
+    Builder::new()
+        .prefix(".bumv-write-probe")
+        .tempfile_in(dir)
+        .map(drop)
+}
+
+/// Check whether files can be created and renamed in `dir` without modifying it, which fails
+/// e.g. on read-only mounts or without write permission.
+#[cfg(unix)]
+pub fn check_writable(dir: &Path) -> std::io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    let path = CString::new(dir.as_os_str().as_bytes())
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidInput, error))?;
+    // SAFETY: `path` is a valid NUL-terminated string that outlives the call
+    if unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Check whether files can be created and renamed in `dir` without modifying it, which fails
+/// e.g. for read-only directories.
+#[cfg(not(unix))]
+pub fn check_writable(dir: &Path) -> std::io::Result<()> {
+    if fs::metadata(dir)?.permissions().readonly() {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "The directory is read-only",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Try to create a file with the given name in `dir`, removing it again afterwards.
+fn can_create(dir: &Path, name: &str) -> bool {
+    let path = dir.join(name);
+    if path.exists() {
+        return true;
+    }
+    match File::create(&path) {
+        Ok(_) => {
+            let _ = fs::remove_file(&path);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+fn probe_case_sensitivity(dir: &Path) -> Result<bool> {
+    let probe = Builder::new().prefix("case-probe").tempfile_in(dir)?;
+    let name = probe
+        .path()
+        .file_name()
+        .map(|name| name.to_string_lossy().to_uppercase())
+        .unwrap_or_default();
+    Ok(!dir.join(name).exists())
+}
+
+/// Find the maximum file name length by binary search.
+fn probe_max_name_length(dir: &Path) -> usize {
+    let prefix = "len-probe-";
+    let name_of_length = |length: usize| format!("{}{}", prefix, "x".repeat(length - prefix.len()));
+    let (mut low, mut high) = (prefix.len() + 1, MAX_PROBED_NAME_LENGTH);
+    if can_create(dir, &name_of_length(high)) {
+        return high;
+    }
+    // invariant: a name of length `low` can be created, a name of length `high` cannot
+    while high - low > 1 {
+        let middle = (low + high) / 2;
+        if can_create(dir, &name_of_length(middle)) {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    low
+}
+
+/// Check whether a name keeps its case, by looking for it in the directory listing.
+/// If the probe file cannot be created, the case is assumed to be kept.
+fn probe_case_preservation(dir: &Path) -> bool {
+    let Ok(probe) = Builder::new().prefix("Case-Probe").tempfile_in(dir) else {
+        return true;
+    };
+    let Some(name) = probe.path().file_name() else {
+        return true;
+    };
+    fs::read_dir(dir).is_ok_and(|entries| {
+        entries
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.file_name() == name)
+    })
+}
+
+/// Check whether a name ending in `character` is kept, by looking for it in the directory
+/// listing. If the probe file cannot be created, the name is assumed to be kept.
+fn probe_trailing_character(dir: &Path, character: char) -> bool {
+    let name = format!("trailing-probe{}", character);
+    let path = dir.join(&name);
+    if File::create(&path).is_err() {
+        return true;
+    }
+    let kept = fs::read_dir(dir).is_ok_and(|entries| {
+        entries
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.file_name() == name.as_str())
+    });
+    let _ = fs::remove_file(&path);
+    kept
+}
+
+/// Check whether a listed name that changes with its case can be found with a different case,
+/// which is the case on case insensitive filesystems. Without such a name, the filesystem is
+/// assumed to be case sensitive.
+fn detect_case_sensitivity(dir: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return true;
+    };
+    let names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    let Some(other_case) = names
+        .iter()
+        .map(|name| {
+            let upper = name.to_uppercase();
+            if upper == *name {
+                name.to_lowercase()
+            } else {
+                upper
+            }
+        })
+        .find(|other| !names.contains(other))
+    else {
+        return true;
+    };
+    fs::symlink_metadata(dir.join(other_case)).is_err()
+}
+
+/// The maximum file name length reported by the operating system.
+#[cfg(unix)]
+fn detect_max_name_length(dir: &Path) -> usize {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return FilesystemCapabilities::default().max_name_length;
+    };
+    // SAFETY: `path` is a valid NUL-terminated string that outlives the call
+    let length = unsafe { libc::pathconf(path.as_ptr(), libc::_PC_NAME_MAX) };
+    usize::try_from(length).unwrap_or(FilesystemCapabilities::default().max_name_length)
+}
+
+#[cfg(not(unix))]
+fn detect_max_name_length(_dir: &Path) -> usize {
+    FilesystemCapabilities::default().max_name_length
+}
+
+impl std::fmt::Display for FilesystemCapabilities {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let yes_no = |value: bool| if value { "yes" } else { "no" };
+        writeln!(f, "case sensitive:     {}", yes_no(self.case_sensitive))?;
+        writeln!(f, "case preserving:    {}", yes_no(self.case_preserving))?;
+        writeln!(f, "max name length:    {}", self.max_name_length)?;
+        writeln!(
+            f,
+            "illegal characters: {}",
+            self.illegal_characters
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        )?;
+        writeln!(f, "trailing spaces:    {}", yes_no(self.trailing_spaces))?;
+        write!(f, "trailing dots:      {}", yes_no(self.trailing_dots))
+    }
+}
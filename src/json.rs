@@ -0,0 +1,365 @@
+//! A minimal JSON representation used for machine readable output, and for reading plans
+//! written with `--plan-out` back in.
+
+use anyhow::Result;
+use std::fmt;
+
+/// A JSON value. Objects keep the insertion order of their keys.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Create an object from key value pairs
+    pub fn object<K: Into<String>>(entries: impl IntoIterator<Item = (K, JsonValue)>) -> Self {
+        JsonValue::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.into(), value))
+                .collect(),
+        )
+    }
+
+    /// Parse a JSON document
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            position: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            parser.fail("end of the document")?;
+        }
+        Ok(value)
+    }
+
+    /// The value of `key` if this is an object containing it
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(entries) => entries
+                .iter()
+                .find(|(entry_key, _)| entry_key == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// A recursive descent parser over the characters of a JSON document
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn fail<T>(&self, expected: &str) -> Result<T> {
+        match self.chars.get(self.position) {
+            Some(c) => anyhow::bail!(
+                "Invalid JSON: expected {} at character {}, found {:?}.",
+                expected,
+                self.position + 1,
+                c
+            ),
+            None => anyhow::bail!("Invalid JSON: expected {}, found the end.", expected),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    /// Consume `expected` if it comes next, after whitespace
+    fn consume(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        let found = self.peek() == Some(expected);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        if !self.consume(expected) {
+            self.fail(&format!("{:?}", expected))?;
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<JsonValue> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => {
+                self.position += 1;
+                let mut entries = Vec::new();
+                if !self.consume('}') {
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        self.expect(':')?;
+                        entries.push((key, self.value()?));
+                        if !self.consume(',') {
+                            break;
+                        }
+                    }
+                    self.expect('}')?;
+                }
+                Ok(JsonValue::Object(entries))
+            }
+            Some('[') => {
+                self.position += 1;
+                let mut values = Vec::new();
+                if !self.consume(']') {
+                    loop {
+                        values.push(self.value()?);
+                        if !self.consume(',') {
+                            break;
+                        }
+                    }
+                    self.expect(']')?;
+                }
+                Ok(JsonValue::Array(values))
+            }
+            Some('"') => Ok(JsonValue::String(self.string()?)),
+            Some('-' | '0'..='9') => {
+                let start = self.position;
+                while matches!(self.peek(), Some('-' | '+' | '.' | 'e' | 'E' | '0'..='9')) {
+                    self.position += 1;
+                }
+                let number: String = self.chars[start..self.position].iter().collect();
+                match number.parse() {
+                    Ok(number) => Ok(JsonValue::Number(number)),
+                    Err(_) => {
+                        self.position = start;
+                        self.fail("a number")
+                    }
+                }
+            }
+            _ => {
+                for (literal, value) in [
+                    ("null", JsonValue::Null),
+                    ("true", JsonValue::Bool(true)),
+                    ("false", JsonValue::Bool(false)),
+                ] {
+                    if self.consume_literal(literal) {
+                        return Ok(value);
+                    }
+                }
+                self.fail("a value")
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        if self.peek() != Some('"') {
+            return self.fail("a string");
+        }
+        self.position += 1;
+        let mut value = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return self.fail("the end of the string");
+            };
+            self.position += 1;
+            match c {
+                '"' => return Ok(value),
+                '\\' => {
+                    let Some(escaped) = self.peek() else {
+                        return self.fail("an escape sequence");
+                    };
+                    self.position += 1;
+                    value.push(match escaped {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => self.unicode_escape()?,
+                        _ => {
+                            self.position -= 1;
+                            return self.fail("an escape sequence");
+                        }
+                    });
+                }
+                c => value.push(c),
+            }
+        }
+    }
+
+    /// The character of a `\u` escape, combining surrogate pairs
+    fn unicode_escape(&mut self) -> Result<char> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            if !self.consume_literal("\\u") {
+                return self.fail("the low surrogate of a surrogate pair");
+            }
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return self.fail("the low surrogate of a surrogate pair");
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        match char::from_u32(code) {
+            Some(c) => Ok(c),
+            None => self.fail("a valid unicode escape"),
+        }
+    }
+
+    fn consume_literal(&mut self, literal: &str) -> bool {
+        let end = self.position + literal.chars().count();
+        let found = self
+            .chars
+            .get(self.position..end)
+            .is_some_and(|chars| chars.iter().copied().eq(literal.chars()));
+        if found {
+            self.position = end;
+        }
+        found
+    }
+
+    fn hex4(&mut self) -> Result<u32> {
+        let digits: Option<String> = self
+            .chars
+            .get(self.position..self.position + 4)
+            .filter(|digits| digits.iter().all(char::is_ascii_hexdigit))
+            .map(|digits| digits.iter().collect());
+        match digits.and_then(|digits| u32::from_str_radix(&digits, 16).ok()) {
+            Some(code) => {
+                self.position += 4;
+                Ok(code)
+            }
+            None => self.fail("four hexadecimal digits"),
+        }
+    }
+}
+
+impl From<&str> for JsonValue {
+    fn from(value: &str) -> Self {
+        JsonValue::String(value.to_string())
+    }
+}
+
+impl From<String> for JsonValue {
+    fn from(value: String) -> Self {
+        JsonValue::String(value)
+    }
+}
+
+impl From<bool> for JsonValue {
+    fn from(value: bool) -> Self {
+        JsonValue::Bool(value)
+    }
+}
+
+impl From<usize> for JsonValue {
+    fn from(value: usize) -> Self {
+        JsonValue::Number(value as f64)
+    }
+}
+
+impl<T: Into<JsonValue>> From<Option<T>> for JsonValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(JsonValue::Null, Into::into)
+    }
+}
+
+impl<T: Into<JsonValue>> From<Vec<T>> for JsonValue {
+    fn from(values: Vec<T>) -> Self {
+        JsonValue::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+fn write_json_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for JsonValue {
+    /// Serialize the value as compact JSON
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Bool(value) => write!(f, "{}", value),
+            JsonValue::Number(value) if value.is_finite() => write!(f, "{}", value),
+            JsonValue::Number(_) => write!(f, "null"),
+            JsonValue::String(value) => write_json_string(f, value),
+            JsonValue::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            JsonValue::Object(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_json_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
@@ -0,0 +1,4091 @@
+//! A bulk file renaming utility that uses your editor as its UI.
+//!
+//! The `bumv` binary is a thin wrapper around [`run`]. Other programs can plan and perform
+//! renamings the same way: parse a [`BumvConfiguration`], create a [`RenamingRequest`] with a
+//! function producing the edited file list, turn it into a [`RenamingPlan`] and execute it.
+//!
+//! ```no_run
+//! use bumv::{BumvConfiguration, RenamingPlan, RenamingRequest};
+//!
+//! let config = BumvConfiguration::from_arguments(["bumv", "--yes", "photos"])?;
+//! let request = RenamingRequest::try_new(config, |list| Ok(list.replace("IMG_", "holiday_")))?;
+//! let plan = RenamingPlan::try_new(request)?;
+//! print!("{}", plan.preview());
+//! plan.execute(|_| true, &mut Vec::new())?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! Instead of the text of [`RenamingPlan::preview`], a frontend can draw its own preview from
+//! the [`render::Segment`]s of [`RenamingPlan::segments`].
+
+use anyhow::{Context, Result};
+use attributes::AttrFilter;
+use config_file::ConfigFile;
+use diff::DiffLine;
+use editor::TempFileEditor;
+use fs_capabilities::FilesystemCapabilities;
+pub use json::JsonValue;
+use lint::{Finding, LintSeverities, Severity};
+use listing::ListingProvider;
+use petgraph::algo::kosaraju_scc;
+use petgraph::graph::Graph;
+use petgraph::prelude::*;
+use petgraph::Directed;
+use protect::ProtectedFiles;
+use render::{Change, DirectorySummary, LeavingSummary, Segment};
+use renumber::RenumberTemplate;
+use session::SessionId;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
+use structopt::clap::ErrorKind as ClapErrorKind;
+use structopt::StructOpt;
+use transfer::SecurityContext;
+pub use transfer::{Sandbox, TransferOptions};
+use undo::FileState;
+
+mod archive;
+mod attributes;
+mod case_collision;
+mod config_file;
+mod diff;
+mod doctor;
+mod dot;
+mod editor;
+mod expand;
+mod explain;
+mod fs_capabilities;
+mod json;
+mod lint;
+mod listing;
+mod logs;
+mod mapping_file;
+mod plan_file;
+mod protect;
+pub mod render;
+mod renumber;
+mod session;
+mod substitute;
+mod sync;
+mod table;
+mod tour;
+mod transfer;
+mod undo;
+mod walk;
+
+/// Creates the edited file list from the original one, e.g. by letting the user edit it
+type EditFunction = dyn Fn(String) -> Result<String>;
+
+/// Exit code when the user declined to perform the renaming
+const EXIT_CODE_ABORTED: i32 = 2;
+
+/// Exit code of a dry run if files would have been renamed
+const EXIT_CODE_WOULD_RENAME: i32 = 3;
+
+/// Maximum number of directories listed per kind in the summary of the confirmation prompt
+const SUMMARY_DIRECTORIES: usize = 10;
+
+/// Number of files leaving the base path from which the move needs to be confirmed separately
+const LEAVING_CONFIRMATION_FILES: usize = 100;
+
+/// Total size of the files leaving the base path from which the move needs to be confirmed
+/// separately, as it may fill up another volume
+const LEAVING_CONFIRMATION_BYTES: u64 = 1 << 30;
+
+/// Version of the format of failure reports, increased on incompatible changes. Reports shared
+/// the version of log files up to version 2.
+const FAILURE_REPORT_FORMAT_VERSION: u32 = 2;
+
+/// The answer assumed when the confirmation prompt is answered by just pressing Enter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum ConfirmDefault {
+    #[default]
+    Yes,
+    No,
+}
+
+impl FromStr for ConfirmDefault {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "yes" | "y" => Ok(ConfirmDefault::Yes),
+            "no" | "n" => Ok(ConfirmDefault::No),
+            _ => anyhow::bail!("Invalid confirmation default {}, expected yes or no.", s),
+        }
+    }
+}
+
+/// How the renamings are shown in the confirmation prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Preview {
+    /// One `old -> new` line per renaming
+    #[default]
+    List,
+    /// A table with aligned columns of the old and new names
+    Table,
+}
+
+impl FromStr for Preview {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "list" => Ok(Preview::List),
+            "table" => Ok(Preview::Table),
+            _ => anyhow::bail!("Invalid preview {}, expected list or table.", s),
+        }
+    }
+}
+
+/// What happens to a target that exists and is not renamed itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Overwrite {
+    /// Refuse the renaming
+    #[default]
+    Abort,
+    /// Move the existing target to the trash before renaming
+    Trash,
+}
+
+impl FromStr for Overwrite {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "abort" => Ok(Overwrite::Abort),
+            "trash" => Ok(Overwrite::Trash),
+            _ => anyhow::bail!("Invalid overwrite mode {}, expected abort or trash.", s),
+        }
+    }
+}
+
+#[derive(StructOpt, Debug, Clone, Default)]
+#[structopt(
+    name = "bumv",
+    about = "bumv (bulk move) - A bulk file renaming utility that uses your editor as its UI. Invoke the utility, edit the filenames, save the temporary file, close the editor and confirm changes."
+)]
+pub struct BumvConfiguration {
+    /// Recursively rename files in subdirectories
+    #[structopt(short, long)]
+    recursive: bool,
+    /// Do not observe ignore files
+    #[structopt(short, long)]
+    no_ignore: bool,
+    /// Allow renaming recursively in a filesystem root or, together with --no-ignore,
+    /// in the home directory
+    #[structopt(long)]
+    i_know_what_im_doing: bool,
+    /// Do not observe the global gitignore file configured by core.excludesFile
+    #[structopt(long)]
+    no_global_gitignore: bool,
+    /// Do not observe the .git/info/exclude file of the repository
+    #[structopt(long)]
+    no_git_exclude: bool,
+    /// Read the files to rename from stdin, one per line or separated by NUL bytes, instead of
+    /// listing a base path
+    #[structopt(
+        long,
+        conflicts_with_all = &[
+            "base-path", "files", "git-index", "mapping", "undo", "apply", "tour", "repeat"
+        ]
+    )]
+    stdin: bool,
+    /// List the files tracked by git below the base path instead of walking it, leaving out
+    /// files outside of a sparse checkout
+    #[structopt(
+        long,
+        visible_alias = "git-files",
+        conflicts_with = "include-empty-dirs"
+    )]
+    git_index: bool,
+    /// Do not descend into nested git repositories and submodules
+    #[structopt(long)]
+    no_cross_repo: bool,
+    /// List log files and failure reports written by bumv, which are excluded by default
+    #[structopt(long)]
+    include_bumv_files: bool,
+    /// List empty directories as well, marked with a trailing /, so that they can be renamed
+    #[structopt(long, conflicts_with = "merge-into")]
+    include_empty_dirs: bool,
+    /// Remove the empty directories whose lines are prefixed with # in the file list
+    #[structopt(
+        long,
+        requires = "include-empty-dirs",
+        conflicts_with_all = &["renumber", "confirm-per-dir"]
+    )]
+    allow_delete: bool,
+    /// Do not write a log file
+    #[structopt(long)]
+    no_log: bool,
+    /// Use VS Code as editor
+    #[structopt(short = "c", long)]
+    use_vscode: bool,
+    /// Validate the file list whenever it is saved in the editor, writing the results to a
+    /// companion `.errors` file
+    #[structopt(long)]
+    live_validation: bool,
+    /// Warn before editing if lines of the file list are longer than this many characters,
+    /// as editors wrapping long lines would break the file list
+    #[structopt(long, value_name = "COLUMNS")]
+    max_line_width: Option<usize>,
+    /// Ask for the new name of each file in the terminal, showing a preview of the file,
+    /// instead of editing the file list in an editor
+    #[structopt(
+        long = "walk",
+        conflicts_with_all = &[
+            "apply-diff", "mapping", "renumber", "abbreviate", "archive-to", "merge-into",
+            "to-root", "sub-on", "separate-case", "remote", "use-vscode"
+        ]
+    )]
+    walk_mode: bool,
+    /// Open the file list in the running Neovim or Emacs instance bumv is started from
+    #[structopt(long, conflicts_with = "use-vscode")]
+    remote: bool,
+    /// Edit the file list in this file instead of a temp file, e.g. for editor plugins.
+    /// The file must not exist yet and is removed after editing
+    #[structopt(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        conflicts_with_all = &["apply-diff", "mapping"]
+    )]
+    buffer_file: Option<PathBuf>,
+    /// Print each renaming step and created directory to stderr while renaming, and the
+    /// execution order in the preview
+    #[structopt(short, long)]
+    verbose: bool,
+    /// Print the outcome as JSON
+    #[structopt(long)]
+    json: bool,
+    /// Save the renaming plan to this file if you decline the renaming, to apply it later with
+    /// --apply
+    #[structopt(long, parse(from_os_str))]
+    save_rejected_plan: Option<PathBuf>,
+    /// Write the graph of the renaming steps to this file in Graphviz DOT format
+    #[structopt(long, value_name = "FILE", parse(from_os_str))]
+    plan_dot: Option<PathBuf>,
+    /// Write the renaming plan including temporary steps to this file as JSON instead of
+    /// renaming, e.g. to review it with other tools. Implies --dry-run
+    #[structopt(
+        long,
+        value_name = "FILE",
+        parse(from_os_str),
+        conflicts_with_all = &["in-archive", "repeat", "confirm-per-dir"]
+    )]
+    plan_out: Option<PathBuf>,
+    /// Write a Markdown report of the renaming plan to this file, e.g. to have it signed off in
+    /// a pull request or ticket
+    #[structopt(long = "report-md", value_name = "FILE", parse(from_os_str))]
+    report_md: Option<PathBuf>,
+    /// Append the performed renaming steps to this file as `old\0new\0` records,
+    /// so that mirrors and backup tools can replay them
+    #[structopt(long, value_name = "FILE", parse(from_os_str))]
+    emit_batch: Option<PathBuf>,
+    /// POST the JSON summary of each session to this URL when it finishes, e.g. to report
+    /// unattended runs to a chat or monitoring system
+    #[structopt(long, value_name = "URL")]
+    notify_webhook: Option<String>,
+    /// Answer assumed when pressing Enter at the confirmation prompt [default: yes]
+    #[structopt(long, possible_values = &["yes", "no"])]
+    confirm_default: Option<ConfirmDefault>,
+    /// Rename without asking for confirmation, e.g. in scripts without a terminal.
+    /// The renamings are printed nevertheless
+    #[structopt(short, long, conflicts_with_all = &["tour", "confirm-per-dir"])]
+    yes: bool,
+    /// Protect files matching this glob pattern from being renamed. Can be given multiple times
+    #[structopt(long, number_of_values = 1)]
+    protect: Vec<String>,
+    /// Only list files with this git attribute as given in .gitattributes, e.g. `asset`,
+    /// `kind=image`, `-diff` for unset or `!export-ignore` for unspecified. Can be given
+    /// multiple times
+    #[structopt(
+        long,
+        value_name = "ATTR",
+        number_of_values = 1,
+        parse(try_from_str = attributes::parse_condition)
+    )]
+    attr_filter: Vec<attributes::Condition>,
+    /// Print the file list as it would appear in the editor and exit
+    #[structopt(long)]
+    list: bool,
+    /// Show the renaming as a unified diff of the file list
+    #[structopt(long)]
+    diff: bool,
+    /// Show the renamings in the confirmation prompt as a list or as an aligned table.
+    /// Ignored with --diff
+    #[structopt(long, default_value = "list", possible_values = &["list", "table"])]
+    preview: Preview,
+    /// Show the size of each renamed file and the total per directory in the confirmation prompt,
+    /// e.g. to notice a large file matched by accident
+    #[structopt(long)]
+    show_sizes: bool,
+    /// Apply a unified diff of the file list (e.g. created with --diff) instead of opening an editor
+    #[structopt(long, parse(from_os_str))]
+    apply_diff: Option<PathBuf>,
+    /// Rename according to a CSV or TSV file of old and new names instead of opening an editor
+    #[structopt(
+        long,
+        value_name = "FILE",
+        parse(from_os_str),
+        conflicts_with = "apply-diff"
+    )]
+    mapping: Option<PathBuf>,
+    /// Undo the renamings recorded in a log file, unless a renamed file changed since
+    #[structopt(
+        long,
+        value_name = "LOG_FILE",
+        parse(from_os_str),
+        conflicts_with_all = &["apply-diff", "mapping", "in-archive", "repeat", "tour", "list"]
+    )]
+    undo: Option<PathBuf>,
+    /// Rename according to a plan written with --plan-out, unless a file to rename is missing or
+    /// a target exists
+    #[structopt(
+        long,
+        value_name = "FILE",
+        parse(from_os_str),
+        conflicts_with_all = &["apply-diff", "mapping", "undo", "in-archive", "repeat", "tour", "list"]
+    )]
+    apply: Option<PathBuf>,
+    /// Propose moving the files to an archive directory named by their modification time,
+    /// e.g. archive/%Y-%m, in the editor
+    #[structopt(
+        long,
+        value_name = "PATTERN",
+        conflicts_with_all = &["apply-diff", "mapping", "renumber"]
+    )]
+    archive_to: Option<String>,
+    /// Propose moving all files of the base path to the same place below this directory,
+    /// noting files that exist there already in the editor. Implies --recursive
+    #[structopt(
+        long,
+        value_name = "DIR",
+        parse(from_os_str),
+        conflicts_with_all = &["apply-diff", "mapping", "renumber", "archive-to"]
+    )]
+    merge_into: Option<PathBuf>,
+    /// List the files of this directory instead of a base path. With --to-root, the files are
+    /// listed relative to it
+    #[structopt(
+        long,
+        value_name = "DIR",
+        parse(from_os_str),
+        conflicts_with = "base-path"
+    )]
+    from_root: Option<PathBuf>,
+    /// Interpret the edited names relative to this directory instead of --from-root, e.g. to
+    /// restructure files while moving them into a new tree
+    #[structopt(
+        long,
+        value_name = "DIR",
+        parse(from_os_str),
+        requires = "from-root",
+        conflicts_with_all = &["archive-to", "merge-into", "renumber", "apply-diff", "mapping"]
+    )]
+    to_root: Option<PathBuf>,
+    /// Print the renaming plan without renaming any files. Exits with code 3 if files would be renamed
+    #[structopt(long)]
+    dry_run: bool,
+    /// Permissions of created directories as an octal number, e.g. 0755.
+    /// By default, created directories get the permissions of their parent directory
+    #[structopt(long, parse(try_from_str = parse_dir_mode))]
+    dir_mode: Option<u32>,
+    /// Keep the owner of files copied to another device and give created directories the owner
+    /// of their parent directory. Requires root privileges
+    #[structopt(long)]
+    preserve_owner: bool,
+    /// Keep the SELinux security context of files copied to another device, instead of giving
+    /// them the context of new files in their directory
+    #[structopt(long, conflicts_with = "reset-context")]
+    preserve_context: bool,
+    /// Reset the SELinux security context of files copied to another device to the default of
+    /// their new path with restorecon
+    #[structopt(long)]
+    reset_context: bool,
+    /// Refuse to move files to another device, where they would be copied instead of renamed
+    #[structopt(long, conflicts_with = "verify-copy")]
+    no_rename_across_devices: bool,
+    /// Verify files copied to another device by comparing checksums before removing the original
+    #[structopt(long)]
+    verify_copy: bool,
+    /// Do not warn if the base path is watched by a sync client like Dropbox or OneDrive
+    #[structopt(long)]
+    no_sync_warning: bool,
+    /// Remove files instead of renaming them if their target exists with identical content,
+    /// e.g. when re-running an interrupted reorganization
+    #[structopt(long)]
+    dedupe_existing: bool,
+    /// What to do if a target exists that is not renamed itself: abort, or move the existing
+    /// file to the trash before renaming, recording it in the log file
+    #[structopt(long, default_value = "abort", possible_values = &["abort", "trash"])]
+    overwrite: Overwrite,
+    /// Copy the files to their new names instead of renaming them, leaving the originals in place
+    #[structopt(
+        long,
+        conflicts_with_all = &["allow-delete", "dedupe-existing", "undo", "in-archive"]
+    )]
+    copy: bool,
+    /// Order the file list by piping it through this shell command, e.g. "sort -r"
+    #[structopt(long, value_name = "CMD")]
+    sort_cmd: Option<String>,
+    /// Propose substituting in the names of the files matching the glob pattern, e.g.
+    /// --sub-on '*.test.js' 's/\.test/.spec/'. Can be given multiple times
+    #[structopt(
+        long,
+        number_of_values = 2,
+        value_names = &["GLOB", "EXPR"],
+        conflicts_with_all = &["archive-to", "merge-into", "renumber", "apply-diff", "mapping"]
+    )]
+    sub_on: Vec<String>,
+    /// Propose renaming apart files whose paths only differ in case, e.g. before copying them
+    /// to a case insensitive filesystem
+    #[structopt(
+        long,
+        conflicts_with_all = &["archive-to", "merge-into", "renumber", "apply-diff", "mapping"]
+    )]
+    separate_case: bool,
+    /// Rename the members of this .zip or .tar archive instead of files, rewriting the archive
+    /// with the renamed members on confirmation
+    #[structopt(
+        long,
+        value_name = "FILE",
+        parse(from_os_str),
+        conflicts_with_all = &[
+            "archive-to", "merge-into", "from-root", "to-root", "renumber", "mapping", "sub-on",
+            "separate-case", "show-computed", "list", "tour", "walk-mode", "confirm-per-dir",
+            "per-component", "abbreviate", "repeat"
+        ]
+    )]
+    in_archive: Option<PathBuf>,
+    /// Print a table of the names computed by --archive-to, --sub-on or --separate-case,
+    /// marking unchanged and clashing names, and ask whether to open the editor with them
+    #[structopt(long, conflicts_with_all = &["merge-into", "apply-diff", "mapping", "list"])]
+    show_computed: bool,
+    /// Replace the directory shared by all files with § in the file list, keeping the lines short
+    /// for deep directories like network shares
+    #[structopt(long, conflicts_with_all = &["apply-diff", "mapping"])]
+    abbreviate: bool,
+    /// Do not expand placeholders like {stem} or {mtime:%Y%m%d} in edited names
+    #[structopt(long)]
+    no_expand: bool,
+    /// Reorder the lines instead of editing names, then name the files by this template
+    /// numbered in the new order, e.g. track_{n:02}. Extensions are kept
+    #[structopt(long, value_name = "TEMPLATE")]
+    renumber: Option<RenumberTemplate>,
+    /// Ask for confirmation separately for the renamings in each directory
+    #[structopt(long)]
+    confirm_per_dir: bool,
+    /// Rename each group of renamings that depend on each other on its own. If a renaming fails,
+    /// the renamings of its group are undone, and the other groups are renamed nevertheless
+    #[structopt(long)]
+    per_component: bool,
+    /// Rename in batches of this many steps, asking for confirmation before each further batch
+    #[structopt(long, value_name = "N", parse(try_from_str = parse_batch_size))]
+    confirm_every: Option<usize>,
+    /// Stop renaming once it took longer than this, e.g. 10m, reporting the pending steps in the
+    /// failure report. Units are s, m, h, d and w
+    #[structopt(long, value_name = "DURATION", parse(try_from_str = logs::parse_age))]
+    timeout: Option<Duration>,
+    /// After renaming, start a new session over the renamed files, until a session does not
+    /// rename any files
+    #[structopt(
+        long = "loop",
+        conflicts_with_all = &["apply-diff", "mapping", "dry-run", "tour", "list"]
+    )]
+    repeat: bool,
+    /// Take a guided tour renaming example files in a temporary directory
+    #[structopt(long)]
+    tour: bool,
+    /// Remove the log files of previous renamings in the base path instead of renaming
+    #[structopt(long)]
+    clean_logs: bool,
+    /// Explain why this path is listed or which ignore rule excludes it, instead of renaming
+    #[structopt(long, value_name = "PATH", parse(from_os_str))]
+    explain_ignores: Option<PathBuf>,
+    /// Check the editor, the terminal, the configuration file and the filesystem of the base
+    /// path and print a report, instead of renaming
+    #[structopt(long)]
+    doctor: bool,
+    /// Only remove log files older than this age, e.g. 30d. Units are s, m, h, d and w
+    #[structopt(long, requires = "clean-logs", parse(try_from_str = logs::parse_age))]
+    older_than: Option<Duration>,
+    /// Base path for the operation. If this is a file, only the given files are renamed
+    #[structopt(parse(from_os_str))]
+    base_path: Option<PathBuf>,
+    /// Further files to rename, e.g. expanded from a shell glob
+    #[structopt(parse(from_os_str))]
+    files: Vec<PathBuf>,
+    /// The files read from stdin with --stdin
+    #[structopt(skip)]
+    stdin_listing: Option<listing::Stdin>,
+    /// Identifies this invocation in file names and JSON output
+    #[structopt(skip)]
+    session: SessionId,
+    /// Severities of the lint rules, from the configuration file
+    #[structopt(skip)]
+    lint: LintSeverities,
+}
+
+impl BumvConfiguration {
+    /// Parse command line arguments, starting with the program name, like the `bumv` binary
+    /// does but without reading the configuration file. Fails on invalid arguments and on
+    /// `--help` and `--version`, with the message clap would print.
+    pub fn from_arguments<I, T>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        Ok(Self::from_iter_safe(args)?)
+    }
+
+    /// Parse the command line `args`, preceded by the default flags for the base path from the
+    /// `[paths]` section of the configuration file. `config` is parsed from `args` alone.
+    /// Flags given on the command line take precedence, so default flags that clash with them
+    /// are left out.
+    fn from_args_with_path_flags(
+        config: Self,
+        args: Vec<OsString>,
+        config_file: &ConfigFile,
+    ) -> Result<Self> {
+        let home = directories_next::BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+        let directory = fs::canonicalize(config.base_directory())
+            .unwrap_or_else(|_| config.base_directory().to_path_buf());
+        let flags = config_file.path_flags(&directory, home.as_deref())?;
+        if flags.is_empty() {
+            return Ok(config);
+        }
+        let (program, command_line) = args.split_first().context("Missing program name")?;
+        // each flag starts a group, followed by its values
+        let mut groups: Vec<Vec<OsString>> = Vec::new();
+        for flag in flags {
+            match groups.last_mut() {
+                Some(group) if !flag.starts_with('-') => group.push(flag.into()),
+                _ => groups.push(vec![flag.into()]),
+            }
+        }
+        let mut defaults: Vec<OsString> = Vec::new();
+        for group in groups {
+            let candidate = std::iter::once(program)
+                .chain(&defaults)
+                .chain(&group)
+                .chain(command_line);
+            match Self::clap().get_matches_from_safe(candidate) {
+                Ok(_) => defaults.extend(group),
+                // given on the command line already
+                Err(e)
+                    if matches!(
+                        e.kind,
+                        ClapErrorKind::UnexpectedMultipleUsage | ClapErrorKind::ArgumentConflict
+                    ) => {}
+                Err(e) => anyhow::bail!(
+                    "Invalid flags for {} in the configuration file: {}",
+                    directory.to_string_lossy(),
+                    e.message
+                ),
+            }
+        }
+        let config = Self::from_iter(
+            std::iter::once(program)
+                .chain(&defaults)
+                .chain(command_line),
+        );
+        if config.verbose && !defaults.is_empty() {
+            let defaults: Vec<_> = defaults.iter().map(|flag| flag.to_string_lossy()).collect();
+            eprintln!("Default flags for this path: {}", defaults.join(" "));
+        }
+        Ok(config)
+    }
+
+    /// Fill in options that were not given on the command line from the configuration file
+    fn apply_config_file(&mut self, config_file: &ConfigFile) -> Result<()> {
+        if self.confirm_default.is_none() {
+            self.confirm_default = config_file
+                .get_str("confirm-default")?
+                .map(str::parse)
+                .transpose()?;
+        }
+        // protection patterns from both sources apply
+        self.protect
+            .extend(config_file.get_array("protect")?.iter().cloned());
+        self.lint = LintSeverities::from_config_file(config_file)?;
+        Ok(())
+    }
+
+    /// The files given as arguments or read from stdin, if files instead of a base path were given
+    fn explicit_files(&self) -> Option<impl Iterator<Item = &PathBuf>> {
+        let stdin_paths = self
+            .stdin_listing
+            .as_ref()
+            .map_or(&[][..], listing::Stdin::paths);
+        if self.stdin_listing.is_none() {
+            let base_path = self.base_path.as_ref()?;
+            if self.files.is_empty() && !base_path.is_file() {
+                return None;
+            }
+        }
+        Some(
+            self.base_path
+                .iter()
+                .chain(self.files.iter())
+                .chain(stdin_paths),
+        )
+    }
+
+    /// The directory the operation is based in.
+    /// If files were given as arguments, this is the current directory.
+    fn base_directory(&self) -> &Path {
+        match (&self.from_root, &self.base_path) {
+            (Some(from_root), _) => from_root,
+            (None, Some(base_path)) if self.explicit_files().is_none() => base_path,
+            _ => Path::new("."),
+        }
+    }
+
+    /// Whether files in subdirectories are listed. Merging always includes them.
+    fn lists_recursively(&self) -> bool {
+        self.recursive || self.merge_into.is_some()
+    }
+
+    /// The directories within which files may be moved without leaving the base path,
+    /// i.e. the base path or the directories of the given files,
+    /// and the directory files are merged into or moved to with --to-root
+    fn base_directories(&self) -> Vec<&Path> {
+        let mut directories: Vec<&Path> = Vec::new();
+        match self.explicit_files() {
+            Some(files) => {
+                for directory in files.map(|file| parent_directory(file)) {
+                    if !directories.contains(&directory) {
+                        directories.push(directory);
+                    }
+                }
+            }
+            None => directories.push(self.base_directory()),
+        }
+        directories
+            .into_iter()
+            .chain(self.merge_into.as_deref())
+            .chain(self.to_root.as_deref())
+            .collect()
+    }
+
+    /// The SELinux security context of files copied to another device
+    fn security_context(&self) -> SecurityContext {
+        if self.preserve_context {
+            SecurityContext::Preserve
+        } else if self.reset_context {
+            SecurityContext::Reset
+        } else {
+            SecurityContext::Inherit
+        }
+    }
+
+    /// The ignore sources observed when listing files
+    fn ignore_sources(&self) -> IgnoreSources {
+        IgnoreSources {
+            ignore_files: !self.no_ignore,
+            git_exclude: !self.no_ignore && !self.no_git_exclude,
+            git_global: !self.no_ignore && !self.no_global_gitignore,
+        }
+    }
+
+    /// Ensure that either a base path or only existing files were given as arguments
+    fn check_arguments(&self) -> Result<()> {
+        if let Some(mut files) = self.explicit_files() {
+            anyhow::ensure!(
+                !self.repeat,
+                "--loop needs a base directory, as the given files are renamed in the first session."
+            );
+            if let Some(path) = files.find(|path| !is_renamable_file(path)) {
+                anyhow::bail!(
+                    "{} is not a file. Either give a base path or files to rename.",
+                    path.to_string_lossy()
+                );
+            }
+        } else if let Some(to_root) = self.to_root.as_ref().filter(|root| root.exists()) {
+            anyhow::ensure!(
+                to_root.is_dir(),
+                "{} is not a directory, so files cannot be moved below it.",
+                to_root.to_string_lossy()
+            );
+        }
+        if self.explicit_files().is_none() && !self.i_know_what_im_doing {
+            let home = directories_next::BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+            if let Some((base_path, contents)) = sweeping_base_path(
+                self.base_directory(),
+                home.as_deref(),
+                self.lists_recursively(),
+                self.no_ignore,
+            ) {
+                anyhow::bail!(
+                    "The base path is {}, so bumv would list {} recursively. \
+                     Use --i-know-what-im-doing if this is intended.",
+                    base_path,
+                    contents
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The source of the files to rename: the files read from stdin, the given files, the
+    /// files tracked by git, or the files found by walking the base path
+    fn listing_provider(&self) -> Box<dyn ListingProvider + '_> {
+        if let Some(stdin) = &self.stdin_listing {
+            return Box::new(stdin);
+        }
+        if let Some(files) = self.explicit_files() {
+            return Box::new(listing::ExplicitFiles {
+                files: files.collect(),
+            });
+        }
+        if self.git_index {
+            return Box::new(listing::GitIndex {
+                directory: self.base_directory(),
+                recursive: self.lists_recursively(),
+            });
+        }
+        Box::new(listing::Walker {
+            directory: self.base_directory(),
+            recursive: self.lists_recursively(),
+            sources: self.ignore_sources(),
+            no_cross_repo: self.no_cross_repo,
+            include_bumv_files: self.include_bumv_files,
+            include_empty_dirs: self.include_empty_dirs,
+        })
+    }
+
+    /// Walk the files to rename in no particular order, keeping those with the attributes
+    /// required by `--attr-filter`
+    fn walk(&self) -> Result<Vec<PathBuf>> {
+        let provider = self.listing_provider();
+        let files = provider.files()?;
+        if self.attr_filter.is_empty() {
+            return Ok(files.collect());
+        }
+        let mut filter = AttrFilter::new(&self.attr_filter);
+        Ok(files.filter(|path| filter.matches(path)).collect())
+    }
+
+    fn file_list(&self) -> Result<Vec<PathBuf>> {
+        let mut result = self.walk()?;
+        // ensure deterministic order, comparing the raw paths avoids allocating a string per path
+        result.sort_unstable_by(|a, b| a.as_os_str().cmp(b.as_os_str()));
+        Ok(result)
+    }
+
+    /// The file list in the order presented to the user, as determined by `--sort-cmd` if given
+    fn ordered_file_list(&self) -> Result<Vec<PathBuf>> {
+        let files = self.file_list()?;
+        match &self.sort_cmd {
+            Some(command) => sort_with_command(files, command),
+            None => Ok(files),
+        }
+    }
+
+    /// The file list exactly as it is presented to the user for editing
+    fn editable_file_list(&self) -> Result<String> {
+        self.check_arguments()?;
+        Ok(create_editable_temp_file_content(
+            &self.ordered_file_list()?,
+        ))
+    }
+
+    /// The file list as proposed for editing, with the names computed by --archive-to,
+    /// --merge-into, --sub-on or --separate-case. `files` are the listed files.
+    fn proposed_file_list(&self, files: &[PathBuf]) -> Result<String> {
+        let content = match (&self.archive_to, &self.merge_into, &self.to_root) {
+            (Some(pattern), _, _) => create_editable_temp_file_content(&archive_proposal(
+                files,
+                self.base_directory(),
+                pattern,
+            )?),
+            (_, Some(destination), _) => merge_proposal(files, self.base_directory(), destination)?,
+            // the roots are left out, so that the names read the same in both trees
+            (_, _, Some(_)) => create_editable_temp_file_content(
+                &files
+                    .iter()
+                    .map(|path| {
+                        path.strip_prefix(self.base_directory())
+                            .unwrap_or(path)
+                            .to_path_buf()
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            _ => create_editable_temp_file_content(files),
+        };
+        let content = if self.sub_on.is_empty() {
+            content
+        } else {
+            let substitutions = self
+                .sub_on
+                .chunks(2)
+                .map(|pair| substitute::Substitution::try_new(&pair[0], &pair[1]))
+                .collect::<Result<Vec<_>>>()?;
+            substitute::substitution_proposal(
+                &content,
+                files,
+                self.base_directory(),
+                &substitutions,
+            )
+        };
+        Ok(if self.separate_case {
+            case_collision::separation_proposal(&content, files)
+        } else {
+            content
+        })
+    }
+
+    /// A table of the listed files and their names computed by --archive-to, --sub-on or
+    /// --separate-case, marking unchanged names and names clashing with each other,
+    /// for checking the computed names without opening an editor
+    fn computed_names(&self) -> Result<String> {
+        anyhow::ensure!(
+            self.archive_to.is_some() || !self.sub_on.is_empty() || self.separate_case,
+            "--show-computed needs --archive-to, --sub-on or --separate-case to compute names."
+        );
+        self.check_arguments()?;
+        let files = self.ordered_file_list()?;
+        let content = self.proposed_file_list(&files)?;
+        let mut targets: HashMap<String, usize> = HashMap::new();
+        for line in content.lines() {
+            *targets.entry(line.to_lowercase()).or_default() += 1;
+        }
+        let rows: Vec<(String, String)> = files
+            .iter()
+            .zip(content.lines())
+            .map(|(file, line)| {
+                let file = file.to_string_lossy().to_string();
+                let computed = if targets[&line.to_lowercase()] > 1 {
+                    format!("{} (clash)", line)
+                } else if line == file {
+                    format!("{} (unchanged)", line)
+                } else {
+                    line.to_string()
+                };
+                (file, computed)
+            })
+            .collect();
+        Ok(table::table(
+            ("Original", "Computed"),
+            &rows,
+            table::terminal_width(),
+        ))
+    }
+
+    /// Whether to only print the listing instead of renaming, which is the case if
+    /// requested with `--list`, or if neither stdin nor stdout is a terminal, renaming would be
+    /// interactive and no other output was requested with `--dry-run`, `--diff` or `--json`.
+    /// This makes bumv usable as a filter in shell pipelines, while `--yes` renames in scripts.
+    fn lists_only(&self, stdin_is_terminal: bool, stdout_is_terminal: bool) -> bool {
+        self.list
+            || (!stdin_is_terminal
+                && !stdout_is_terminal
+                && !self.dry_run
+                && !self.diff
+                && !self.json
+                && self.needs_editor()
+                && !self.tour
+                && !self.yes
+                && self.in_archive.is_none())
+    }
+
+    /// The change shown in the preview for renaming `old` to `new`, with the size of the file
+    /// if requested with `--show-sizes`
+    fn rename_change(&self, old: &Path, new: &Path) -> Change {
+        if self.show_sizes {
+            Change::rename_with_size(old, new)
+        } else {
+            Change::rename(old, new)
+        }
+    }
+
+    /// Whether to plan the renaming without renaming, as requested with `--dry-run` or
+    /// `--plan-out`
+    fn is_dry_run(&self) -> bool {
+        self.dry_run || self.plan_out.is_some()
+    }
+
+    /// Whether the file list is edited in an editor, rather than by a diff, a mapping file, a
+    /// log file to undo or a plan to apply
+    fn needs_editor(&self) -> bool {
+        self.apply_diff.is_none()
+            && self.mapping.is_none()
+            && self.undo.is_none()
+            && self.apply.is_none()
+    }
+
+    /// The file list as printed with `--list`, as a JSON object if `--json` is given.
+    /// Special files are marked with their kind as in the editor, except in JSON.
+    fn listing(&self) -> Result<String> {
+        let listing = self.editable_file_list()?;
+        if self.json {
+            let files: Vec<&str> = listing.lines().collect();
+            Ok(JsonValue::object([("files", files.into())]).to_string())
+        } else {
+            Ok(mark_special_files(&listing, &self.ordered_file_list()?, ""))
+        }
+    }
+}
+
+/// Whether bumv renames the file at `path`, i.e. it is a regular file or a special file like a
+/// FIFO, which is renamed but never copied
+fn is_renamable_file(path: &Path) -> bool {
+    path.is_file() || transfer::special_file_kind(path).is_some()
+}
+
+/// Separates the name of a special file from its kind in the file list, e.g. `pipe\t# FIFO`
+const SPECIAL_FILE_MARK: &str = "\t# ";
+
+/// Whether `path` is a directory without any entries, not following symlinks
+fn is_empty_directory(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir())
+        && fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_none())
+}
+
+/// Whether renaming in `base_path` would sweep over an entire system or home directory.
+/// Filesystem roots are refused when listing recursively, the home directory only when
+/// ignore files are not observed either. Returns descriptions of the base path and its contents.
+fn sweeping_base_path(
+    base_path: &Path,
+    home: Option<&Path>,
+    recursive: bool,
+    no_ignore: bool,
+) -> Option<(&'static str, &'static str)> {
+    if !recursive {
+        return None;
+    }
+    let base_path = fs::canonicalize(base_path).ok()?;
+    if base_path.parent().is_none() {
+        return Some(("a filesystem root", "the entire filesystem"));
+    }
+    let home = home.and_then(|home| fs::canonicalize(home).ok());
+    if no_ignore && home.is_some_and(|home| home == base_path) {
+        return Some((
+            "your home directory",
+            "all your files, including ignored ones,",
+        ));
+    }
+    None
+}
+
+/// Order files by piping the list through a shell command, which has to output the same files
+fn sort_with_command(files: Vec<PathBuf>, command: &str) -> Result<Vec<PathBuf>> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut child = Command::new(shell)
+        .args([flag, command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run the sort command {}", command))?;
+    let input = create_editable_temp_file_content(&files) + "\n";
+    let mut stdin = child
+        .stdin
+        .take()
+        .context("Failed to open stdin of the sort command")?;
+    // write in a separate thread, so that a command streaming its output cannot block
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    // a command that does not read all of its input is fine, as long as its output is complete
+    let _ = writer.join();
+    anyhow::ensure!(
+        output.status.success(),
+        "The sort command {} failed with {}",
+        command,
+        output.status
+    );
+    let sorted = parse_temp_file_content(String::from_utf8_lossy(&output.stdout).to_string());
+    let mut expected: Vec<&PathBuf> = files.iter().collect();
+    let mut actual: Vec<&PathBuf> = sorted.iter().collect();
+    expected.sort_unstable();
+    actual.sort_unstable();
+    anyhow::ensure!(
+        expected == actual,
+        "The sort command {} must output each listed file exactly once.",
+        command
+    );
+    Ok(sorted)
+}
+
+/// The validated renamings of a request, ordered into steps that can be performed one after
+/// another without overwriting files
+pub struct RenamingPlan {
+    request: RenamingRequest,
+    /// Directories that need to be created before renaming, parents before their children
+    directories: Vec<PathBuf>,
+    /// Steps grouped by volume, or by group of dependent steps with --per-component,
+    /// see `partition_by_volume`
+    steps: Vec<(PathBuf, PathBuf)>,
+    /// Number of steps of each volume
+    volumes: Vec<usize>,
+    /// Renamings whose target exists with identical content (`--dedupe-existing`).
+    /// Their sources are removed before the steps are performed.
+    duplicates: Vec<(PathBuf, PathBuf)>,
+    /// Existing targets moved to the trash before the steps are performed (`--overwrite trash`)
+    trashed: Vec<PathBuf>,
+    /// Findings of the lint rules with severity `warn`
+    warnings: Vec<Finding>,
+    /// The folder watched by a sync client the files are renamed in, if any
+    watched_folder: Option<sync::WatchedFolder>,
+}
+
+/// Break cycles in the rename mapping by temporarily renaming files if necessary,
+/// and finds a conflict-free ordering of the renaming steps.
+pub fn break_cycles_and_fix_ordering(
+    renames: &[(PathBuf, PathBuf)],
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    // The algorithm views the renaming mappings as a directed graph.
+    // It finds all cycles in a single pass over the strongly connected components of the graph.
+    // As every file is renamed at most once and targets are unique, each component with more than
+    // one node is a simple cycle, which is broken by temporarily renaming one of its files.
+    // Then, the steps are ordered so that each file is renamed only after its target was renamed away.
+    // Among the steps that can be executed next, the one with the smallest source path comes first,
+    // so that identical inputs always result in identical plans.
+    // Then, the missing renames of temporary files are added to the end of the list.
+
+    // For example a -> b, b -> a is a cycle, so a and b form a strongly connected component.
+    // The algorithm will choose one of the files in the cycle, for example a.
+    // It will remove the edge a -> b and add the edge a -> a.tmp instead.
+    // It will remember new renaming step of a.tmp -> b by storing it in a list of deferred steps.
+    // Now the remaining graph b -> a, a -> a.tmp is cycle free.
+    // The step a -> a.tmp can be executed first, as a.tmp does not exist, which frees a for b -> a.
+    // To complete the list of renamings, the deferred step a.tmp -> b is added to the end of the list,
+    // resulting in a -> a.tmp, b -> a, a.tmp -> b.
+
+    // The nodes borrow the paths of the mapping, only temporary files are owned by the graph.
+    // Paths are cloned once when the steps are created.
+    let mut graph = Graph::<Cow<Path>, (), Directed>::new();
+    let mut nodes = HashMap::<&Path, NodeIndex>::with_capacity(renames.len() * 2);
+    let mut temp_file_counter = 0;
+    let mut deferred_steps = Vec::new();
+
+    // Create the initial graph
+    for (old, new) in renames {
+        let node_old = *nodes
+            .entry(old)
+            .or_insert_with(|| graph.add_node(Cow::Borrowed(old)));
+        let node_new = *nodes
+            .entry(new)
+            .or_insert_with(|| graph.add_node(Cow::Borrowed(new)));
+        graph.add_edge(node_old, node_new, ());
+    }
+
+    // Break each cycle once
+    // Kosaraju's algorithm is used as petgraph's implementation of Tarjan's algorithm is recursive,
+    // which overflows the stack on long cycles.
+    for component in kosaraju_scc(&graph) {
+        if component.len() < 2 {
+            continue;
+        }
+        // break the cycle at its smallest path, independently of the order of the mapping
+        let node_idx = *component
+            .iter()
+            .min_by_key(|&&idx| &graph[idx])
+            .expect("components are not empty");
+        let source_file = &graph[node_idx];
+        let temp_file = unused_temp_file_name(source_file, &mut temp_file_counter)?;
+        // Remove the original renaming, add the renaming of the source file to the temporary file
+        // and defer the renaming of the temporary file to its target.
+        let edges: Vec<_> = graph.edges(node_idx).collect();
+        let edge_causing_cycle = edges[0];
+        let target = edge_causing_cycle.target();
+        let target_path = graph[target].to_path_buf();
+        eprintln!(
+            "Breaking a cycle by temporarily renaming {} to {}.",
+            source_file.to_string_lossy(),
+            temp_file.to_string_lossy()
+        );
+        graph.remove_edge(edge_causing_cycle.id());
+        deferred_steps.push((temp_file.clone(), target_path));
+        let temp_file_node = graph.add_node(Cow::Owned(temp_file));
+        graph.update_edge(node_idx, temp_file_node, ());
+    }
+
+    // Turn the graph back into a list of renaming steps. A step is ready once its target is not
+    // renamed itself or was renamed away already. Ready steps are taken in the order of their paths.
+    let target = |idx: NodeIndex| graph.neighbors(idx).next();
+    let mut ready: BTreeSet<(&Path, NodeIndex)> = graph
+        .node_indices()
+        .filter(|&idx| target(idx).is_some_and(|target_idx| target(target_idx).is_none()))
+        .map(|idx| (graph[idx].as_ref(), idx))
+        .collect();
+    let mut steps = Vec::with_capacity(graph.edge_count() + deferred_steps.len());
+    while let Some((source, idx)) = ready.pop_first() {
+        let target_idx = target(idx).expect("ready nodes are renamed");
+        steps.push((source.to_path_buf(), graph[target_idx].to_path_buf()));
+        // the source is free now, so the file renamed to it can follow
+        for waiting in graph.neighbors_directed(idx, Incoming) {
+            ready.insert((graph[waiting].as_ref(), waiting));
+        }
+    }
+    anyhow::ensure!(
+        steps.len() == graph.edge_count(),
+        "Cycle detected even after breaking all cycles."
+    );
+    // Now add the deferred steps. Their relative order does not matter, they are sorted for stability.
+    deferred_steps.sort_unstable_by(|(_, a), (_, b)| a.cmp(b));
+    steps.append(&mut deferred_steps);
+
+    Ok(steps)
+}
+
+/// Create a temp file name that makes sense to a human if renaming fails at any point
+/// and which is deterministic for testing.
+fn unused_temp_file_name(source_file: &Path, temp_file_counter: &mut usize) -> Result<PathBuf> {
+    let file_name = source_file.file_name().with_context(|| {
+        format!(
+            "Cannot create a temporary name for {}, as it has no file name.",
+            source_file.to_string_lossy()
+        )
+    })?;
+    loop {
+        let mut temp_file_name = file_name.to_os_string();
+        temp_file_name.push(format!(".n{}.tmp", temp_file_counter));
+        let temp_file = source_file.with_file_name(temp_file_name);
+        *temp_file_counter += 1;
+        if !temp_file.exists() {
+            return Ok(temp_file);
+        }
+    }
+}
+
+/// Split renamings that only change the case of a file name into two steps via a temporary
+/// file, because on a case insensitive filesystem the target seems to exist already.
+fn split_case_only_renamings(
+    steps: Vec<(PathBuf, PathBuf)>,
+    capabilities: &FilesystemCapabilities,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut temp_file_counter = 0;
+    let used_names: HashSet<PathBuf> = steps
+        .iter()
+        .flat_map(|(old, new)| [old.clone(), new.clone()])
+        .collect();
+    let mut result = Vec::with_capacity(steps.len());
+    for (old, new) in steps {
+        if old != new && capabilities.paths_equivalent(&old, &new) {
+            // avoid the names of temporary files introduced by cycle breaking
+            let temp_file = loop {
+                let candidate = unused_temp_file_name(&old, &mut temp_file_counter)?;
+                if !used_names.contains(&candidate) {
+                    break candidate;
+                }
+            };
+            result.push((old, temp_file.clone()));
+            result.push((temp_file, new));
+        } else {
+            result.push((old, new));
+        }
+    }
+    Ok(result)
+}
+
+/// Whether a renaming only adds or removes whitespace at the start or end of names in the path,
+/// which is easily done by accident in an editor
+fn changes_only_whitespace(old: &Path, new: &Path) -> bool {
+    let trimmed = |path: &Path| -> Vec<String> {
+        path.components()
+            .map(|component| component.as_os_str().to_string_lossy().trim().to_string())
+            .collect()
+    };
+    old != new && trimmed(old) == trimmed(new)
+}
+
+/// List renamings that only change whitespace at the ends of names, or only the case on a
+/// filesystem that does not preserve it, as they hardly change the names visibly and are easily
+/// made by accident. If they are declined, the plan is restricted to the other renamings.
+/// Whitespace changes the filesystem cannot represent are dropped without asking.
+fn resolve_subtle_renamings(
+    plan: RenamingPlan,
+    prompt_function: &mut impl FnMut(String) -> bool,
+) -> Result<RenamingPlan> {
+    let capabilities = &plan.request.capabilities;
+    let mut unrepresentable = Vec::new();
+    let mut subtle = Vec::new();
+    for (old, new) in &plan.request.mapping {
+        if changes_only_whitespace(old, new) {
+            let representable = new.components().all(|component| {
+                capabilities
+                    .validate_file_name(&component.as_os_str().to_string_lossy())
+                    .is_ok()
+            });
+            if representable {
+                subtle.push((old, format!("{:?} -> {:?} (whitespace)", old, new)));
+            } else {
+                unrepresentable.push((old, format!("{:?} -> {:?}", old, new)));
+            }
+        } else if !capabilities.case_preserving
+            && old != new
+            && capabilities.paths_equivalent(old, new)
+        {
+            subtle.push((
+                old,
+                format!(
+                    "{} -> {} (case, which this filesystem may not keep)",
+                    old.to_string_lossy(),
+                    new.to_string_lossy()
+                ),
+            ));
+        }
+    }
+    let mut dropped: HashSet<PathBuf> = HashSet::new();
+    if !unrepresentable.is_empty() {
+        print_human_readable(
+            &format!(
+                "Not renaming these files, as this filesystem removes spaces or dots at the end \
+                 of names:\n{}",
+                unrepresentable
+                    .iter()
+                    .map(|(_, line)| format!("  {}", line))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+            plan.request.config.json,
+        );
+        dropped.extend(unrepresentable.into_iter().map(|(old, _)| old.clone()));
+    }
+    if !subtle.is_empty()
+        && !prompt_function(format!(
+            "These renamings only change whitespace at the start or end of names or the case \
+             of names:\n{}\n\nDecline to keep the names of these files and rename the others only.",
+            subtle
+                .iter()
+                .map(|(_, line)| format!("  {}", line))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ))
+    {
+        dropped.extend(subtle.into_iter().map(|(old, _)| old.clone()));
+    }
+    if dropped.is_empty() {
+        return Ok(plan);
+    }
+    let request = plan.request.restricted_to(|old| !dropped.contains(old))?;
+    RenamingPlan::try_new(request)
+}
+
+impl RenamingPlan {
+    /// Plan the renamings of `request`. Fails if a target exists that is not renamed itself,
+    /// unless it is a duplicate removed with `--dedupe-existing`, or if the renaming violates a
+    /// lint rule with severity `error`.
+    pub fn try_new(request: RenamingRequest) -> Result<Self> {
+        // Targets must not exist unless they are renamed themselves. Files not part of the
+        // listing, e.g. ignored files, are detected here, before the user confirms.
+        let sources: HashSet<String> = request
+            .mapping
+            .iter()
+            .map(|(old, _)| request.capabilities.normalized(old))
+            .collect();
+        let mut duplicates = Vec::new();
+        let mut trashed = Vec::new();
+        for (old, new) in &request.mapping {
+            // with --copy, the sources stay where they are
+            let is_source = sources.contains(&request.capabilities.normalized(new));
+            if (is_source && !request.config.copy) || fs::symlink_metadata(new).is_err() {
+                continue;
+            }
+            anyhow::ensure!(
+                !is_source,
+                "The file {} is copied itself, so it cannot be the target of another copy.",
+                new.to_string_lossy()
+            );
+            if request.config.dedupe_existing && same_content(old, new)? {
+                duplicates.push((old.clone(), new.clone()));
+                continue;
+            }
+            anyhow::ensure!(
+                request.config.overwrite == Overwrite::Trash,
+                "The file {} already exists.",
+                new.to_string_lossy()
+            );
+            trashed.push(new.clone());
+        }
+        let removed: HashSet<&(PathBuf, PathBuf)> = duplicates.iter().collect();
+        let renamings: Vec<(PathBuf, PathBuf)> = request
+            .mapping
+            .iter()
+            .filter(|renaming| !removed.contains(renaming))
+            .cloned()
+            .collect();
+        let (errors, warnings): (Vec<Finding>, Vec<Finding>) = lint::lint(
+            &request.mapping,
+            &request.config.base_directories(),
+            &request.config.lint,
+        )
+        .into_iter()
+        .partition(|finding| finding.severity == Severity::Error);
+        if !errors.is_empty() {
+            anyhow::bail!(
+                "The renaming violates lint rules:\n{}",
+                errors
+                    .iter()
+                    .map(|finding| format!("  {}", finding))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+        // copies never free a name, so there are no cycles to break and any order works
+        let steps = if request.config.copy {
+            renamings
+        } else {
+            split_case_only_renamings(
+                break_cycles_and_fix_ordering(&renamings)?,
+                &request.capabilities,
+            )?
+        };
+        let (steps, volumes) = if request.config.per_component {
+            // keyed by their first source, each group of dependent steps is a volume of its own
+            partition_by_volume(steps, Path::to_path_buf)
+        } else {
+            partition_by_volume(steps, |path| transfer::device_of(path).ok())
+        };
+        let directories = missing_parent_directories(&steps);
+        let watched_folder = if request.config.no_sync_warning {
+            None
+        } else {
+            sync::watched_folder(request.config.base_directory())
+        };
+
+        Ok(RenamingPlan {
+            request,
+            directories,
+            steps,
+            volumes,
+            warnings,
+            duplicates,
+            trashed,
+            watched_folder,
+        })
+    }
+    /// Whether there is nothing to rename
+    pub fn is_empty(&self) -> bool {
+        self.request.is_empty()
+    }
+
+    /// The steps in the order they are performed, including those from or to temporary files
+    pub fn steps(&self) -> &[(PathBuf, PathBuf)] {
+        &self.steps
+    }
+
+    /// The directories created before the steps are performed, parents before their children
+    pub fn directories(&self) -> &[PathBuf] {
+        &self.directories
+    }
+
+    /// The preview shown in the confirmation prompt, in the style given with `--preview`
+    pub fn preview(&self) -> String {
+        self.human_readable_rename_mapping()
+    }
+
+    /// Create a human readable representation of the rename mapping
+    fn human_readable_rename_mapping(&self) -> String {
+        render::to_text(&self.segments(), self.request.config.preview)
+    }
+
+    /// The plan as reported in JSON output of dry runs and written with `--plan-out`: the directories to create and remove,
+    /// the duplicates to remove, the existing targets to move to the trash, the renamings in the order of the file list and the steps in
+    /// the order they are performed, marking the steps from or to temporary files as internal
+    pub fn to_json(&self) -> JsonValue {
+        let path = |path: &Path| JsonValue::from(path.to_string_lossy().as_ref());
+        let requested: HashSet<&(PathBuf, PathBuf)> = self.request.mapping.iter().collect();
+        JsonValue::object([
+            (
+                "create_directories",
+                JsonValue::Array(self.directories.iter().map(|d| path(d)).collect()),
+            ),
+            (
+                "remove_directories",
+                JsonValue::Array(
+                    self.request
+                        .removed_directories
+                        .iter()
+                        .map(|d| path(d))
+                        .collect(),
+                ),
+            ),
+            (
+                "remove_duplicates",
+                JsonValue::Array(
+                    self.duplicates
+                        .iter()
+                        .map(|(old, new)| {
+                            JsonValue::object([("from", path(old)), ("identical_to", path(new))])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "trash",
+                JsonValue::Array(self.trashed.iter().map(|t| path(t)).collect()),
+            ),
+            (
+                "renamings",
+                JsonValue::Array(
+                    self.renamings()
+                        .map(|(old, new)| {
+                            JsonValue::object([("from", path(old)), ("to", path(new))])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "steps",
+                JsonValue::Array(
+                    self.steps
+                        .iter()
+                        .map(|step| {
+                            JsonValue::object([
+                                ("from", path(&step.0)),
+                                ("to", path(&step.1)),
+                                ("internal", (!requested.contains(step)).into()),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+
+    /// The preview of the plan as structured segments, which the confirmation prompt renders
+    /// as text with [`render::to_text`]
+    pub fn segments(&self) -> Vec<Segment> {
+        let changes = if self.request.config.diff {
+            Segment::Diff(self.request.unified_diff())
+        } else {
+            Segment::Changes(self.changes())
+        };
+        std::iter::once(changes)
+            .chain(
+                DirectorySummary::new(&self.request.mapping, self.directories.len())
+                    .map(Segment::Directories),
+            )
+            .chain(
+                self.request
+                    .config
+                    .show_sizes
+                    .then(|| Segment::Sizes(render::sizes_by_directory(&self.request.mapping))),
+            )
+            .chain(self.leaving_summary().map(Segment::Leaving))
+            .chain(
+                self.execution_order()
+                    .filter(|_| self.request.config.verbose)
+                    .map(Segment::ExecutionOrder),
+            )
+            .chain(self.watched_folder.clone().map(Segment::WatchedFolder))
+            .chain((!self.warnings.is_empty()).then(|| Segment::Lint(self.warnings.clone())))
+            .collect()
+    }
+
+    /// The requested renamings without the duplicates to remove, in the order of the file list
+    fn renamings(&self) -> impl Iterator<Item = &(PathBuf, PathBuf)> {
+        let removed: HashSet<&(PathBuf, PathBuf)> = self.duplicates.iter().collect();
+        self.request
+            .mapping
+            .iter()
+            .filter(move |renaming| !removed.contains(renaming))
+    }
+
+    /// The steps in the order they are performed, if it differs from the order of the renamings
+    /// in the file list
+    fn execution_order(&self) -> Option<Vec<(PathBuf, PathBuf)>> {
+        (!self.steps.iter().eq(self.renamings())).then(|| self.steps.clone())
+    }
+
+    /// The directories to create and remove, the duplicates to remove, the existing targets to
+    /// move to the trash and the renamings in the order of the file list
+    fn changes(&self) -> Vec<Change> {
+        self.directories
+            .iter()
+            .map(|directory| Change::CreateDirectory(directory.clone()))
+            .chain(
+                self.request
+                    .removed_directories
+                    .iter()
+                    .map(|directory| Change::RemoveDirectory(directory.clone())),
+            )
+            .chain(
+                self.duplicates
+                    .iter()
+                    .map(|(old, new)| Change::RemoveDuplicate {
+                        old: old.clone(),
+                        existing: new.clone(),
+                    }),
+            )
+            .chain(self.trashed.iter().map(|path| Change::Trash(path.clone())))
+            .chain(
+                self.renamings()
+                    .map(|(old, new)| self.request.config.rename_change(old, new)),
+            )
+            .collect()
+    }
+
+    /// The plan as a Markdown report for sign-off, written with `--report-md`
+    fn markdown_report(&self) -> String {
+        render::to_markdown(
+            &self.changes(),
+            &self.request.mapping,
+            &self.segments(),
+            self.execution_order().map(|steps| steps.len()),
+            self.request.config.save_rejected_plan.as_deref(),
+        )
+    }
+
+    /// Summarize the files moved out of the base path, if there are any
+    fn leaving_summary(&self) -> Option<LeavingSummary> {
+        let base_directories = self.request.config.base_directories();
+        let leaving: Vec<&(PathBuf, PathBuf)> = self
+            .request
+            .mapping
+            .iter()
+            .filter(|(_, new)| {
+                !base_directories
+                    .iter()
+                    .any(|directory| lint::is_within(new, directory))
+            })
+            .collect();
+        if leaving.is_empty() {
+            return None;
+        }
+        let bytes = leaving
+            .iter()
+            .filter_map(|(old, _)| fs::metadata(old).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        // the existing directories the files are moved to, or below which directories are created
+        let existing: BTreeSet<&Path> = leaving
+            .iter()
+            .filter_map(|(_, new)| {
+                parent_directory(new)
+                    .ancestors()
+                    .find(|directory| directory.is_dir())
+            })
+            .collect();
+        let destinations = existing
+            .iter()
+            .filter(|destination| {
+                !existing
+                    .iter()
+                    .any(|other| other != *destination && lint::is_within(destination, other))
+            })
+            .map(|destination| destination.to_path_buf())
+            .collect();
+        Some(LeavingSummary {
+            files: leaving.len(),
+            bytes,
+            destinations,
+        })
+    }
+
+    /// Ask for a separate confirmation if many files or much data leave the base path.
+    /// Returns whether the renaming may proceed.
+    fn confirm_leaving(&self, prompt_function: &mut impl FnMut(String) -> bool) -> bool {
+        match self.leaving_summary() {
+            Some(summary) if summary.needs_confirmation() => prompt_function(format!(
+                "{}\n\nThis may fill up another volume. \
+                 Please confirm moving these files out of the base path.",
+                summary
+            )),
+            _ => true,
+        }
+    }
+
+    /// Execute the plan. With `--confirm-every`, `prompt_function` is asked before each batch
+    /// after the first one. Returns whether all steps were executed.
+    /// The performed steps are recorded in `statistics`.
+    pub fn execute(
+        &self,
+        mut prompt_function: impl FnMut(String) -> bool,
+        statistics: &mut Vec<StepStatistics>,
+    ) -> Result<bool> {
+        self.request.ensure_files_did_not_change()?;
+        let sandbox = transfer::Sandbox::open_all(&self.request.config.base_directories())?;
+        let created_directories = create_directories(
+            &self.directories,
+            self.request.config.dir_mode,
+            self.request.config.preserve_owner,
+            self.request.config.verbose,
+            statistics,
+        )?;
+        let transfer_options = TransferOptions {
+            verify_copy: self.request.config.verify_copy,
+            preserve_owner: self.request.config.preserve_owner,
+            security_context: self.request.config.security_context(),
+            keep_source: self.request.config.copy,
+        };
+        let log_file_path = self.request.log_file_path();
+        let removed_directories = &self.request.removed_directories;
+        let deadline = self
+            .request
+            .config
+            .timeout
+            .map(|timeout| Instant::now() + timeout);
+        // existing targets, sources and directories are removed first, as other files may be
+        // renamed to their names
+        for (i, path) in self.trashed.iter().enumerate() {
+            let start = Instant::now();
+            if let Err(e) = trash::delete(path) {
+                remove_empty_directories(&created_directories);
+                self.write_log_file(
+                    &log_file_path,
+                    &created_directories,
+                    &self.trashed[..i],
+                    &[],
+                    &[],
+                    &[],
+                );
+                return Err(e).with_context(|| {
+                    format!("Failed to move {} to the trash", path.to_string_lossy())
+                });
+            }
+            statistics.push(StepStatistics::new("trash", Some(path), None, start));
+        }
+        let mut removed_duplicates: Vec<(PathBuf, PathBuf)> =
+            Vec::with_capacity(self.duplicates.len());
+        for (old, new) in &self.duplicates {
+            // either file may have changed since the plan was made
+            match same_content(old, new) {
+                Ok(true) => {}
+                Ok(false) => {
+                    eprintln!(
+                        "Warning: Not removing {}, as its content differs from {} now.",
+                        old.to_string_lossy(),
+                        new.to_string_lossy()
+                    );
+                    continue;
+                }
+                Err(e) => {
+                    eprintln!("Warning: Not removing {}: {:#}", old.to_string_lossy(), e);
+                    continue;
+                }
+            }
+            let start = Instant::now();
+            if let Err(e) = fs::remove_file(old) {
+                remove_empty_directories(&created_directories);
+                self.write_log_file(
+                    &log_file_path,
+                    &created_directories,
+                    &self.trashed,
+                    &removed_duplicates,
+                    &[],
+                    &[],
+                );
+                return Err(e).with_context(|| {
+                    format!(
+                        "Failed to remove {}, which is identical to {}",
+                        old.to_string_lossy(),
+                        new.to_string_lossy()
+                    )
+                });
+            }
+            statistics.push(StepStatistics::new("remove", Some(old), None, start));
+            removed_duplicates.push((old.clone(), new.clone()));
+        }
+        for (i, directory) in removed_directories.iter().enumerate() {
+            let start = Instant::now();
+            if let Err(e) = fs::remove_dir(directory) {
+                remove_empty_directories(&created_directories);
+                self.write_log_file(
+                    &log_file_path,
+                    &created_directories,
+                    &self.trashed,
+                    &removed_duplicates,
+                    &removed_directories[..i],
+                    &[],
+                );
+                return Err(e).with_context(|| {
+                    format!(
+                        "Failed to remove the directory {}",
+                        directory.to_string_lossy()
+                    )
+                });
+            }
+            statistics.push(StepStatistics::new(
+                "remove_dir",
+                Some(directory),
+                None,
+                start,
+            ));
+        }
+        if self.steps.is_empty() {
+            self.write_log_file(
+                &log_file_path,
+                &created_directories,
+                &self.trashed,
+                &removed_duplicates,
+                removed_directories,
+                &[],
+            );
+        }
+        let mut performed: Vec<(PathBuf, PathBuf)> = Vec::with_capacity(self.steps.len());
+        // the first failed step and its error. Other volumes are renamed nevertheless.
+        let mut failure: Option<(usize, anyhow::Error)> = None;
+        let per_component = self.request.config.per_component;
+        let mut declined = false;
+        let mut volume_start = 0;
+        for (v, &volume_size) in self.volumes.iter().enumerate() {
+            // other volumes are not started once the time is up
+            if failure.is_some() && deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break;
+            }
+            let volume = &self.steps[volume_start..volume_start + volume_size];
+            if self.volumes.len() > 1 && !per_component {
+                eprintln!(
+                    "Renaming on volume {} of {} ({} steps)",
+                    v + 1,
+                    self.volumes.len(),
+                    volume_size
+                );
+            }
+            let component_start = volume_start;
+            let mut batch_start = volume_start;
+            volume_start += volume_size;
+            for batch in step_batches(volume, self.request.config.confirm_every) {
+                if !performed.is_empty() || failure.is_some() {
+                    let next_batch = batch
+                        .iter()
+                        .map(|(old, new)| {
+                            format!("{} -> {}", old.to_string_lossy(), new.to_string_lossy())
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    let message = format!(
+                        "Renamed {} of {} steps. Next batch:\n{}",
+                        performed.len(),
+                        self.steps.len(),
+                        next_batch
+                    );
+                    if self.request.config.confirm_every.is_some() && !prompt_function(message) {
+                        declined = true;
+                        break;
+                    }
+                }
+                let transcript = self.request.config.verbose.then_some(Transcript {
+                    first_step: batch_start,
+                    total_steps: self.steps.len(),
+                });
+                let result = rename_files(
+                    batch,
+                    &transfer_options,
+                    &sandbox,
+                    transcript,
+                    deadline,
+                    statistics,
+                );
+                let renamed = match &result {
+                    Ok(()) => batch.len(),
+                    Err((failed, _)) => *failed,
+                };
+                performed.extend_from_slice(&batch[..renamed]);
+                // The log is rewritten after each batch, so it reflects the progress.
+                if renamed > 0 {
+                    self.write_log_file(
+                        &log_file_path,
+                        &created_directories,
+                        &self.trashed,
+                        &removed_duplicates,
+                        removed_directories,
+                        &performed,
+                    );
+                }
+                if let Err((failed, e)) = result {
+                    if per_component {
+                        // no file of the group is left at a temporary name
+                        let component = batch_start + failed - component_start;
+                        let undone = roll_back(
+                            &performed[performed.len() - component..],
+                            &transfer_options,
+                            &sandbox,
+                        );
+                        performed.truncate(performed.len() - undone);
+                        self.write_log_file(
+                            &log_file_path,
+                            &created_directories,
+                            &self.trashed,
+                            &removed_duplicates,
+                            removed_directories,
+                            &performed,
+                        );
+                        eprintln!(
+                            "Renaming {} failed, undid {} renamings of its group: {:#}",
+                            self.steps[batch_start + failed].0.to_string_lossy(),
+                            undone,
+                            e
+                        );
+                    } else if self.volumes.len() > 1 {
+                        eprintln!("Renaming on volume {} failed: {:#}", v + 1, e);
+                    }
+                    failure.get_or_insert((batch_start + failed, e));
+                    break;
+                }
+                batch_start += batch.len();
+            }
+            if declined {
+                break;
+            }
+        }
+        if let Some(path) = &self.request.config.emit_batch {
+            // like the log file, the batch file describes what happened, so it is not an error
+            // if it cannot be written
+            if let Err(e) = append_batch_file(path, &removed_duplicates, &performed) {
+                eprintln!("Warning: {:#}", e);
+            }
+        }
+        if let Some((failed, e)) = failure {
+            remove_empty_directories(&created_directories);
+            match self.write_failure_report(&performed, failed, &e, &created_directories) {
+                Ok(path) => eprintln!(
+                    "A report of the completed, failed and pending steps was written to {}",
+                    path.to_string_lossy()
+                ),
+                Err(report_error) => eprintln!("Warning: {:#}", report_error),
+            }
+            return Err(e);
+        }
+        Ok(!declined)
+    }
+
+    /// Write the log file of the trashed targets, the removed duplicates and directories and the
+    /// performed steps, unless --no-log is given. The files are renamed already, so failing to
+    /// write the log is not an error. Nothing is written if nothing happened.
+    fn write_log_file(
+        &self,
+        log_file_path: &Path,
+        created_directories: &[PathBuf],
+        trashed: &[PathBuf],
+        removed_duplicates: &[(PathBuf, PathBuf)],
+        removed_directories: &[PathBuf],
+        performed: &[(PathBuf, PathBuf)],
+    ) {
+        if self.request.config.no_log
+            || (trashed.is_empty()
+                && removed_duplicates.is_empty()
+                && removed_directories.is_empty()
+                && performed.is_empty())
+        {
+            return;
+        }
+        if let Err(e) = self.request.write_renaming_log_file(
+            log_file_path,
+            created_directories,
+            removed_directories,
+            trashed,
+            removed_duplicates,
+            performed,
+        ) {
+            eprintln!("Warning: {:#}", e);
+        }
+    }
+
+    /// Write a JSON report of the completed, failed and pending steps after renaming failed,
+    /// so the user can recover. The report is written next to the log files.
+    fn write_failure_report(
+        &self,
+        performed: &[(PathBuf, PathBuf)],
+        failed: usize,
+        error: &anyhow::Error,
+        created_directories: &[PathBuf],
+    ) -> Result<PathBuf> {
+        let step = |(old, new): &(PathBuf, PathBuf)| {
+            JsonValue::object([
+                ("from", old.to_string_lossy().as_ref().into()),
+                ("to", new.to_string_lossy().as_ref().into()),
+            ])
+        };
+        let (failed_old, failed_new) = &self.steps[failed];
+        let performed_steps: HashSet<&(PathBuf, PathBuf)> = performed.iter().collect();
+        let report = JsonValue::object([
+            (
+                "format_version",
+                (FAILURE_REPORT_FORMAT_VERSION as usize).into(),
+            ),
+            ("bumv_version", env!("CARGO_PKG_VERSION").into()),
+            ("session", self.request.config.session.as_str().into()),
+            (
+                "created_directories",
+                created_directories
+                    .iter()
+                    .map(|directory| directory.to_string_lossy().to_string())
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+            (
+                "completed",
+                JsonValue::Array(performed.iter().map(step).collect()),
+            ),
+            (
+                "failed",
+                JsonValue::object([
+                    ("from", failed_old.to_string_lossy().as_ref().into()),
+                    ("to", failed_new.to_string_lossy().as_ref().into()),
+                    ("error", format!("{:#}", error).into()),
+                ]),
+            ),
+            (
+                "pending",
+                JsonValue::Array(
+                    self.steps
+                        .iter()
+                        .enumerate()
+                        .filter(|&(i, step)| i != failed && !performed_steps.contains(step))
+                        .map(|(_, s)| step(s))
+                        .collect(),
+                ),
+            ),
+        ]);
+        let config = &self.request.config;
+        let report_path = config
+            .base_directory()
+            .join(format!("bumv_{}_failure.json", config.session));
+        fs::write(&report_path, report.to_string()).with_context(|| {
+            format!(
+                "Failed to write the failure report {}",
+                report_path.to_string_lossy()
+            )
+        })?;
+        Ok(report_path)
+    }
+}
+
+/// Partition the steps into volumes, e.g. several USB drives mounted below the base path, which are
+/// renamed independently of each other. Steps sharing a path stay together, so that chains and
+/// cycles spanning volumes are never split, and belong to the volume of their first source.
+/// Returns the steps ordered by volume, keeping their order within each volume,
+/// and the number of steps of each volume.
+fn partition_by_volume<K: Eq + std::hash::Hash>(
+    steps: Vec<(PathBuf, PathBuf)>,
+    device_of: impl Fn(&Path) -> K,
+) -> (Vec<(PathBuf, PathBuf)>, Vec<usize>) {
+    fn root(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+    // union-find over the steps, joining steps that share a path
+    let mut parents: Vec<usize> = (0..steps.len()).collect();
+    let mut step_by_path: HashMap<&Path, usize> = HashMap::with_capacity(steps.len() * 2);
+    for (i, (old, new)) in steps.iter().enumerate() {
+        for path in [old.as_path(), new.as_path()] {
+            let j = *step_by_path.entry(path).or_insert(i);
+            let (root_i, root_j) = (root(&mut parents, i), root(&mut parents, j));
+            // the smaller index becomes the root, so a group's root is its first step
+            parents[root_i.max(root_j)] = root_i.min(root_j);
+        }
+    }
+    let mut volume_by_device: HashMap<K, usize> = HashMap::new();
+    let mut volume_by_root: HashMap<usize, usize> = HashMap::new();
+    let mut volume_of_step = Vec::with_capacity(steps.len());
+    for i in 0..steps.len() {
+        let group = root(&mut parents, i);
+        let volume = *volume_by_root.entry(group).or_insert_with(|| {
+            let next_volume = volume_by_device.len();
+            *volume_by_device
+                .entry(device_of(&steps[group].0))
+                .or_insert(next_volume)
+        });
+        volume_of_step.push(volume);
+    }
+    let mut volumes = vec![0; volume_by_device.len()];
+    for &volume in &volume_of_step {
+        volumes[volume] += 1;
+    }
+    let mut ordered: Vec<(usize, (PathBuf, PathBuf))> =
+        volume_of_step.into_iter().zip(steps).collect();
+    ordered.sort_by_key(|(volume, _)| *volume);
+    (ordered.into_iter().map(|(_, step)| step).collect(), volumes)
+}
+
+/// Split the steps into batches of at least `batch_size` steps. Batches are extended so that
+/// they do not end while a file is at a temporary name, as stopping there would leave it behind.
+fn step_batches(
+    steps: &[(PathBuf, PathBuf)],
+    batch_size: Option<usize>,
+) -> Vec<&[(PathBuf, PathBuf)]> {
+    let batch_size = match batch_size {
+        Some(batch_size) => batch_size,
+        None => return vec![steps],
+    };
+    let step_by_source: HashMap<&Path, usize> = steps
+        .iter()
+        .enumerate()
+        .map(|(i, (old, _))| (old.as_path(), i))
+        .collect();
+    let mut batches = Vec::new();
+    let mut start = 0;
+    // temporary names introduced by steps of the current batch, that later steps rename again
+    let mut pending: HashSet<&Path> = HashSet::new();
+    for (i, (old, new)) in steps.iter().enumerate() {
+        pending.remove(old.as_path());
+        if step_by_source.get(new.as_path()).is_some_and(|&j| j > i) {
+            pending.insert(new);
+        }
+        if i + 1 - start >= batch_size && pending.is_empty() {
+            batches.push(&steps[start..=i]);
+            start = i + 1;
+        }
+    }
+    if start < steps.len() {
+        batches.push(&steps[start..]);
+    }
+    batches
+}
+
+/// Parse the size of a batch, which must be positive
+fn parse_batch_size(size: &str) -> Result<usize> {
+    let size: usize = size
+        .parse()
+        .with_context(|| format!("Invalid batch size {}", size))?;
+    anyhow::ensure!(size > 0, "The batch size must be at least 1");
+    Ok(size)
+}
+
+/// The directory containing a file, `.` for relative paths without a directory
+fn parent_directory(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Find the directories that need to be created for the renaming steps, ordered so that
+/// parent directories come before their children.
+fn missing_parent_directories(steps: &[(PathBuf, PathBuf)]) -> Vec<PathBuf> {
+    let directories: BTreeSet<PathBuf> = steps
+        .iter()
+        .filter_map(|(_, new)| new.parent())
+        .flat_map(|parent| parent.ancestors())
+        .filter(|directory| !directory.as_os_str().is_empty() && !directory.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    // the ordering of paths compares component by component, so parents come first
+    directories.into_iter().collect()
+}
+
+/// Create the given directories in order, returning the directories that were actually created.
+/// On failure, the directories created so far are removed again.
+/// Created directories get the permissions `dir_mode` if specified, or the permissions of their
+/// parent directory otherwise.
+/// With `preserve_owner`, they also get the owner and group of their parent directory.
+/// With `verbose`, each created directory is printed to stderr.
+/// Each created directory is recorded in `statistics`.
+fn create_directories(
+    directories: &[PathBuf],
+    dir_mode: Option<u32>,
+    preserve_owner: bool,
+    verbose: bool,
+    statistics: &mut Vec<StepStatistics>,
+) -> Result<Vec<PathBuf>> {
+    let mut created = Vec::new();
+    for directory in directories {
+        if directory.exists() {
+            continue;
+        }
+        let start = Instant::now();
+        let result = fs::create_dir(directory)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| {
+                created.push(directory.clone());
+                set_directory_mode(directory, dir_mode)
+            })
+            .and_then(|_| match directory.parent() {
+                Some(parent) if preserve_owner => transfer::copy_owner(parent, directory),
+                _ => Ok(()),
+            });
+        if let Err(e) = result {
+            remove_empty_directories(&created);
+            return Err(e)
+                .with_context(|| format!("Failed to create directory {}", directory.display()));
+        }
+        statistics.push(StepStatistics::new(
+            "create_dir",
+            None,
+            Some(directory),
+            start,
+        ));
+        if verbose {
+            eprintln!("Created directory {}", directory.to_string_lossy());
+        }
+    }
+    Ok(created)
+}
+
+/// Set the permissions of a created directory exactly, i.e. not reduced by the umask
+#[cfg(unix)]
+fn set_directory_mode(directory: &Path, dir_mode: Option<u32>) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = match dir_mode {
+        Some(mode) => mode,
+        None => match directory.parent() {
+            Some(parent) => fs::metadata(parent)?.permissions().mode() & 0o7777,
+            None => return Ok(()),
+        },
+    };
+    fs::set_permissions(directory, fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+/// Directory permissions are not supported on this platform
+#[cfg(not(unix))]
+fn set_directory_mode(_directory: &Path, _dir_mode: Option<u32>) -> Result<()> {
+    Ok(())
+}
+
+/// Parse permissions given as an octal number like `0755`
+fn parse_dir_mode(mode: &str) -> Result<u32> {
+    let digits = mode.strip_prefix("0o").unwrap_or(mode);
+    let mode = u32::from_str_radix(digits, 8)
+        .with_context(|| format!("Invalid directory mode {}, expected an octal number", mode))?;
+    anyhow::ensure!(mode <= 0o7777, "Invalid directory mode {:o}", mode);
+    Ok(mode)
+}
+
+/// Remove previously created directories in reverse order of their creation, as long as they
+/// are empty. Used to roll back the directory creation phase when renaming fails.
+fn remove_empty_directories(directories: &[PathBuf]) {
+    for directory in directories.iter().rev() {
+        // directories that are not empty anymore are kept
+        let _ = fs::remove_dir(directory);
+    }
+}
+
+/// The ignore sources observed when listing files. Hidden files are skipped whenever
+/// `ignore_files` is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IgnoreSources {
+    /// `.ignore` and `.gitignore` files
+    ignore_files: bool,
+    /// The `.git/info/exclude` file of the repository
+    git_exclude: bool,
+    /// The global gitignore file configured by `core.excludesFile`
+    git_global: bool,
+}
+
+impl std::fmt::Display for IgnoreSources {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.ignore_files {
+            return write!(f, "none");
+        }
+        let mut sources = vec![".ignore", ".gitignore"];
+        if self.git_exclude {
+            sources.push(".git/info/exclude");
+        }
+        if self.git_global {
+            sources.push("core.excludesFile");
+        }
+        write!(f, "{}", sources.join(", "))
+    }
+}
+
+/// Position of renaming steps in the plan, for printing them with `--verbose`
+#[derive(Debug, Clone, Copy)]
+pub struct Transcript {
+    /// Index of the first of the steps in the plan
+    pub first_step: usize,
+    /// Number of steps in the plan
+    pub total_steps: usize,
+}
+
+/// Undo performed steps in reverse order, returning the number of undone steps. Copies made
+/// with --copy are removed. Stops at the first step that cannot be undone, as the steps before
+/// it may depend on it.
+fn roll_back(
+    performed: &[(PathBuf, PathBuf)],
+    transfer_options: &TransferOptions,
+    sandbox: &transfer::Sandbox,
+) -> usize {
+    for (i, (old, new)) in performed.iter().rev().enumerate() {
+        let result = if transfer_options.keep_source {
+            transfer::remove_copy(new)
+        } else {
+            transfer::move_file(new, old, transfer_options, sandbox).map(|_| ())
+        };
+        if let Err(e) = result {
+            eprintln!(
+                "Warning: Failed to undo renaming {} to {}: {:#}",
+                old.to_string_lossy(),
+                new.to_string_lossy(),
+                e
+            );
+            return i;
+        }
+    }
+    performed.len()
+}
+
+/// What happened in a session, reported in JSON output and to webhooks
+#[derive(Debug, Default)]
+struct SessionReport {
+    /// The performed steps
+    steps: Vec<StepStatistics>,
+    /// The plan of a dry run, which performs no steps
+    plan: Option<JsonValue>,
+}
+
+/// Duration and strategy of a performed step, reported in JSON output
+/// to find out which steps of a plan are slow
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepStatistics {
+    /// How the step was performed, like `rename`, `copy` or `create_dir`
+    strategy: &'static str,
+    from: Option<PathBuf>,
+    to: Option<PathBuf>,
+    duration: Duration,
+}
+
+impl StepStatistics {
+    /// Statistics of a step that started at `start` and just finished
+    fn new(strategy: &'static str, from: Option<&Path>, to: Option<&Path>, start: Instant) -> Self {
+        Self {
+            strategy,
+            from: from.map(Path::to_path_buf),
+            to: to.map(Path::to_path_buf),
+            duration: start.elapsed(),
+        }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let path = |path: &Option<PathBuf>| {
+            JsonValue::from(path.as_ref().map(|path| path.to_string_lossy().to_string()))
+        };
+        JsonValue::object([
+            ("strategy", self.strategy.into()),
+            ("from", path(&self.from)),
+            ("to", path(&self.to)),
+            (
+                "duration_ms",
+                JsonValue::Number(self.duration.as_secs_f64() * 1000.0),
+            ),
+        ])
+    }
+}
+
+/// Perform the actual renaming of the files.
+/// On failure, the index of the failed step is returned along with the error.
+/// With a transcript, each step is printed to stderr, e.g. `[42/317] old -> new ... ok (3ms)`.
+/// Each renamed file is recorded in `statistics`.
+/// Files within the base path are renamed relative to the `sandbox`.
+/// No step is started after the `deadline` of --timeout.
+pub fn rename_files(
+    rename_mapping: &[(PathBuf, PathBuf)],
+    transfer_options: &TransferOptions,
+    sandbox: &transfer::Sandbox,
+    transcript: Option<Transcript>,
+    deadline: Option<Instant>,
+    statistics: &mut Vec<StepStatistics>,
+) -> std::result::Result<(), (usize, anyhow::Error)> {
+    for (i, (old, new)) in rename_mapping.iter().enumerate() {
+        let start = Instant::now();
+        // a step in progress is completed, so no file is left half copied
+        if deadline.is_some_and(|deadline| start >= deadline) {
+            return Err((
+                i,
+                anyhow::anyhow!("The time limit given by --timeout was exceeded."),
+            ));
+        }
+        if let Some(transcript) = transcript {
+            eprint!(
+                "[{}/{}] {} -> {} ... ",
+                transcript.first_step + i + 1,
+                transcript.total_steps,
+                old.to_string_lossy(),
+                new.to_string_lossy()
+            );
+        }
+        let result = if new.exists() {
+            Err(anyhow::anyhow!(
+                "The file {} already exists. Aborting.",
+                new.to_string_lossy()
+            ))
+        } else if transfer_options.keep_source {
+            transfer::duplicate_file(old, new, transfer_options)
+        } else {
+            transfer::move_file(old, new, transfer_options, sandbox)
+        };
+        if transcript.is_some() {
+            let outcome = if result.is_ok() { "ok" } else { "failed" };
+            eprintln!("{} ({}ms)", outcome, start.elapsed().as_millis());
+        }
+        let strategy = result.map_err(|e| (i, e))?;
+        statistics.push(StepStatistics::new(
+            strategy.name(),
+            Some(old),
+            Some(new),
+            start,
+        ));
+    }
+    Ok(())
+}
+
+/// Create the content of the temp file the user will edit
+fn create_editable_temp_file_content(files: &[PathBuf]) -> String {
+    files
+        .iter()
+        .map(|f| f.to_string_lossy().to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Propose moving each file to the archive directory for its modification time, e.g. `archive/%Y-%m`
+/// in the base directory, keeping its path relative to the base directory.
+/// Files that are already in their archive directory stay where they are.
+fn archive_proposal(
+    files: &[PathBuf],
+    base_directory: &Path,
+    pattern: &str,
+) -> Result<Vec<PathBuf>> {
+    files
+        .iter()
+        .map(|file| {
+            let archive = base_directory.join(
+                expand::format_modification_time(file, pattern)
+                    .with_context(|| format!("Invalid archive directory {}", pattern))?,
+            );
+            if file.starts_with(&archive) {
+                return Ok(file.clone());
+            }
+            let relative = file.strip_prefix(base_directory).unwrap_or(file);
+            Ok(archive.join(relative))
+        })
+        .collect()
+}
+
+/// Summarize the size of a listing, e.g.
+/// `Listing 317 files in 12 directories, 1.4 GiB in total, nested up to 3 levels deep`
+fn listing_summary(files: &[PathBuf], base_directory: &Path) -> String {
+    let directories: HashSet<&Path> = files.iter().map(|file| parent_directory(file)).collect();
+    let size: u64 = files
+        .iter()
+        .filter_map(|file| fs::metadata(file).ok())
+        .filter(|metadata| !metadata.is_dir())
+        .map(|metadata| metadata.len())
+        .sum();
+    let depth = files
+        .iter()
+        .map(|file| {
+            file.strip_prefix(base_directory)
+                .unwrap_or(file)
+                .components()
+                .filter(|component| matches!(component, Component::Normal(_)))
+                .count()
+                .saturating_sub(1)
+        })
+        .max()
+        .unwrap_or(0);
+    let plural = |count: usize, singular: &str, plural: &str| match count {
+        1 => format!("1 {}", singular),
+        _ => format!("{} {}", count, plural),
+    };
+    let special = files
+        .iter()
+        .filter(|file| transfer::special_file_kind(file).is_some())
+        .count();
+    let summary = format!(
+        "Listing {} in {}, {} in total, nested up to {} deep",
+        plural(files.len(), "file", "files"),
+        plural(directories.len(), "directory", "directories"),
+        format_size(size),
+        plural(depth, "level", "levels")
+    );
+    let empty_directories = files.iter().filter(|file| file.is_dir()).count();
+    let included: Vec<String> = [
+        (special, "special file", "special files"),
+        (empty_directories, "empty directory", "empty directories"),
+    ]
+    .into_iter()
+    .filter(|&(count, _, _)| count > 0)
+    .map(|(count, singular, plural_form)| plural(count, singular, plural_form))
+    .collect();
+    if included.is_empty() {
+        summary
+    } else {
+        format!("{}, including {}", summary, included.join(" and "))
+    }
+}
+
+/// Format a number of bytes for humans, e.g. `1.4 GiB`
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Find the directories of the files in which files cannot be renamed, e.g. on read-only mounts,
+/// so that this is known before the user edits the file list. The directories are not modified.
+fn find_unwritable_directories(files: &[PathBuf]) -> BTreeMap<&Path, std::io::Error> {
+    let directories: BTreeSet<&Path> = files.iter().map(|file| parent_directory(file)).collect();
+    directories
+        .into_iter()
+        .filter_map(|directory| {
+            fs_capabilities::check_writable(directory)
+                .err()
+                .map(|error| (directory, error))
+        })
+        .collect()
+}
+
+/// Prefix of the lines noting collisions when merging, which are removed after editing
+const MERGE_NOTE_PREFIX: &str = "# ";
+
+/// Prefix of the lines of empty directories to remove with --allow-delete
+const REMOVE_PREFIX: &str = "#";
+
+/// Blank the notes of the file list for merging, instead of removing them, so that line numbers
+/// stay the same
+fn blank_merge_notes(content: &str) -> String {
+    content
+        .lines()
+        .map(|line| {
+            if line.starts_with(MERGE_NOTE_PREFIX) {
+                ""
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Stands for the directory shared by all files in the file list with --abbreviate
+const ABBREVIATION: &str = "§";
+
+/// Propose moving each file to the same path relative to the base directory in `destination`.
+/// Files whose target exists already are left where they are, preceded by a note whether the
+/// existing file has the same content, so the user can decide what to do with them.
+fn merge_proposal(files: &[PathBuf], base_directory: &Path, destination: &Path) -> Result<String> {
+    // `./dst/a.txt` is in `dst`, and `./a.txt` in `.`
+    let base_directory = without_current_directory(base_directory);
+    let normalized_destination = without_current_directory(destination);
+    let mut lines = Vec::with_capacity(files.len());
+    for file in files {
+        let normalized = without_current_directory(file);
+        if normalized.starts_with(&normalized_destination) {
+            lines.push(file.to_string_lossy().to_string());
+            continue;
+        }
+        let relative = normalized
+            .strip_prefix(&base_directory)
+            .unwrap_or(&normalized);
+        let target = destination.join(relative);
+        if fs::symlink_metadata(&target).is_ok() {
+            let content = if same_content(file, &target)? {
+                "the same"
+            } else {
+                "different"
+            };
+            lines.push(format!(
+                "{}{} exists with {} content. Edit the next line to move the file anyway.",
+                MERGE_NOTE_PREFIX,
+                target.to_string_lossy(),
+                content
+            ));
+            lines.push(file.to_string_lossy().to_string());
+        } else {
+            lines.push(target.to_string_lossy().to_string());
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+/// The path without `.` components, which `Path::starts_with` does not skip at the start
+fn without_current_directory(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+/// Whether two files have the same content, comparing their sizes and then their bytes chunk by
+/// chunk. Deciding to remove a file must not rely on checksums, which may collide.
+fn same_content(a: &Path, b: &Path) -> Result<bool> {
+    const CHUNK_SIZE: u64 = 64 * 1024;
+    let size = |path: &Path| {
+        fs::metadata(path)
+            .map(|metadata| (metadata.is_file(), metadata.len()))
+            .with_context(|| format!("Failed to read the metadata of {}", path.display()))
+    };
+    let (a_size, b_size) = (size(a)?, size(b)?);
+    // reading special files like FIFOs could block, so only regular files are compared
+    if a_size != b_size || !a_size.0 {
+        return Ok(false);
+    }
+    let open = |path: &Path| {
+        File::open(path)
+            .with_context(|| format!("Failed to open {} for comparison", path.display()))
+    };
+    let (mut a_file, mut b_file) = (open(a)?, open(b)?);
+    let (mut a_chunk, mut b_chunk) = (Vec::new(), Vec::new());
+    loop {
+        a_chunk.clear();
+        b_chunk.clear();
+        let read = (&mut a_file).take(CHUNK_SIZE).read_to_end(&mut a_chunk)?;
+        (&mut b_file).take(CHUNK_SIZE).read_to_end(&mut b_chunk)?;
+        if a_chunk != b_chunk {
+            return Ok(false);
+        }
+        if read == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// The directory shared by all file names in the file list, if there is one. Notes are ignored.
+fn common_directory(content: &str) -> Option<PathBuf> {
+    let mut files = content
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with(MERGE_NOTE_PREFIX))
+        .map(Path::new);
+    let mut common = files.next()?.parent()?.to_path_buf();
+    for file in files {
+        while !file.starts_with(&common) {
+            if !common.pop() {
+                return None;
+            }
+        }
+    }
+    // abbreviating a root directory or `.` would not shorten the lines
+    common
+        .components()
+        .any(|component| matches!(component, Component::Normal(_)))
+        .then_some(common)
+}
+
+/// Mark the lines of directories in the file list with a trailing `/`.
+/// `files` are the listed files of each line.
+fn mark_directories(content: &str, files: &[PathBuf]) -> String {
+    content
+        .lines()
+        .zip(files)
+        .map(|(line, file)| {
+            if file.is_dir() && !line.ends_with('/') {
+                format!("{}/", line)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Mark the lines of special files in the file list with their kind, e.g. `pipe\t# FIFO`.
+/// `files` are the listed files of the lines not starting with `note_prefix`, if it is not empty.
+fn mark_special_files(content: &str, files: &[PathBuf], note_prefix: &str) -> String {
+    let mut files = files.iter();
+    content
+        .lines()
+        .map(|line| {
+            if !note_prefix.is_empty() && line.starts_with(note_prefix) {
+                return line.to_string();
+            }
+            match files
+                .next()
+                .and_then(|file| transfer::special_file_kind(file))
+            {
+                Some(kind) => format!("{}{}{}", line, SPECIAL_FILE_MARK, kind),
+                None => line.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Remove the mark of special files from the edited names, as far as it was kept.
+/// `files` are the listed files of the edited names.
+fn unmark_special_files(edited_filenames: Vec<PathBuf>, files: &[PathBuf]) -> Vec<PathBuf> {
+    edited_filenames
+        .into_iter()
+        .zip(files)
+        .map(|(edited, file)| {
+            let mark = transfer::special_file_kind(file)
+                .map(|kind| format!("{}{}", SPECIAL_FILE_MARK, kind));
+            match (edited.to_str(), mark) {
+                (Some(name), Some(mark)) => match name.strip_suffix(&mark) {
+                    Some(name) => PathBuf::from(name),
+                    None => edited,
+                },
+                _ => edited,
+            }
+        })
+        .collect()
+}
+
+/// Replace `directory` at the start of each line of the file list with the abbreviation
+fn abbreviate(content: &str, directory: &Path) -> String {
+    content
+        .lines()
+        .map(|line| match Path::new(line).strip_prefix(directory) {
+            Ok(relative) if !line.starts_with(MERGE_NOTE_PREFIX) => {
+                // keep the mark of directories
+                let mark = if line.ends_with('/') { "/" } else { "" };
+                format!("{}/{}{}", ABBREVIATION, relative.to_string_lossy(), mark)
+            }
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parse the content of the temp file the user edited
+fn parse_temp_file_content(content: String) -> Vec<PathBuf> {
+    content
+        .lines()
+        // skip empty lines (usually the last line)
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// A problem with a line of the edited file list
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Diagnostic {
+    /// The line number, starting at 1
+    line: usize,
+    /// The file name in the line
+    text: String,
+    message: String,
+}
+
+impl Diagnostic {
+    fn new(line: usize, path: &Path, message: String) -> Self {
+        Self {
+            line,
+            text: path.to_string_lossy().to_string(),
+            message,
+        }
+    }
+
+    fn to_json(&self) -> JsonValue {
+        JsonValue::object([
+            ("line", self.line.into()),
+            ("text", self.text.as_str().into()),
+            ("message", self.message.as_str().into()),
+        ])
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+/// The edited file list is invalid. The problems are reported with their line numbers, also in
+/// JSON output, so that editor integrations can point to them.
+#[derive(Debug)]
+struct ValidationError {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl ValidationError {
+    /// Fail if there are any problems, ordered by their line
+    fn check(mut diagnostics: Vec<Diagnostic>) -> Result<()> {
+        if diagnostics.is_empty() {
+            return Ok(());
+        }
+        diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+        Err(ValidationError { diagnostics }.into())
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lines: Vec<String> = self.diagnostics.iter().map(ToString::to_string).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Ensure that the edited file names neither clash with each other
+/// nor use the name of a file as a directory. `edited_lines` are the lines of the file names.
+/// `is_kept_directory` tells whether the file with the given index is a listed directory that
+/// stays where it is, which may receive files.
+fn check_edited_filenames(
+    edited_filenames: &[PathBuf],
+    edited_lines: &[usize],
+    capabilities: &FilesystemCapabilities,
+    is_kept_directory: impl Fn(usize) -> bool,
+) -> Result<()> {
+    let mut diagnostics = Vec::new();
+    // on a case insensitive filesystem, names only differing in case clash as well.
+    // Each name maps to the index and line of its first file.
+    let mut first_files: HashMap<String, (usize, usize)> = HashMap::new();
+    for (i, (path, &line)) in edited_filenames.iter().zip(edited_lines).enumerate() {
+        match first_files.entry(capabilities.normalized(path)) {
+            Entry::Occupied(first) => diagnostics.push(Diagnostic::new(
+                line,
+                path,
+                format!(
+                    "{} is also the target in line {}.",
+                    path.to_string_lossy(),
+                    first.get().1
+                ),
+            )),
+            Entry::Vacant(entry) => {
+                entry.insert((i, line));
+            }
+        }
+    }
+    // A file cannot be a directory at the same time, e.g. `a.txt -> docs` and `docs/b.txt`.
+    // As unchanged files are part of the edited files, this also covers remaining sources.
+    for (path, &line) in edited_filenames.iter().zip(edited_lines) {
+        if let Some((ancestor, ancestor_line)) = path.ancestors().skip(1).find_map(|ancestor| {
+            first_files
+                .get(&capabilities.normalized(ancestor))
+                .filter(|&&(i, _)| !is_kept_directory(i))
+                .map(|&(_, ancestor_line)| (ancestor, ancestor_line))
+        }) {
+            diagnostics.push(Diagnostic::new(
+                ancestor_line,
+                ancestor,
+                format!(
+                    "The file {} would be the parent directory of {} in line {}.",
+                    ancestor.to_string_lossy(),
+                    path.to_string_lossy(),
+                    line
+                ),
+            ));
+        }
+    }
+    ValidationError::check(diagnostics)
+}
+
+/// Interpret the edited file names relative to the target root of --to-root.
+/// The names must stay below it, so that no file is moved elsewhere by a stray `..`.
+fn below_target_root(
+    edited_filenames: Vec<PathBuf>,
+    edited_lines: &[usize],
+    to_root: &Path,
+) -> Result<Vec<PathBuf>> {
+    let diagnostics = edited_filenames
+        .iter()
+        .zip(edited_lines)
+        .filter(|(path, _)| {
+            !path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        })
+        .map(|(path, &line)| {
+            Diagnostic::new(
+                line,
+                path,
+                format!(
+                    "{} leaves the target root {}.",
+                    path.to_string_lossy(),
+                    to_root.to_string_lossy()
+                ),
+            )
+        })
+        .collect();
+    ValidationError::check(diagnostics)?;
+    Ok(edited_filenames
+        .iter()
+        .map(|path| to_root.join(path))
+        .collect())
+}
+
+/// Quickly check the edited file list for problems while the editor is still open.
+/// The complete validation happens after editing, when the renaming request is created.
+/// With `dedupe_existing`, targets with the same content as their source may exist, and with
+/// `Overwrite::Trash` any target may exist.
+fn validate_edited_content(
+    original: &str,
+    edited: &str,
+    dedupe_existing: bool,
+    overwrite: Overwrite,
+) -> Vec<String> {
+    let original_files = parse_temp_file_content(original.to_string());
+    let known_files: HashSet<&PathBuf> = original_files.iter().collect();
+    let mut errors = Vec::new();
+    let edited_lines: Vec<(usize, &str)> = edited
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| (i + 1, line))
+        .collect();
+    if edited_lines.len() != original_files.len() {
+        errors.push(format!(
+            "The file list contains {} files, but {} files are expected.",
+            edited_lines.len(),
+            original_files.len()
+        ));
+    }
+    let mut targets: HashMap<&str, usize> = HashMap::new();
+    for ((line_number, line), original) in edited_lines.into_iter().zip(&original_files) {
+        if let Some(first) = targets.insert(line, line_number) {
+            errors.push(format!(
+                "Line {}: {} is also the target in line {}.",
+                line_number, line, first
+            ));
+            continue;
+        }
+        let path = PathBuf::from(line);
+        if overwrite == Overwrite::Abort
+            && !known_files.contains(&path)
+            && path.exists()
+            && !(dedupe_existing && same_content(original, &path).unwrap_or(false))
+        {
+            errors.push(format!("Line {}: {} already exists.", line_number, line));
+        }
+    }
+    errors
+}
+
+/// Expand placeholders in the edited file names. Unchanged names are kept as they are,
+/// so existing names containing braces are not affected.
+fn expand_edited_filenames(
+    original_filenames: &[PathBuf],
+    edited_filenames: Vec<PathBuf>,
+) -> Result<Vec<PathBuf>> {
+    original_filenames
+        .iter()
+        .zip(edited_filenames)
+        .map(|(original, edited)| {
+            let line = edited.to_string_lossy();
+            if *original == edited || !expand::has_placeholders(&line) {
+                return Ok(edited);
+            }
+            expand::expand_placeholders(&line, original).map(PathBuf::from)
+        })
+        .collect()
+}
+
+/// The renamings requested by editing the file list of a configuration, checked for
+/// conflicting and invalid names
+pub struct RenamingRequest {
+    config: BumvConfiguration,
+    all_files_at_creation_time: Vec<PathBuf>,
+    /// The line of each file in the edited file list, for pointing to problems
+    edited_lines: Vec<usize>,
+    mapping: Vec<(PathBuf, PathBuf)>,
+    /// Empty directories to remove, as requested with --allow-delete
+    removed_directories: Vec<PathBuf>,
+    capabilities: FilesystemCapabilities,
+}
+
+impl RenamingRequest {
+    /// List the files of `config` and pass the file list to `edit_function`, which returns the
+    /// edited one, e.g. by letting the user edit it. Fails if the edited file list is invalid,
+    /// e.g. because lines were added or removed, or two files would get the same name.
+    pub fn try_new<F: FnOnce(String) -> Result<String>>(
+        config: BumvConfiguration,
+        edit_function: F,
+    ) -> Result<Self> {
+        config.check_arguments()?;
+        if config.verbose && config.explicit_files().is_none() {
+            eprintln!("Ignore sources: {}", config.ignore_sources());
+        }
+        let original_filenames = config.ordered_file_list()?;
+        // a single given file is renamed like with `mv`, so there is nothing to summarize
+        let single_file = config.explicit_files().is_some() && original_filenames.len() == 1;
+        if config.needs_editor() && !single_file {
+            // so that the user can bail out if the selection is larger than intended
+            eprintln!(
+                "{}",
+                listing_summary(&original_filenames, config.base_directory())
+            );
+        }
+        // a dry run renames nothing, so it works in read-only directories as well
+        let unwritable_directories = if config.is_dry_run() {
+            BTreeMap::new()
+        } else {
+            find_unwritable_directories(&original_filenames)
+        };
+        if let Some(error) = unwritable_directories.get(config.base_directory()) {
+            anyhow::bail!(
+                "Files cannot be renamed in {}: {}",
+                config.base_directory().to_string_lossy(),
+                error
+            );
+        }
+        if !unwritable_directories.is_empty() {
+            eprintln!("Warning: Files in these directories cannot be renamed:");
+            for (directory, error) in unwritable_directories.iter().take(SUMMARY_DIRECTORIES) {
+                eprintln!("  {}: {}", directory.to_string_lossy(), error);
+            }
+            if unwritable_directories.len() > SUMMARY_DIRECTORIES {
+                eprintln!(
+                    "  ... and {} more directories",
+                    unwritable_directories.len() - SUMMARY_DIRECTORIES
+                );
+            }
+        }
+        let case_collisions = case_collision::case_collisions(&original_filenames);
+        if !case_collisions.is_empty() {
+            eprintln!("Warning: These files clash on case insensitive filesystems:");
+            for group in case_collisions.iter().take(SUMMARY_DIRECTORIES) {
+                let files: Vec<_> = group.iter().map(|file| file.to_string_lossy()).collect();
+                eprintln!("  {}", files.join(", "));
+            }
+            if case_collisions.len() > SUMMARY_DIRECTORIES {
+                eprintln!(
+                    "  ... and {} more",
+                    case_collisions.len() - SUMMARY_DIRECTORIES
+                );
+            }
+            if !config.separate_case {
+                eprintln!("Use --separate-case to propose renaming them apart.");
+            }
+        }
+        // given files are probed in their directories, as the current directory may be on another
+        // filesystem or not writable at all. A dry run must not modify the filesystem.
+        let capabilities = FilesystemCapabilities::common(
+            config
+                .base_directories()
+                .into_iter()
+                .map(|directory| {
+                    if config.is_dry_run() {
+                        Ok(FilesystemCapabilities::detect(directory))
+                    } else {
+                        FilesystemCapabilities::probe(directory)
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?,
+        );
+        let temp_file_content = config.proposed_file_list(&original_filenames)?;
+        let temp_file_content = if config.include_empty_dirs {
+            mark_directories(&temp_file_content, &original_filenames)
+        } else {
+            temp_file_content
+        };
+        // mappings and diffs refer to the names as listed, without marks
+        let temp_file_content = if config.needs_editor() {
+            let note_prefix = if config.merge_into.is_some() {
+                MERGE_NOTE_PREFIX
+            } else {
+                ""
+            };
+            mark_special_files(&temp_file_content, &original_filenames, note_prefix)
+        } else {
+            temp_file_content
+        };
+        let abbreviation = if config.abbreviate {
+            common_directory(&temp_file_content)
+        } else {
+            None
+        };
+        let temp_file_content = match &abbreviation {
+            Some(directory) => {
+                eprintln!(
+                    "In the file list, {} stands for {}",
+                    ABBREVIATION,
+                    directory.to_string_lossy()
+                );
+                abbreviate(&temp_file_content, directory)
+            }
+            None => temp_file_content,
+        };
+        let listed_content = temp_file_content.clone();
+        let mut modified_temp_file_content = edit_function(temp_file_content)?;
+        if config.merge_into.is_some() {
+            modified_temp_file_content = blank_merge_notes(&modified_temp_file_content);
+        }
+        let edited_lines: Vec<usize> = modified_temp_file_content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(i, _)| i + 1)
+            .collect();
+        let mut edited_filenames = parse_temp_file_content(modified_temp_file_content);
+        if let Some(directory) = &abbreviation {
+            edited_filenames = edited_filenames
+                .into_iter()
+                .map(|path| match path.strip_prefix(ABBREVIATION) {
+                    Ok(relative) => directory.join(relative),
+                    Err(_) => path,
+                })
+                .collect();
+        }
+        if original_filenames.len() != edited_filenames.len() {
+            anyhow::bail!("The number of files in the edited file does not match the original.");
+        }
+        if config.needs_editor() {
+            edited_filenames = unmark_special_files(edited_filenames, &original_filenames);
+        }
+        // the lines of removed directories are restored, so that they are not renamed. Names
+        // listed with the prefix already, e.g. of a directory `#archive`, are only removed if
+        // prefixed once more, like `##archive`.
+        let removed: Vec<usize> = if config.allow_delete {
+            let listed_filenames = parse_temp_file_content(if config.merge_into.is_some() {
+                blank_merge_notes(&listed_content)
+            } else {
+                listed_content
+            });
+            (0..original_filenames.len())
+                .filter(|&i| {
+                    let edited = edited_filenames[i].to_string_lossy();
+                    let listed = listed_filenames[i].to_string_lossy();
+                    let prefixed = if listed.starts_with(REMOVE_PREFIX) {
+                        edited.strip_prefix(REMOVE_PREFIX) == Some(listed.as_ref())
+                    } else {
+                        edited.starts_with(REMOVE_PREFIX)
+                    };
+                    prefixed && original_filenames[i].is_dir()
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        if let Some(to_root) = &config.to_root {
+            edited_filenames = below_target_root(edited_filenames, &edited_lines, to_root)?;
+        }
+        if let Some(template) = &config.renumber {
+            edited_filenames =
+                renumber::renumber(&original_filenames, &edited_filenames, template)?;
+        } else if !config.no_expand {
+            edited_filenames = expand_edited_filenames(&original_filenames, edited_filenames)?;
+        }
+        for &i in &removed {
+            edited_filenames[i] = original_filenames[i].clone();
+        }
+        let removed_directories: Vec<PathBuf> = removed
+            .iter()
+            .map(|&i| original_filenames[i].clone())
+            .collect();
+        check_edited_filenames(&edited_filenames, &edited_lines, &capabilities, |i| {
+            edited_filenames[i] == original_filenames[i] && original_filenames[i].is_dir()
+        })?;
+        if let Some((old, _)) =
+            original_filenames
+                .iter()
+                .zip(edited_filenames.iter())
+                .find(|(old, new)| {
+                    old != new && unwritable_directories.contains_key(parent_directory(old))
+                })
+        {
+            anyhow::bail!(
+                "The file {} cannot be renamed, as its directory is not writable.",
+                old.to_string_lossy()
+            );
+        }
+
+        let mapping: Vec<(PathBuf, PathBuf)> = original_filenames
+            .iter()
+            .zip(edited_filenames.iter())
+            .filter(|(old, new)| old != new)
+            .map(|(old, new)| (old.clone(), new.clone()))
+            .collect();
+        let protected_files = ProtectedFiles::try_new(config.base_directory(), &config.protect)?;
+        if let Some((old, _)) = mapping
+            .iter()
+            .find(|(old, _)| protected_files.is_protected(old))
+        {
+            anyhow::bail!(
+                "The file {} is protected and must not be renamed.",
+                old.to_string_lossy()
+            );
+        }
+        if config.no_rename_across_devices {
+            for (old, new) in &mapping {
+                anyhow::ensure!(
+                    transfer::same_device(old, new)?,
+                    "Moving {} to {} would copy it to another device.",
+                    old.to_string_lossy(),
+                    new.to_string_lossy()
+                );
+            }
+        }
+        // special files cannot be copied, so they must stay on their device
+        for (old, new) in &mapping {
+            if let Some(kind) = transfer::special_file_kind(old) {
+                anyhow::ensure!(
+                    transfer::same_device(old, new)?,
+                    "{} is a {}, which cannot be moved to another device.",
+                    old.to_string_lossy(),
+                    kind
+                );
+            }
+        }
+        // directories that will not exist anymore, e.g. `old/ -> new/` and `old/a.txt`
+        let vacated_directories: Vec<(&PathBuf, &str)> = removed_directories
+            .iter()
+            .map(|directory| (directory, "removed"))
+            .chain(
+                mapping
+                    .iter()
+                    .filter(|(old, _)| old.is_dir())
+                    .map(|(old, _)| (old, "renamed")),
+            )
+            .collect();
+        let mut diagnostics = Vec::new();
+        for ((old, new), &line) in original_filenames
+            .iter()
+            .zip(edited_filenames.iter())
+            .zip(edited_lines.iter())
+        {
+            if let Some((directory, change)) = vacated_directories
+                .iter()
+                .find(|(directory, _)| new != *directory && new.starts_with(directory))
+            {
+                diagnostics.push(Diagnostic::new(
+                    line,
+                    new,
+                    format!(
+                        "{} is {}, so no file can be moved into it.",
+                        directory.to_string_lossy(),
+                        change
+                    ),
+                ));
+            }
+            // whitespace changes the filesystem cannot represent are dropped before confirmation
+            if old == new || changes_only_whitespace(old, new) {
+                continue;
+            }
+            if let Some(error) = new.components().find_map(|component| match component {
+                Component::Normal(name) => capabilities
+                    .validate_file_name(&name.to_string_lossy())
+                    .err(),
+                _ => None,
+            }) {
+                diagnostics.push(Diagnostic::new(line, new, error.to_string()));
+            }
+        }
+        ValidationError::check(diagnostics)?;
+        Ok(Self {
+            config,
+            all_files_at_creation_time: original_filenames,
+            edited_lines,
+            mapping,
+            removed_directories,
+            capabilities,
+        })
+    }
+
+    /// Whether there is nothing to rename and no directory to remove
+    pub fn is_empty(&self) -> bool {
+        self.mapping.is_empty() && self.removed_directories.is_empty()
+    }
+
+    /// The requested renamings from old to new paths, in the order of the file list
+    pub fn mapping(&self) -> &[(PathBuf, PathBuf)] {
+        &self.mapping
+    }
+
+    /// Restrict the request to the renamings of files for which `keep` is true.
+    /// Fails if the kept renamings clash with files that are not renamed anymore.
+    fn restricted_to(self, keep: impl Fn(&Path) -> bool) -> Result<Self> {
+        let mapping: Vec<(PathBuf, PathBuf)> = self
+            .mapping
+            .into_iter()
+            .filter(|(old, _)| keep(old))
+            .collect();
+        let renames: HashMap<&PathBuf, &PathBuf> =
+            mapping.iter().map(|(old, new)| (old, new)).collect();
+        let edited_filenames: Vec<PathBuf> = self
+            .all_files_at_creation_time
+            .iter()
+            .map(|path| renames.get(path).copied().unwrap_or(path).clone())
+            .collect();
+        let original_filenames = &self.all_files_at_creation_time;
+        check_edited_filenames(
+            &edited_filenames,
+            &self.edited_lines,
+            &self.capabilities,
+            |i| edited_filenames[i] == original_filenames[i] && original_filenames[i].is_dir(),
+        )
+        .context("The confirmed renamings conflict with the declined ones")?;
+        Ok(Self { mapping, ..self })
+    }
+
+    /// Create the lines of a unified diff between the original and the edited file list
+    fn unified_diff(&self) -> Vec<DiffLine> {
+        let renames: HashMap<&PathBuf, &PathBuf> =
+            self.mapping.iter().map(|(old, new)| (old, new)).collect();
+        let original: Vec<String> = self
+            .all_files_at_creation_time
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        let edited: Vec<String> = self
+            .all_files_at_creation_time
+            .iter()
+            .map(|path| {
+                renames
+                    .get(path)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        diff::unified_diff_lines(&original, &edited)
+    }
+
+    /// Ensure that the files have not changed since this request was created
+    fn ensure_files_did_not_change(&self) -> Result<()> {
+        // Compare in a single pass without sorting. As there are no duplicates in either listing,
+        // they are equal if every walked file was known and the number of files is the same.
+        let snapshot: HashSet<&Path> = self
+            .all_files_at_creation_time
+            .iter()
+            .map(PathBuf::as_path)
+            .collect();
+        // the plan graph and report may have been written to the base path in the meantime
+        let written: Vec<PathBuf> = [&self.config.plan_dot, &self.config.report_md]
+            .into_iter()
+            .flatten()
+            .filter_map(|path| fs::canonicalize(path).ok())
+            .collect();
+        let mut count = 0;
+        for path in self.config.walk()? {
+            if !written.is_empty()
+                && !snapshot.contains(path.as_path())
+                && fs::canonicalize(&path).is_ok_and(|path| written.contains(&path))
+            {
+                continue;
+            }
+            count += 1;
+            anyhow::ensure!(
+                snapshot.contains(path.as_path()),
+                "The files in the directory changed while you were editing them."
+            );
+        }
+        anyhow::ensure!(
+            count == snapshot.len(),
+            "The files in the directory changed while you were editing them."
+        );
+        Ok(())
+    }
+
+    /// The path of the log file called bumv_{session ID}.log in the base path of the renaming request,
+    /// or the current directory if none is specified.
+    fn log_file_path(&self) -> PathBuf {
+        let log_file_name = format!("bumv_{}.log", self.config.session);
+        self.config.base_directory().join(log_file_name)
+    }
+
+    /// The part of the requested mapping that was completed by the given steps.
+    /// A requested renaming is complete once a step has renamed a file to its target.
+    fn completed_mapping(&self, steps: &[(PathBuf, PathBuf)]) -> Vec<(PathBuf, PathBuf)> {
+        let targets: HashSet<&Path> = steps.iter().map(|(_, new)| new.as_path()).collect();
+        self.mapping
+            .iter()
+            .filter(|(_, new)| targets.contains(new.as_path()))
+            .cloned()
+            .collect()
+    }
+
+    // Write the log file containing the created directories and the renaming mapping.
+    // The mapping is based on the request, because the user is usually not interested in the
+    // temporary files created in the planning phase. The performed steps including temporary
+    // files follow as comments, so an interrupted run can be reconstructed. The state of each
+    // renamed file is recorded as well, so that --undo can tell whether it changed since.
+    fn write_renaming_log_file(
+        &self,
+        log_file_path: &Path,
+        created_directories: &[PathBuf],
+        removed_directories: &[PathBuf],
+        trashed: &[PathBuf],
+        removed_duplicates: &[(PathBuf, PathBuf)],
+        steps: &[(PathBuf, PathBuf)],
+    ) -> Result<()> {
+        let mapping = self.completed_mapping(steps);
+        let states: Vec<String> = mapping
+            .iter()
+            .filter_map(|(_, new)| {
+                FileState::of(new)
+                    .ok()
+                    .map(|state| undo::state_line(new, state))
+            })
+            .collect();
+        let content = mapping_file_content(
+            created_directories,
+            removed_directories,
+            trashed,
+            removed_duplicates,
+            &mapping,
+            steps,
+            &states,
+        );
+        File::create(log_file_path)
+            .and_then(|mut log_file| log_file.write_all(content.as_bytes()))
+            .with_context(|| {
+                format!(
+                    "Failed to write the log file {}",
+                    log_file_path.to_string_lossy()
+                )
+            })
+    }
+}
+
+/// Format the created and removed directories, the targets moved to the trash, the removed
+/// duplicates, the rename mapping, the states of the renamed files and the performed steps as
+/// used in log files, after the header naming the format version. Steps involving temporary
+/// files are marked as internal.
+fn mapping_file_content(
+    created_directories: &[PathBuf],
+    removed_directories: &[PathBuf],
+    trashed: &[PathBuf],
+    removed_duplicates: &[(PathBuf, PathBuf)],
+    mapping: &[(PathBuf, PathBuf)],
+    steps: &[(PathBuf, PathBuf)],
+    states: &[String],
+) -> String {
+    // format the rename mapping to be tab separated, with nicely aligned columns
+    let rows: Vec<(String, String)> = mapping
+        .iter()
+        .map(|(old, new)| {
+            (
+                mapping_file::field(&old.to_string_lossy()).into_owned(),
+                mapping_file::field(&new.to_string_lossy()).into_owned(),
+            )
+        })
+        .collect();
+    let requested: HashSet<&(PathBuf, PathBuf)> = mapping.iter().collect();
+    // created directories are listed as comments first, the steps as comments last
+    std::iter::once(mapping_file::header())
+        .chain(
+            created_directories
+                .iter()
+                .map(|directory| format!("# created directory {}", directory.to_string_lossy())),
+        )
+        .chain(
+            removed_directories
+                .iter()
+                .map(|directory| format!("# removed directory {}", directory.to_string_lossy())),
+        )
+        .chain(
+            trashed
+                .iter()
+                .map(|path| format!("# moved to trash {}", path.to_string_lossy())),
+        )
+        .chain(removed_duplicates.iter().map(|(old, new)| {
+            format!(
+                "# removed duplicate {}, identical to {}",
+                old.to_string_lossy(),
+                new.to_string_lossy()
+            )
+        }))
+        .chain(table::align_columns(&rows, "\t"))
+        .chain(states.iter().cloned())
+        .chain(steps.iter().enumerate().map(|(i, step)| {
+            format!(
+                "# step {}: {} -> {}{}",
+                i + 1,
+                step.0.to_string_lossy(),
+                step.1.to_string_lossy(),
+                if requested.contains(step) {
+                    ""
+                } else {
+                    " (internal)"
+                }
+            )
+        }))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Append the removed duplicates and the performed steps to a batch file as `old\0new\0`
+/// records, in the order they were performed. A removed duplicate has an empty new path.
+/// Steps from and to temporary files are included, so the renaming can be replayed.
+fn append_batch_file(
+    path: &Path,
+    removed_duplicates: &[(PathBuf, PathBuf)],
+    steps: &[(PathBuf, PathBuf)],
+) -> Result<()> {
+    let mut content = Vec::new();
+    for (old, _) in removed_duplicates {
+        content.extend_from_slice(&path_bytes(old));
+        content.extend_from_slice(&[0, 0]);
+    }
+    for (old, new) in steps {
+        for path in [old, new] {
+            content.extend_from_slice(&path_bytes(path));
+            content.push(0);
+        }
+    }
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(&content))
+        .with_context(|| format!("Failed to write the batch file {}", path.to_string_lossy()))
+}
+
+/// The bytes of a path, which need not be valid UTF-8
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+/// The bytes of a path as UTF-8, as paths are not stored as bytes on this platform
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    match path.to_string_lossy() {
+        Cow::Borrowed(path) => Cow::Borrowed(path.as_bytes()),
+        Cow::Owned(path) => Cow::Owned(path.into_bytes()),
+    }
+}
+
+/// The width lines of the file list should not exceed, as given by --max-line-width or by
+/// an editor that is known to wrap long lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LineWidth {
+    columns: usize,
+    /// Whether the editor wraps lines longer than the width
+    wrapped: bool,
+}
+
+impl LineWidth {
+    /// The smaller one of both widths, if any is given
+    fn new(max_line_width: Option<usize>, wrapping_width: Option<usize>) -> Option<Self> {
+        match (max_line_width, wrapping_width) {
+            (Some(max), Some(wrapping)) if max < wrapping => Some(Self {
+                columns: max,
+                wrapped: false,
+            }),
+            (_, Some(wrapping)) => Some(Self {
+                columns: wrapping,
+                wrapped: true,
+            }),
+            (Some(max), None) => Some(Self {
+                columns: max,
+                wrapped: false,
+            }),
+            (None, None) => None,
+        }
+    }
+
+    /// A warning if lines of `content` are longer than the width
+    fn long_lines_warning(&self, content: &str) -> Option<String> {
+        let long_lines = content
+            .lines()
+            .filter(|line| line.chars().count() > self.columns)
+            .count();
+        if long_lines == 0 {
+            return None;
+        }
+        let consequence = if self.wrapped {
+            "Your editor wraps them, which breaks the file list, as files are matched to lines by \
+             their position."
+        } else {
+            "Make sure your editor does not wrap them, as files are matched to lines by their \
+             position."
+        };
+        let lines = match long_lines {
+            1 => "1 line is".to_string(),
+            n => format!("{} lines are", n),
+        };
+        Some(format!(
+            "Warning: {} longer than {} characters. {} Use --abbreviate or run bumv in \
+             the base path to shorten them.",
+            lines, self.columns, consequence
+        ))
+    }
+
+    /// Print the warning about long lines of `content` to stderr, if there are any
+    fn warn_about_long_lines(&self, content: &str) {
+        if let Some(warning) = self.long_lines_warning(content) {
+            eprintln!("{}", warning);
+        }
+    }
+}
+
+/// The outcome of a bulk renaming session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Renamed,
+    Aborted,
+    /// Renaming was declined for a later batch or some of the directories
+    PartiallyRenamed,
+    NothingToRename,
+    WouldRename,
+}
+
+impl Outcome {
+    fn message(&self) -> &'static str {
+        match self {
+            Outcome::Renamed => "Files renamed successfully.",
+            Outcome::Aborted => "Aborted.",
+            Outcome::PartiallyRenamed => "Only some of the files were renamed, see the log file.",
+            Outcome::NothingToRename => "No files to rename.",
+            Outcome::WouldRename => "Dry run, no files were renamed.",
+        }
+    }
+
+    /// Identifier of the outcome in JSON output
+    fn name(&self) -> &'static str {
+        match self {
+            Outcome::Renamed => "renamed",
+            Outcome::Aborted => "aborted",
+            Outcome::PartiallyRenamed => "partially_renamed",
+            Outcome::NothingToRename => "nothing_to_rename",
+            Outcome::WouldRename => "would_rename",
+        }
+    }
+
+    fn exit_code(&self) -> i32 {
+        match self {
+            Outcome::Renamed | Outcome::NothingToRename => 0,
+            Outcome::Aborted | Outcome::PartiallyRenamed => EXIT_CODE_ABORTED,
+            Outcome::WouldRename => EXIT_CODE_WOULD_RENAME,
+        }
+    }
+}
+
+/// Bulk rename files according to the configuration
+/// `edit_function` and `prompt_function` are passed as parameters to allow for testing.
+fn bulk_rename(
+    config: BumvConfiguration,
+    edit_function: impl Fn(String) -> Result<String>,
+    prompt_function: impl FnMut(String) -> bool,
+) -> Result<Outcome> {
+    bulk_rename_session(
+        config,
+        edit_function,
+        prompt_function,
+        &mut SessionReport::default(),
+    )
+}
+
+/// Bulk rename files like `bulk_rename`, recording the performed steps and the plan of a dry run
+/// in `report`
+fn bulk_rename_session(
+    config: BumvConfiguration,
+    edit_function: impl Fn(String) -> Result<String>,
+    mut prompt_function: impl FnMut(String) -> bool,
+    report: &mut SessionReport,
+) -> Result<Outcome> {
+    if let Some(archive) = &config.in_archive {
+        let outcome = rename_in_archive(&config, archive, edit_function, prompt_function)?;
+        if !config.json {
+            println!("{}", outcome.message());
+        }
+        return Ok(outcome);
+    }
+    let request = RenamingRequest::try_new(config, edit_function)?;
+
+    let plan = RenamingPlan::try_new(request)?;
+    let plan = if plan.request.config.is_dry_run() {
+        plan
+    } else {
+        resolve_subtle_renamings(plan, &mut prompt_function)?
+    };
+    let json = plan.request.config.json;
+    if let Some(path) = &plan.request.config.plan_dot {
+        fs::write(path, dot::plan_dot(&plan.request.mapping, &plan.steps))
+            .with_context(|| format!("Failed to write the plan graph to {}", path.display()))?;
+    }
+    if let Some(path) = &plan.request.config.plan_out {
+        let document = plan_file::document(&plan.request.config, plan.to_json());
+        fs::write(path, format!("{}\n", document))
+            .with_context(|| format!("Failed to write the plan to {}", path.display()))?;
+    }
+    if let Some(path) = &plan.request.config.report_md {
+        if !plan.is_empty() {
+            fs::write(path, plan.markdown_report())
+                .with_context(|| format!("Failed to write the report to {}", path.display()))?;
+        }
+    }
+
+    let outcome = if plan.is_empty() {
+        Outcome::NothingToRename
+    } else if plan.request.config.is_dry_run() {
+        print_human_readable(
+            &plan.human_readable_rename_mapping(),
+            plan.request.config.json,
+        );
+        report.plan = Some(plan.to_json());
+        Outcome::WouldRename
+    } else if plan.request.config.confirm_per_dir {
+        confirm_per_directory_and_execute(plan, prompt_function, &mut report.steps)?
+    } else if prompt_function(plan.human_readable_rename_mapping())
+        && plan.confirm_leaving(&mut prompt_function)
+    {
+        if plan.execute(prompt_function, &mut report.steps)? {
+            Outcome::Renamed
+        } else {
+            Outcome::PartiallyRenamed
+        }
+    } else {
+        // no log is written, but the plan can be saved to apply it later
+        if let Some(path) = &plan.request.config.save_rejected_plan {
+            let document = plan_file::document(&plan.request.config, plan.to_json());
+            fs::write(path, format!("{}\n", document)).with_context(|| {
+                format!("Failed to save the rejected plan to {}", path.display())
+            })?;
+        }
+        Outcome::Aborted
+    };
+    if !json {
+        println!("{}", outcome.message());
+    }
+    Ok(outcome)
+}
+
+/// Rename the members of an archive given with `--in-archive`. The member names are edited and
+/// validated like file names, and the archive is rewritten once the renamings are confirmed.
+fn rename_in_archive(
+    config: &BumvConfiguration,
+    archive: &Path,
+    edit_function: impl Fn(String) -> Result<String>,
+    mut prompt_function: impl FnMut(String) -> bool,
+) -> Result<Outcome> {
+    let members = archive::member_names(archive)?;
+    let edited = edit_function(members.join("\n"))?;
+    let edited_lines: Vec<usize> = edited
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, _)| i + 1)
+        .collect();
+    let edited_members = parse_temp_file_content(edited);
+    anyhow::ensure!(
+        edited_members.len() == members.len(),
+        "The number of members changed from {} to {}. Only rename members, do not add or remove lines.",
+        members.len(),
+        edited_members.len()
+    );
+    // members are stored relative to the root of the archive
+    let diagnostics = edited_members
+        .iter()
+        .zip(&edited_lines)
+        .filter(|(path, _)| {
+            !path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        })
+        .map(|(path, &line)| {
+            Diagnostic::new(
+                line,
+                path,
+                format!("{} leaves the archive.", path.to_string_lossy()),
+            )
+        })
+        .collect();
+    ValidationError::check(diagnostics)?;
+    // directory entries are not listed, so no member is a directory that may receive members
+    check_edited_filenames(
+        &edited_members,
+        &edited_lines,
+        &FilesystemCapabilities::default(),
+        |_| false,
+    )?;
+    let renamings: Vec<(&str, String)> = members
+        .iter()
+        .zip(&edited_members)
+        .map(|(old, new)| (old.as_str(), new.to_string_lossy().to_string()))
+        .filter(|(old, new)| old != new)
+        .collect();
+    if renamings.is_empty() {
+        return Ok(Outcome::NothingToRename);
+    }
+    // members are not files, so there are no special files among them
+    let changes = Segment::Changes(
+        renamings
+            .iter()
+            .map(|(old, new)| Change::Rename {
+                old: PathBuf::from(old),
+                new: PathBuf::from(new),
+                kind: None,
+                size: None,
+            })
+            .collect(),
+    );
+    let mapping = format!(
+        "In the archive {}:\n{}",
+        archive.to_string_lossy(),
+        changes.to_text(config.preview)
+    );
+    if config.dry_run {
+        print_human_readable(&mapping, config.json);
+        return Ok(Outcome::WouldRename);
+    }
+    if !prompt_function(mapping) {
+        return Ok(Outcome::Aborted);
+    }
+    let renamings: HashMap<&str, &str> = renamings
+        .iter()
+        .map(|(old, new)| (*old, new.as_str()))
+        .collect();
+    archive::rename_members(archive, &renamings)?;
+    Ok(Outcome::Renamed)
+}
+
+/// Bulk rename files like `bulk_rename`. With `--loop`, a new session over the renamed files
+/// is started after each session that renamed files, until a session does not rename any.
+/// `edit_function` creates the function editing the file list of a session, and
+/// `session_finished` is called with the result and the report of each session.
+fn bulk_rename_in_loop<E: Fn(String) -> Result<String>>(
+    mut config: BumvConfiguration,
+    edit_function: impl Fn(&BumvConfiguration) -> Result<E>,
+    mut prompt_function: impl FnMut(String) -> bool,
+    mut session_finished: impl FnMut(&SessionId, &Result<Outcome>, &SessionReport),
+) -> Result<Outcome> {
+    loop {
+        let session = config.session.clone();
+        let mut report = SessionReport::default();
+        let result = edit_function(&config).and_then(|edit| {
+            bulk_rename_session(config.clone(), edit, &mut prompt_function, &mut report)
+        });
+        session_finished(&session, &result, &report);
+        if !(config.repeat && matches!(result, Ok(Outcome::Renamed))) {
+            return result;
+        }
+        config.session = SessionId::new();
+        if !config.json {
+            println!("Starting a new session, decline or leave the file list unchanged to stop.");
+        }
+    }
+}
+
+/// Ask for confirmation of the renamings directory by directory,
+/// then execute the plan restricted to the confirmed directories
+fn confirm_per_directory_and_execute(
+    plan: RenamingPlan,
+    mut prompt_function: impl FnMut(String) -> bool,
+    statistics: &mut Vec<StepStatistics>,
+) -> Result<Outcome> {
+    let groups = render::group_by_directory(&plan.request.mapping);
+    let group_count = groups.len();
+    let mut confirmed: HashSet<PathBuf> = HashSet::new();
+    for (directory, renamings) in groups {
+        let warnings: Vec<Finding> = plan
+            .warnings
+            .iter()
+            .filter(|finding| renamings.iter().any(|(old, _)| *old == finding.old))
+            .cloned()
+            .collect();
+        let segments: Vec<Segment> = std::iter::once(Segment::Changes(
+            renamings
+                .iter()
+                .map(|(old, new)| plan.request.config.rename_change(old, new))
+                .collect(),
+        ))
+        .chain((!warnings.is_empty()).then_some(Segment::Lint(warnings)))
+        .collect();
+        let message = format!(
+            "In {}:\n{}",
+            directory.to_string_lossy(),
+            render::to_text(&segments, plan.request.config.preview)
+        );
+        if prompt_function(message) {
+            confirmed.insert(directory.to_path_buf());
+        }
+    }
+    if confirmed.is_empty() {
+        return Ok(Outcome::Aborted);
+    }
+    let all_confirmed = confirmed.len() == group_count;
+    let plan = if all_confirmed {
+        plan
+    } else {
+        let request = plan.request.restricted_to(|old| {
+            confirmed.contains(old.parent().unwrap_or_else(|| Path::new("")))
+        })?;
+        RenamingPlan::try_new(request)?
+    };
+    if !plan.confirm_leaving(&mut prompt_function) {
+        return Ok(Outcome::Aborted);
+    }
+    let completed = plan.execute(prompt_function, statistics)?;
+    Ok(if completed && all_confirmed {
+        Outcome::Renamed
+    } else {
+        Outcome::PartiallyRenamed
+    })
+}
+
+/// Print output meant for humans.
+/// In JSON mode, it is printed to stderr to keep stdout machine readable.
+fn print_human_readable(text: &str, json: bool) {
+    if json {
+        eprintln!("{}", text);
+    } else {
+        println!("{}", text);
+    }
+}
+
+/// POST the JSON summary of a session to a webhook
+fn notify(url: &str, summary: &JsonValue) -> Result<()> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(10))
+        .build();
+    agent
+        .post(url)
+        .set("Content-Type", "application/json")
+        .send_string(&summary.to_string())
+        .with_context(|| format!("Failed to notify the webhook {}", url))?;
+    Ok(())
+}
+
+/// Prompt the user for confirmation, or only print the mapping if `--yes` was given
+fn prompt_for_confirmation(
+    human_readable_mapping: String,
+    json: bool,
+    confirm_default: ConfirmDefault,
+    yes: bool,
+) -> bool {
+    print_human_readable(&human_readable_mapping, json);
+    if yes {
+        return true;
+    }
+    let choices = match confirm_default {
+        ConfirmDefault::Yes => "[Y/n]",
+        ConfirmDefault::No => "[y/N]",
+    };
+    match rprompt::prompt_reply(format!("\nRename: {}? ", choices)) {
+        Ok(input) => is_confirmation(&input, confirm_default),
+        Err(e) => {
+            eprintln!("Failed to read the answer, not renaming: {}", e);
+            false
+        }
+    }
+}
+
+/// Interpret the answer to the confirmation prompt
+fn is_confirmation(input: &str, confirm_default: ConfirmDefault) -> bool {
+    match input.trim().to_lowercase().as_str() {
+        "" => confirm_default == ConfirmDefault::Yes,
+        "y" | "yes" => true,
+        _ => false,
+    }
+}
+
+/// Run bumv with the arguments of the process, reading the configuration file. Exits the
+/// process with the exit code of the outcome, e.g. when the user declined the renaming.
+pub fn run() -> Result<()> {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    // --help and --version exit here, so that they work even with a malformed configuration file
+    let command_line = BumvConfiguration::from_iter(&args);
+    let config_file = ConfigFile::load()?;
+    let mut config =
+        BumvConfiguration::from_args_with_path_flags(command_line, args, &config_file)?;
+    config.apply_config_file(&config_file)?;
+    if config.stdin {
+        config.stdin_listing = Some(listing::Stdin::read(std::io::stdin().lock())?);
+    }
+    if config.clean_logs {
+        let removed = logs::clean_logs(
+            config.base_directory(),
+            config.recursive,
+            config.older_than,
+            SystemTime::now(),
+        )?;
+        let removed: Vec<String> = removed
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        if config.json {
+            println!("{}", JsonValue::object([("removed", removed.into())]));
+        } else {
+            removed.iter().for_each(|path| println!("Removed {}", path));
+        }
+        return Ok(());
+    }
+    if let Some(path) = &config.explain_ignores {
+        println!(
+            "{}",
+            explain::explain_ignores(
+                path,
+                config.base_directory(),
+                config.ignore_sources(),
+                config.lists_recursively(),
+                config.include_bumv_files,
+            )?
+        );
+        return Ok(());
+    }
+    if config.doctor {
+        let checks = doctor::diagnose(&config, &config_file);
+        if config.json {
+            let checks: Vec<JsonValue> = checks.iter().map(doctor::Check::to_json).collect();
+            println!("{}", JsonValue::object([("checks", checks.into())]));
+        } else {
+            println!("{}", doctor::report(&checks));
+        }
+        std::process::exit(if checks.iter().all(|check| check.ok) {
+            0
+        } else {
+            1
+        });
+    }
+    if config.show_computed {
+        println!("{}", config.computed_names()?);
+        let interactive = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+        let proceed = interactive
+            && rprompt::prompt_reply("\nOpen the editor with these names? [Y/n] ")
+                .is_ok_and(|input| is_confirmation(&input, ConfirmDefault::Yes));
+        if !proceed {
+            return Ok(());
+        }
+    }
+    if config.lists_only(
+        std::io::stdin().is_terminal(),
+        std::io::stdout().is_terminal(),
+    ) {
+        println!("{}", config.listing()?);
+        return Ok(());
+    }
+    let json = config.json;
+    let confirm_default = config.confirm_default.unwrap_or_default();
+    let yes = config.yes;
+    let mapping = match (&config.mapping, &config.undo, &config.apply) {
+        (Some(path), _, _) => {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read the mapping file {}", path.display()))?;
+            Some(
+                mapping_file::parse_mapping(&content)
+                    .with_context(|| format!("Invalid mapping file {}", path.display()))?,
+            )
+        }
+        (None, Some(path), _) => {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read the log file {}", path.display()))?;
+            Some(
+                undo::undo_mapping(&content)
+                    .with_context(|| format!("Cannot undo the log file {}", path.display()))?,
+            )
+        }
+        (None, None, Some(path)) => {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read the plan {}", path.display()))?;
+            let (mapping, options) = plan_file::requested_mapping(&content)
+                .with_context(|| format!("Cannot apply the plan {}", path.display()))?;
+            options.apply_to(&mut config);
+            Some(mapping)
+        }
+        (None, None, None) => None,
+    };
+    // without a base path or files, exactly the files of the mapping are listed
+    if let (Some(mapping), None) = (&mapping, &config.base_path) {
+        let mut sources = mapping.iter().map(|(old, _)| old.clone());
+        config.base_path = sources.next();
+        config.files = sources.collect();
+    }
+    let patch = match &config.apply_diff {
+        Some(path) => Some(
+            fs::read_to_string(path)
+                .with_context(|| format!("Failed to read the diff {}", path.display()))?,
+        ),
+        None => None,
+    };
+    // the editor is set up for each session, so that the temp file is named after it
+    let edit_function = move |config: &BumvConfiguration| -> Result<Box<EditFunction>> {
+        let edit_function: Box<EditFunction> = match (&mapping, &patch) {
+            (Some(mapping), _) => {
+                let mapping = mapping.clone();
+                Box::new(move |content| mapping_file::apply_mapping(&content, &mapping))
+            }
+            (None, Some(patch)) => {
+                let patch = patch.clone();
+                Box::new(move |content| diff::apply_unified_diff(&content, &patch))
+            }
+            (None, None) if config.walk_mode => Box::new(|content| {
+                walk::walk(&content, |question| {
+                    rprompt::prompt_reply(question).context("Failed to read the new name")
+                })
+            }),
+            (None, None) => {
+                let editor = if config.remote {
+                    TempFileEditor::remote_from_environment()?
+                } else {
+                    TempFileEditor::from_environment(config.use_vscode)
+                };
+                let editor = if config.live_validation {
+                    let (dedupe_existing, overwrite) = (config.dedupe_existing, config.overwrite);
+                    editor.with_live_validation(move |original, edited| {
+                        validate_edited_content(original, edited, dedupe_existing, overwrite)
+                    })
+                } else {
+                    editor
+                };
+                let editor = editor.with_temp_file_prefix(format!("bumv_{}_", config.session));
+                let editor = match &config.buffer_file {
+                    Some(buffer_file) => editor.with_buffer_file(buffer_file.clone()),
+                    None => editor,
+                };
+                editor.ensure_available()?;
+                let line_width = LineWidth::new(
+                    config.max_line_width,
+                    editor.wrapping_width(table::terminal_width()),
+                );
+                Box::new(move |content| {
+                    if let Some(line_width) = &line_width {
+                        line_width.warn_about_long_lines(&content);
+                    }
+                    editor.edit(content)
+                })
+            }
+        };
+        Ok(edit_function)
+    };
+
+    if config.tour {
+        return tour::run_tour(edit_function(&config)?, move |mapping| {
+            prompt_for_confirmation(mapping, false, confirm_default, false)
+        });
+    }
+
+    let notify_webhook = config.notify_webhook.clone();
+    let result = bulk_rename_in_loop(
+        config,
+        edit_function,
+        move |mapping| prompt_for_confirmation(mapping, json, confirm_default, yes),
+        |session, result, report| {
+            if !json && notify_webhook.is_none() {
+                return;
+            }
+            let steps =
+                JsonValue::Array(report.steps.iter().map(StepStatistics::to_json).collect());
+            let outcome = match result {
+                Ok(outcome) => JsonValue::object(
+                    [
+                        ("outcome", outcome.name().into()),
+                        ("session", session.as_str().into()),
+                        ("steps", steps),
+                    ]
+                    .into_iter()
+                    .chain(report.plan.clone().map(|plan| ("plan", plan))),
+                ),
+                Err(e) => {
+                    let diagnostics: Vec<JsonValue> = e
+                        .downcast_ref::<ValidationError>()
+                        .map(|error| error.diagnostics.iter().map(Diagnostic::to_json).collect())
+                        .unwrap_or_default();
+                    JsonValue::object([
+                        ("outcome", "error".into()),
+                        ("session", session.as_str().into()),
+                        ("error", format!("{:#}", e).into()),
+                        ("diagnostics", diagnostics.into()),
+                        ("steps", steps),
+                    ])
+                }
+            };
+            if let Some(url) = &notify_webhook {
+                // the files are renamed already, so failing to notify is not an error
+                if let Err(e) = notify(url, &outcome) {
+                    eprintln!("Warning: {:#}", e);
+                }
+            }
+            if json {
+                println!("{}", outcome);
+            }
+        },
+    );
+    match (result, json) {
+        (Ok(outcome), _) => std::process::exit(outcome.exit_code()),
+        (Err(_), true) => std::process::exit(1),
+        (Err(e), false) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests;
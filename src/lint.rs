@@ -0,0 +1,226 @@
+//! Lint rules that point out renamings which are possibly unintended.
+//!
+//! Each rule has a severity, which can be configured in the `[lint]` section of the
+//! configuration file, e.g. `extension-change = "error"`. Findings of rules set to `warn`
+//! are shown in the confirmation prompt, findings of rules set to `error` reject the plan.
+
+use crate::config_file::{ConfigFile, ConfigValue};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+/// File names longer than this number of characters are reported by the `long-name` rule
+const LONG_NAME_LENGTH: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintRule {
+    ExtensionChange,
+    OutsideBasePath,
+    HiddenFile,
+    LongName,
+    CaseOnly,
+}
+
+impl LintRule {
+    pub const ALL: [LintRule; 5] = [
+        LintRule::ExtensionChange,
+        LintRule::OutsideBasePath,
+        LintRule::HiddenFile,
+        LintRule::LongName,
+        LintRule::CaseOnly,
+    ];
+
+    /// Name of the rule in the configuration file and in findings
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintRule::ExtensionChange => "extension-change",
+            LintRule::OutsideBasePath => "outside-base-path",
+            LintRule::HiddenFile => "hidden-file",
+            LintRule::LongName => "long-name",
+            LintRule::CaseOnly => "case-only",
+        }
+    }
+
+    /// Describe the finding of this rule for a renaming, if the rule applies to it
+    fn check(&self, old: &Path, new: &Path, base_directories: &[&Path]) -> Option<String> {
+        let name = |path: &Path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        };
+        match self {
+            LintRule::ExtensionChange => (old.extension() != new.extension())
+                .then(|| "changes the file extension".to_string()),
+            LintRule::OutsideBasePath => (!base_directories
+                .iter()
+                .any(|directory| is_within(new, directory)))
+            .then(|| "moves the file outside of the base path".to_string()),
+            LintRule::HiddenFile => (name(new).starts_with('.') && !name(old).starts_with('.'))
+                .then(|| "creates a hidden file".to_string()),
+            LintRule::LongName => {
+                let length = name(new).chars().count();
+                (length > LONG_NAME_LENGTH)
+                    .then(|| format!("creates a file name of {} characters", length))
+            }
+            LintRule::CaseOnly => {
+                let (old, new) = (old.to_string_lossy(), new.to_string_lossy());
+                (old != new && old.to_lowercase() == new.to_lowercase())
+                    .then(|| "only changes the case".to_string())
+            }
+        }
+    }
+}
+
+impl FromStr for LintRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        LintRule::ALL
+            .into_iter()
+            .find(|rule| rule.name() == s)
+            .with_context(|| {
+                let names: Vec<&str> = LintRule::ALL.iter().map(LintRule::name).collect();
+                format!(
+                    "Unknown lint rule {}, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Ignore,
+    Warn,
+    Error,
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ignore" => Ok(Severity::Ignore),
+            "warn" => Ok(Severity::Warn),
+            "error" => Ok(Severity::Error),
+            _ => anyhow::bail!("Invalid severity {}, expected ignore, warn or error", s),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Ignore => "ignore",
+            Severity::Warn => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// The configured severity of each rule. Rules default to `warn`.
+#[derive(Debug, Clone, Default)]
+pub struct LintSeverities {
+    configured: HashMap<LintRule, Severity>,
+}
+
+impl LintSeverities {
+    /// Read the severities from the `[lint]` section of the configuration file
+    pub fn from_config_file(config_file: &ConfigFile) -> Result<Self> {
+        let mut configured = HashMap::new();
+        for (key, value) in config_file.section("lint") {
+            let rule: LintRule = key.parse()?;
+            let severity = match value {
+                ConfigValue::String(severity) => severity.parse()?,
+                _ => anyhow::bail!("The severity of the lint rule {} must be a string.", key),
+            };
+            configured.insert(rule, severity);
+        }
+        Ok(Self { configured })
+    }
+
+    pub fn get(&self, rule: LintRule) -> Severity {
+        self.configured
+            .get(&rule)
+            .copied()
+            .unwrap_or(Severity::Warn)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub rule: LintRule,
+    pub severity: Severity,
+    pub old: PathBuf,
+    pub new: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} [{}]: {} -> {} {}",
+            self.severity,
+            self.rule.name(),
+            self.old.to_string_lossy(),
+            self.new.to_string_lossy(),
+            self.message
+        )
+    }
+}
+
+/// Check the renamings against all rules that are not ignored. Files may be moved within any of
+/// the `base_directories`, e.g. the base path and the directory files are merged into.
+pub fn lint(
+    mapping: &[(PathBuf, PathBuf)],
+    base_directories: &[&Path],
+    severities: &LintSeverities,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (old, new) in mapping {
+        for rule in LintRule::ALL {
+            let severity = severities.get(rule);
+            if severity == Severity::Ignore {
+                continue;
+            }
+            if let Some(message) = rule.check(old, new, base_directories) {
+                findings.push(Finding {
+                    rule,
+                    severity,
+                    old: old.clone(),
+                    new: new.clone(),
+                    message,
+                });
+            }
+        }
+    }
+    findings
+}
+
+/// Whether `path` is located within `directory`, comparing the paths lexically
+pub fn is_within(path: &Path, directory: &Path) -> bool {
+    match (normalize(path), normalize(directory)) {
+        (Some(path), Some(directory)) => path.starts_with(directory),
+        _ => false,
+    }
+}
+
+/// Make a path absolute and resolve `.` and `..` components without accessing the filesystem.
+/// Returns `None` if the path cannot be made absolute.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in std::path::absolute(path).ok()?.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    Some(normalized)
+}
@@ -0,0 +1,182 @@
+//! The sources of the files to rename. Each source implements `ListingProvider`, so further
+//! sources like database exports or playlists can be added without changing how the listed
+//! files are edited, validated and renamed.
+
+use crate::{is_empty_directory, is_renamable_file, logs, IgnoreSources};
+use anyhow::{Context, Result};
+use ignore::WalkBuilder;
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The files to rename, in no particular order
+pub type Files<'a> = Box<dyn Iterator<Item = PathBuf> + 'a>;
+
+/// A source of the files to rename
+pub trait ListingProvider {
+    /// The files to rename in no particular order. Files that cannot be renamed, e.g. because
+    /// they do not exist anymore, are left out.
+    fn files(&self) -> Result<Files<'_>>;
+}
+
+impl<P: ListingProvider + ?Sized> ListingProvider for &P {
+    fn files(&self) -> Result<Files<'_>> {
+        (**self).files()
+    }
+}
+
+/// The files below a directory, observing ignore files
+#[derive(Debug, Clone)]
+pub struct Walker<'a> {
+    pub directory: &'a Path,
+    pub recursive: bool,
+    pub sources: IgnoreSources,
+    /// Skip nested repositories and submodules
+    pub no_cross_repo: bool,
+    /// List the log files and failure reports of previous sessions
+    pub include_bumv_files: bool,
+    pub include_empty_dirs: bool,
+}
+
+impl ListingProvider for Walker<'_> {
+    fn files(&self) -> Result<Files<'_>> {
+        // special files like FIFOs are only listed with --no-ignore, like hidden files
+        let include_special_files = !self.sources.ignore_files;
+        let no_cross_repo = self.no_cross_repo;
+        let include_bumv_files = self.include_bumv_files;
+        let include_empty_dirs = self.include_empty_dirs;
+        Ok(Box::new(
+            WalkBuilder::new(self.directory)
+                .standard_filters(self.sources.ignore_files)
+                .git_exclude(self.sources.git_exclude)
+                .git_global(self.sources.git_global)
+                // non-recursive mode: only include files in the base path
+                .max_depth(if self.recursive { None } else { Some(1) })
+                // nested repositories and submodules contain a `.git` directory or file
+                .filter_entry(move |entry| {
+                    !(no_cross_repo
+                        && entry.depth() > 0
+                        && entry.file_type().is_some_and(|t| t.is_dir())
+                        && entry.path().join(".git").exists())
+                })
+                .build()
+                .filter_map(Result::ok)
+                .filter(move |entry| {
+                    entry.path().is_file()
+                        || (include_special_files && is_renamable_file(entry.path()))
+                        || (include_empty_dirs
+                            && entry.depth() > 0
+                            && is_empty_directory(entry.path()))
+                })
+                .map(|entry| entry.into_path())
+                // don't rename the files of previous sessions
+                .filter(move |path| {
+                    include_bumv_files
+                        || !path
+                            .file_name()
+                            .and_then(|name| name.to_str())
+                            .is_some_and(logs::is_bumv_artifact)
+                }),
+        ))
+    }
+}
+
+/// Files given one by one, e.g. as arguments or on stdin
+#[derive(Debug, Clone)]
+pub struct ExplicitFiles<'a> {
+    pub files: Vec<&'a PathBuf>,
+}
+
+impl ListingProvider for ExplicitFiles<'_> {
+    fn files(&self) -> Result<Files<'_>> {
+        Ok(renamable_once(self.files.iter().copied()))
+    }
+}
+
+/// The renamable files among `paths`, listing files given more than once only once
+fn renamable_once<'a>(paths: impl Iterator<Item = &'a PathBuf> + 'a) -> Files<'a> {
+    let mut seen = HashSet::new();
+    Box::new(
+        paths
+            .filter(move |path| seen.insert(*path))
+            .filter(|path| is_renamable_file(path))
+            .cloned(),
+    )
+}
+
+/// The paths read from stdin with `--stdin`, one per line, or separated by NUL bytes as written
+/// by `find -print0`. Stdin is read once, so that the listing can be repeated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stdin {
+    paths: Vec<PathBuf>,
+}
+
+impl Stdin {
+    pub fn read(mut reader: impl Read) -> Result<Self> {
+        let mut content = String::new();
+        reader
+            .read_to_string(&mut content)
+            .context("Failed to read the files from stdin")?;
+        let separator = if content.contains('\0') { '\0' } else { '\n' };
+        Ok(Self {
+            paths: content
+                .split(separator)
+                .map(|line| line.strip_suffix('\r').unwrap_or(line))
+                .filter(|line| !line.is_empty())
+                .map(PathBuf::from)
+                .collect(),
+        })
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+}
+
+impl ListingProvider for Stdin {
+    fn files(&self) -> Result<Files<'_>> {
+        Ok(renamable_once(self.paths.iter()))
+    }
+}
+
+/// The files tracked in the git index below a directory, as listed by `git ls-files`. Files
+/// marked skip-worktree, e.g. because they are outside of a sparse checkout, are left out.
+#[derive(Debug, Clone)]
+pub struct GitIndex<'a> {
+    pub directory: &'a Path,
+    pub recursive: bool,
+}
+
+impl ListingProvider for GitIndex<'_> {
+    fn files(&self) -> Result<Files<'_>> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(self.directory)
+            .args(["ls-files", "-z", "-t"])
+            .output()
+            .context("Failed to run git ls-files")?;
+        anyhow::ensure!(
+            output.status.success(),
+            "git ls-files failed in {}: {}",
+            self.directory.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        let stdout = String::from_utf8(output.stdout)
+            .context("git ls-files listed a path that is not valid UTF-8")?;
+        let recursive = self.recursive;
+        let files: Vec<PathBuf> = stdout
+            .split('\0')
+            // each path is preceded by its status, `S` for skip-worktree
+            .filter_map(|entry| entry.split_once(' '))
+            .filter(|(status, _)| *status != "S")
+            .map(|(_, path)| path)
+            // the paths are relative to the directory
+            .filter(|path| recursive || !path.contains('/'))
+            .map(|path| self.directory.join(path))
+            // tracked files may have been deleted from the working tree
+            .filter(|path| is_renamable_file(path))
+            .collect();
+        Ok(Box::new(files.into_iter()))
+    }
+}
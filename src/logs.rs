@@ -0,0 +1,94 @@
+//! Retention of the log files written after renaming.
+
+use crate::session::{is_timestamp, SessionId};
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+/// Whether a file name is the name of a log file, i.e. `bumv_{session ID}.log`,
+/// or `bumv_{YYYYmmdd}_{HHMMSS}.log` as written by versions without session IDs
+pub fn is_log_file_name(name: &str) -> bool {
+    name.strip_prefix("bumv_")
+        .and_then(|name| name.strip_suffix(".log"))
+        .is_some_and(|id| SessionId::is_valid(id) || is_timestamp(id))
+}
+
+/// Whether a file name is the name of a failure report, i.e. `bumv_{session ID}_failure.json`
+pub fn is_failure_report_name(name: &str) -> bool {
+    name.strip_prefix("bumv_")
+        .and_then(|name| name.strip_suffix("_failure.json"))
+        .is_some_and(SessionId::is_valid)
+}
+
+/// Whether a file was written by bumv itself, i.e. is a log file or a failure report
+pub fn is_bumv_artifact(name: &str) -> bool {
+    is_log_file_name(name) || is_failure_report_name(name)
+}
+
+/// Parse a duration like `30d`, e.g. the age of log files. Supported units are `s`, `m`, `h`,
+/// `d` and `w`.
+pub fn parse_age(age: &str) -> Result<Duration> {
+    let unit_start = age
+        .find(|c: char| !c.is_ascii_digit())
+        .with_context(|| format!("The age {} is missing a unit, e.g. 30d", age))?;
+    let (amount, unit) = age.split_at(unit_start);
+    let amount: u64 = amount
+        .parse()
+        .with_context(|| format!("Invalid age {}", age))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => anyhow::bail!(
+            "Unknown unit {} in age {}, expected s, m, h, d or w",
+            unit,
+            age
+        ),
+    };
+    Ok(Duration::from_secs(amount * seconds))
+}
+
+/// Find the log files in a directory, including subdirectories if `recursive` is set
+pub fn find_log_files(directory: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut walker = WalkDir::new(directory).min_depth(1).sort_by_file_name();
+    if !recursive {
+        walker = walker.max_depth(1);
+    }
+    let mut log_files = Vec::new();
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_file() && entry.file_name().to_str().is_some_and(is_log_file_name) {
+            log_files.push(entry.into_path());
+        }
+    }
+    Ok(log_files)
+}
+
+/// Remove the log files that were last modified longer than `older_than` before `now`,
+/// or all log files if no age is given. Returns the removed files.
+pub fn clean_logs(
+    directory: &Path,
+    recursive: bool,
+    older_than: Option<Duration>,
+    now: SystemTime,
+) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for log_file in find_log_files(directory, recursive)? {
+        if let Some(older_than) = older_than {
+            let modified = fs::metadata(&log_file)?.modified()?;
+            // files modified in the future have an age of zero
+            let age = now.duration_since(modified).unwrap_or_default();
+            if age < older_than {
+                continue;
+            }
+        }
+        fs::remove_file(&log_file)
+            .with_context(|| format!("Failed to remove the log file {}", log_file.display()))?;
+        removed.push(log_file);
+    }
+    Ok(removed)
+}
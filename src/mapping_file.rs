@@ -0,0 +1,222 @@
+//! Renaming driven by a CSV or TSV file of `old,new` rows, e.g. exported from a spreadsheet.
+//!
+//! Log files and saved plans use the same format, preceded by a header naming the version of
+//! the format and of bumv, so that they can be given to `--mapping` as well. Versions of bumv
+//! that do not know the format version refuse to read them instead of misinterpreting them.
+
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Version of the format of log files and mapping files, increased on incompatible changes.
+/// Version 2 lists removed duplicates as comments instead of rows, and records the states of
+/// the renamed files.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Start of the header of log files and saved plans, followed by the format version
+const HEADER_PREFIX: &str = "# bumv mapping format ";
+
+/// Column names recognized in a header row, compared case insensitively
+const HEADER_NAMES: [(&str, &str); 4] = [
+    ("old", "new"),
+    ("from", "to"),
+    ("source", "target"),
+    ("old name", "new name"),
+];
+
+/// Split CSV or TSV content into rows of fields. Fields may be quoted with `"`, in which case
+/// they may contain the delimiter, line breaks and quotes written as `""`.
+/// In content written by bumv, lines starting with `#` outside of quotes are comments, and
+/// spaces before a delimiter are the padding aligning the columns, unless they are quoted.
+fn parse_rows(content: &str, delimiter: char, written_by_bumv: bool) -> Result<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    // whether the current field was quoted, so that following spaces are padding
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if field.is_empty() => {
+                in_quotes = true;
+                quoted = true;
+            }
+            _ if in_quotes => field.push(c),
+            '#' if written_by_bumv && field.is_empty() && row.is_empty() => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            ' ' if written_by_bumv && quoted => {}
+            _ if c == delimiter => {
+                if written_by_bumv && !quoted {
+                    field.truncate(field.trim_end_matches(' ').len());
+                }
+                row.push(std::mem::take(&mut field));
+                quoted = false;
+            }
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+                quoted = false;
+            }
+            _ => field.push(c),
+        }
+    }
+    anyhow::ensure!(!in_quotes, "Unterminated quoted field in the mapping file");
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    // skip empty lines
+    rows.retain(|row| !(row.len() == 1 && row[0].is_empty()));
+    Ok(rows)
+}
+
+fn is_header(row: &[String]) -> bool {
+    let row: Vec<String> = row
+        .iter()
+        .map(|field| field.trim().to_lowercase())
+        .collect();
+    HEADER_NAMES
+        .iter()
+        .any(|(old, new)| row[0] == *old && row[1] == *new)
+}
+
+/// The first line of log files and saved plans, naming the format and the bumv version
+pub fn header() -> String {
+    format!(
+        "{}{}, bumv {}",
+        HEADER_PREFIX,
+        FORMAT_VERSION,
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// The format version named in the header of a log file or saved plan, or `None` if the content
+/// has no such header. Fails if the version is newer than this bumv supports.
+pub fn format_version(content: &str) -> Result<Option<u32>> {
+    let first_line = content.lines().next().unwrap_or_default();
+    let Some(rest) = first_line.strip_prefix(HEADER_PREFIX) else {
+        return Ok(None);
+    };
+    let (version, writer) = rest
+        .split_once(", ")
+        .unwrap_or((rest, "an unknown version of bumv"));
+    let version: u32 = version
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid format version in the header {:?}", first_line))?;
+    anyhow::ensure!(
+        version <= FORMAT_VERSION,
+        "The file was written by {} in format version {}, but bumv {} only reads versions up to {}. \
+         Please update bumv to use it.",
+        writer,
+        version,
+        env!("CARGO_PKG_VERSION"),
+        FORMAT_VERSION
+    );
+    Ok(Some(version))
+}
+
+/// A name as a field of a log file or saved plan. Names that could be mistaken for a comment
+/// or the padding aligning the columns, or that contain a tab or a line break, are quoted.
+pub fn field(name: &str) -> Cow<'_, str> {
+    if name.starts_with(['#', '"']) || name.ends_with(' ') || name.contains(['\t', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", name.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// Parse the mapping file content into pairs of old and new paths.
+/// Tab separated content is detected by a tab in the first line, a header row by its column names.
+/// In log files and saved plans, recognized by their header, lines starting with `#` are
+/// comments, and the old names are padded to align the columns.
+pub fn parse_mapping(content: &str) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let written_by_bumv = format_version(content)?.is_some();
+    let first_line = content
+        .lines()
+        .find(|line| !(written_by_bumv && line.starts_with('#')))
+        .unwrap_or_default();
+    let delimiter = if first_line.contains('\t') { '\t' } else { ',' };
+    let mut rows = parse_rows(content, delimiter, written_by_bumv)?;
+    if let Some((line, row)) = rows.iter().enumerate().find(|(_, row)| row.len() != 2) {
+        anyhow::bail!(
+            "Row {} of the mapping file has {} columns, expected 2 (old and new name).",
+            line + 1,
+            row.len()
+        );
+    }
+    if rows.first().is_some_and(|row| is_header(row)) {
+        rows.remove(0);
+    }
+    Ok(rows
+        .into_iter()
+        .map(|row| (PathBuf::from(&row[0]), PathBuf::from(&row[1])))
+        .collect())
+}
+
+/// Remove `.` components, so that `./a.txt` and `a.txt` are considered the same file
+fn normalized(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+/// Apply the mapping to the file list. Each renamed file must be part of the file list.
+/// New names of listed files are written as in the file list, e.g. `./b.txt` for `b.txt`,
+/// so that renamings between listed files are recognized.
+pub fn apply_mapping(file_list: &str, mapping: &[(PathBuf, PathBuf)]) -> Result<String> {
+    let listed_spelling: HashMap<PathBuf, &str> = file_list
+        .lines()
+        .map(|line| (normalized(Path::new(line)), line))
+        .collect();
+    let mut renames: HashMap<PathBuf, &Path> = HashMap::new();
+    for (old, new) in mapping {
+        anyhow::ensure!(
+            renames.insert(normalized(old), new).is_none(),
+            "The file {} is renamed more than once in the mapping file.",
+            old.to_string_lossy()
+        );
+    }
+    let mut applied = 0;
+    let lines: Vec<String> = file_list
+        .lines()
+        .map(|line| match renames.get(&normalized(Path::new(line))) {
+            Some(new) => {
+                applied += 1;
+                match listed_spelling.get(&normalized(new)) {
+                    Some(listed) => listed.to_string(),
+                    None => new.to_string_lossy().to_string(),
+                }
+            }
+            None => line.to_string(),
+        })
+        .collect();
+    if applied != renames.len() {
+        let listed: Vec<PathBuf> = file_list
+            .lines()
+            .map(|line| normalized(Path::new(line)))
+            .collect();
+        let (missing, _) = mapping
+            .iter()
+            .find(|(old, _)| !listed.contains(&normalized(old)))
+            .context("Inconsistent mapping")?;
+        anyhow::bail!(
+            "The file {} of the mapping file is not listed.",
+            missing.to_string_lossy()
+        );
+    }
+    Ok(lines.join("\n"))
+}
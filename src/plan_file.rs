@@ -0,0 +1,178 @@
+//! Plans written with `--plan-out` or `--save-rejected-plan`, and applying them with `--apply`,
+//! e.g. after reviewing them on another machine or in CI. The requested renamings are
+//! reconstructed from the steps, so the plan is executed through the usual validation and
+//! confirmation.
+
+use crate::json::JsonValue;
+use crate::{BumvConfiguration, Overwrite};
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// Version of the format of plan files, increased on incompatible changes. Plans shared the
+/// version of log files up to version 2.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// The options a plan was made with that change what applying it does, so that the plan
+/// is applied the same way without giving them again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PlanOptions {
+    pub dedupe_existing: bool,
+    pub overwrite: Overwrite,
+}
+
+impl PlanOptions {
+    pub fn of(config: &BumvConfiguration) -> Self {
+        Self {
+            dedupe_existing: config.dedupe_existing,
+            overwrite: config.overwrite,
+        }
+    }
+
+    pub fn to_json(self) -> JsonValue {
+        JsonValue::object([
+            ("dedupe_existing", self.dedupe_existing.into()),
+            (
+                "overwrite",
+                match self.overwrite {
+                    Overwrite::Abort => "abort",
+                    Overwrite::Trash => "trash",
+                }
+                .into(),
+            ),
+        ])
+    }
+
+    /// Apply the plan with these options, in addition to those given on the command line
+    pub fn apply_to(self, config: &mut BumvConfiguration) {
+        config.dedupe_existing |= self.dedupe_existing;
+        if self.overwrite == Overwrite::Trash {
+            config.overwrite = Overwrite::Trash;
+        }
+    }
+
+    /// The options of a plan document. Plans of earlier versions do not record them.
+    fn from_json(document: &JsonValue) -> Result<Self> {
+        let Some(options) = document.get("options") else {
+            return Ok(Self::default());
+        };
+        Ok(Self {
+            dedupe_existing: options
+                .get("dedupe_existing")
+                .and_then(JsonValue::as_bool)
+                .unwrap_or_default(),
+            overwrite: match options.get("overwrite").and_then(JsonValue::as_str) {
+                Some(overwrite) => overwrite.parse()?,
+                None => Overwrite::default(),
+            },
+        })
+    }
+}
+
+/// The plan document for `plan`, as written with `--plan-out` and `--save-rejected-plan`
+pub fn document(config: &BumvConfiguration, plan: JsonValue) -> JsonValue {
+    JsonValue::object([
+        ("format_version", (FORMAT_VERSION as usize).into()),
+        ("bumv_version", env!("CARGO_PKG_VERSION").into()),
+        ("session", config.session.as_str().into()),
+        ("options", PlanOptions::of(config).to_json()),
+        ("plan", plan),
+    ])
+}
+
+/// The requested renamings of a plan document, with files removed as duplicates mapped to their
+/// identical target, and the options the plan was made with. Fails if the document is not a
+/// plan this bumv can read, if a file to rename does not exist anymore, or if a target exists
+/// that was not there when the plan was made. Targets the plan moves to the trash may exist.
+pub fn requested_mapping(content: &str) -> Result<(Vec<(PathBuf, PathBuf)>, PlanOptions)> {
+    let document = JsonValue::parse(content)?;
+    let version = document
+        .get("format_version")
+        .and_then(JsonValue::as_f64)
+        .context("The file is not a plan written with --plan-out.")?;
+    anyhow::ensure!(
+        version <= FORMAT_VERSION as f64,
+        "The plan was written by bumv {} in format version {}, but bumv {} only reads versions up \
+         to {}. Please update bumv.",
+        document
+            .get("bumv_version")
+            .and_then(JsonValue::as_str)
+            .unwrap_or("unknown"),
+        version,
+        env!("CARGO_PKG_VERSION"),
+        FORMAT_VERSION
+    );
+    let options = PlanOptions::from_json(&document)?;
+    let plan = document
+        .get("plan")
+        .context("The plan file does not contain a plan.")?;
+    let pairs = |key: &str, to_key: &str| -> Result<Vec<(PathBuf, PathBuf)>> {
+        let entries = plan
+            .get(key)
+            .and_then(JsonValue::as_array)
+            .with_context(|| format!("The plan does not contain {}.", key))?;
+        entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let path = |field: &str| {
+                    entry
+                        .get(field)
+                        .and_then(JsonValue::as_str)
+                        .map(PathBuf::from)
+                        .with_context(|| format!("Entry {} of {} lacks {}.", i + 1, key, field))
+                };
+                Ok((path("from")?, path(to_key)?))
+            })
+            .collect()
+    };
+    let steps = pairs("steps", "to")?;
+    let duplicates = pairs("remove_duplicates", "identical_to")?;
+    // plans of earlier versions do not move targets to the trash
+    let trashed: HashSet<PathBuf> = plan
+        .get("trash")
+        .and_then(JsonValue::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(JsonValue::as_str)
+        .map(PathBuf::from)
+        .collect();
+
+    // follow each file through the steps, including those via temporary files
+    let mut locations: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut by_location: HashMap<PathBuf, usize> = HashMap::new();
+    for (from, to) in steps {
+        let i = by_location.remove(&from).unwrap_or_else(|| {
+            locations.push((from.clone(), from));
+            locations.len() - 1
+        });
+        locations[i].1 = to.clone();
+        by_location.insert(to, i);
+    }
+    let mapping: Vec<(PathBuf, PathBuf)> = locations
+        .into_iter()
+        .filter(|(original, location)| original != location)
+        .collect();
+
+    let sources: HashSet<&PathBuf> = mapping.iter().map(|(old, _)| old).collect();
+    for (old, new) in &mapping {
+        anyhow::ensure!(
+            old.symlink_metadata().is_ok(),
+            "{} does not exist anymore.",
+            old.to_string_lossy()
+        );
+        anyhow::ensure!(
+            sources.contains(new) || trashed.contains(new) || new.symlink_metadata().is_err(),
+            "{} exists already.",
+            new.to_string_lossy()
+        );
+    }
+    for (old, _) in &duplicates {
+        anyhow::ensure!(
+            old.symlink_metadata().is_ok(),
+            "{} does not exist anymore.",
+            old.to_string_lossy()
+        );
+    }
+    Ok((mapping.into_iter().chain(duplicates).collect(), options))
+}
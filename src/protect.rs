@@ -0,0 +1,47 @@
+//! Protected files, which may be listed but must not be renamed.
+
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::{Path, PathBuf};
+
+/// Matches files against protection patterns.
+/// Patterns containing a `/` are matched against the path relative to the base path,
+/// all other patterns are matched against the file name.
+#[derive(Debug, Clone)]
+pub struct ProtectedFiles {
+    base_path: PathBuf,
+    by_name: GlobSet,
+    by_path: GlobSet,
+}
+
+impl ProtectedFiles {
+    pub fn try_new(base_path: &Path, patterns: &[String]) -> Result<Self> {
+        let mut by_name = GlobSetBuilder::new();
+        let mut by_path = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = GlobBuilder::new(pattern.trim_start_matches('/'))
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("Invalid protection pattern {}", pattern))?;
+            if pattern.contains('/') {
+                by_path.add(glob);
+            } else {
+                by_name.add(glob);
+            }
+        }
+        Ok(Self {
+            base_path: base_path.to_path_buf(),
+            by_name: by_name.build()?,
+            by_path: by_path.build()?,
+        })
+    }
+
+    pub fn is_protected(&self, path: &Path) -> bool {
+        let by_name = path
+            .file_name()
+            .map(|name| self.by_name.is_match(name))
+            .unwrap_or(false);
+        let relative_path = path.strip_prefix(&self.base_path).unwrap_or(path);
+        by_name || self.by_path.is_match(relative_path)
+    }
+}
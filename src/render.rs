@@ -0,0 +1,528 @@
+//! The preview of a renaming plan as segments of structured lines, e.g. the renamings, the
+//! summary of the directories files move between, and lint findings. The confirmation prompt
+//! renders them as text, while frontends embedding bumv can draw their own preview from them,
+//! e.g. coloring the lines of a diff by their kind.
+
+pub use crate::diff::DiffLine;
+pub use crate::lint::{Finding, LintRule, Severity};
+pub use crate::sync::WatchedFolder;
+pub use crate::Preview;
+use crate::{
+    format_size, parent_directory, table, transfer, LEAVING_CONFIRMATION_BYTES,
+    LEAVING_CONFIRMATION_FILES, SUMMARY_DIRECTORIES,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A change to the filesystem as shown in the preview
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    CreateDirectory(PathBuf),
+    RemoveDirectory(PathBuf),
+    /// A file removed because the target exists with identical content
+    RemoveDuplicate {
+        old: PathBuf,
+        existing: PathBuf,
+    },
+    /// An existing target moved to the trash with `--overwrite trash`
+    Trash(PathBuf),
+    /// A renaming, with the kind of special files like FIFOs and the size of regular files if
+    /// requested with `--show-sizes`
+    Rename {
+        old: PathBuf,
+        new: PathBuf,
+        kind: Option<&'static str>,
+        size: Option<u64>,
+    },
+}
+
+impl Change {
+    /// The renaming of `old` to `new`, determining the kind of special files
+    pub fn rename(old: &Path, new: &Path) -> Self {
+        Change::Rename {
+            old: old.to_path_buf(),
+            new: new.to_path_buf(),
+            kind: transfer::special_file_kind(old),
+            size: None,
+        }
+    }
+
+    /// The renaming of `old` to `new` like `Change::rename`, with the size of `old` if it is a
+    /// regular file
+    pub fn rename_with_size(old: &Path, new: &Path) -> Self {
+        let size = std::fs::symlink_metadata(old)
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len());
+        match Change::rename(old, new) {
+            Change::Rename { old, new, kind, .. } => Change::Rename {
+                old,
+                new,
+                kind,
+                size,
+            },
+            change => change,
+        }
+    }
+}
+
+/// How many files leave and arrive in each directory, if files move between directories
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectorySummary {
+    /// The directories files leave, with the number of files, ordered by directory
+    pub leaving: Vec<(PathBuf, usize)>,
+    /// The directories files arrive in, with the number of files, ordered by directory
+    pub arriving: Vec<(PathBuf, usize)>,
+    /// The number of directories created
+    pub created: usize,
+}
+
+impl DirectorySummary {
+    /// Summarize the renamings, or `None` if all files stay in their directory
+    pub fn new(mapping: &[(PathBuf, PathBuf)], created: usize) -> Option<Self> {
+        let mut leaving: BTreeMap<&Path, usize> = BTreeMap::new();
+        let mut arriving: BTreeMap<&Path, usize> = BTreeMap::new();
+        for (old, new) in mapping {
+            let (old_directory, new_directory) = (parent_directory(old), parent_directory(new));
+            if old_directory != new_directory {
+                *leaving.entry(old_directory).or_default() += 1;
+                *arriving.entry(new_directory).or_default() += 1;
+            }
+        }
+        if leaving.is_empty() {
+            return None;
+        }
+        let owned = |directories: BTreeMap<&Path, usize>| {
+            directories
+                .into_iter()
+                .map(|(directory, n)| (directory.to_path_buf(), n))
+                .collect()
+        };
+        Some(Self {
+            leaving: owned(leaving),
+            arriving: owned(arriving),
+            created,
+        })
+    }
+}
+
+impl fmt::Display for DirectorySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let files = |count: usize| match count {
+            1 => "1 file".to_string(),
+            _ => format!("{} files", count),
+        };
+        write!(f, "Summary:")?;
+        for (directories, verb) in [
+            (&self.leaving, "will leave"),
+            (&self.arriving, "will arrive in"),
+        ] {
+            for (directory, n) in directories.iter().take(SUMMARY_DIRECTORIES) {
+                write!(
+                    f,
+                    "\n  {} {} {}",
+                    files(*n),
+                    verb,
+                    directory.to_string_lossy()
+                )?;
+            }
+            if directories.len() > SUMMARY_DIRECTORIES {
+                write!(
+                    f,
+                    "\n  ... and {} more directories",
+                    directories.len() - SUMMARY_DIRECTORIES
+                )?;
+            }
+        }
+        match self.created {
+            0 => Ok(()),
+            1 => write!(f, "\n  1 directory will be created"),
+            n => write!(f, "\n  {} directories will be created", n),
+        }
+    }
+}
+
+/// The files moved out of the base path, which may fill up another volume
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeavingSummary {
+    pub files: usize,
+    /// Total size of the files
+    pub bytes: u64,
+    /// The existing directories the files are moved to, without those within another one
+    pub destinations: BTreeSet<PathBuf>,
+}
+
+impl LeavingSummary {
+    /// Whether the move is large enough to be confirmed separately
+    pub fn needs_confirmation(&self) -> bool {
+        self.files >= LEAVING_CONFIRMATION_FILES || self.bytes >= LEAVING_CONFIRMATION_BYTES
+    }
+}
+
+impl fmt::Display for LeavingSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Leaving the base path:")?;
+        write!(
+            f,
+            "  {} {}, {} in total, will be moved to",
+            self.files,
+            if self.files == 1 { "file" } else { "files" },
+            format_size(self.bytes)
+        )?;
+        for destination in self.destinations.iter().take(SUMMARY_DIRECTORIES) {
+            write!(f, "\n    {}", destination.to_string_lossy())?;
+        }
+        if self.destinations.len() > SUMMARY_DIRECTORIES {
+            write!(
+                f,
+                "\n    ... and {} more directories",
+                self.destinations.len() - SUMMARY_DIRECTORIES
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A part of the preview, separated from the others by an empty line when rendered as text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Changes(Vec<Change>),
+    /// The renamings as a diff of the file list, shown with `--diff`
+    Diff(Vec<DiffLine>),
+    Directories(DirectorySummary),
+    Leaving(LeavingSummary),
+    WatchedFolder(WatchedFolder),
+    Lint(Vec<Finding>),
+    /// The total size of the renamed files in each directory, largest first, shown with
+    /// `--show-sizes`
+    Sizes(Vec<(PathBuf, u64)>),
+    /// The steps in the order they are performed, shown if it differs from the order of the
+    /// file list, e.g. because of temporary renamings that break cycles
+    ExecutionOrder(Vec<(PathBuf, PathBuf)>),
+}
+
+impl Segment {
+    /// Render the segment as shown in the confirmation prompt. Renamings are shown as a list
+    /// or a table, depending on `preview`.
+    pub fn to_text(&self, preview: Preview) -> String {
+        match self {
+            Segment::Changes(changes) => changes_to_text(changes, preview),
+            Segment::Diff(lines) => lines
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Segment::Directories(summary) => summary.to_string(),
+            Segment::Leaving(summary) => summary.to_string(),
+            Segment::WatchedFolder(folder) => folder.to_string(),
+            Segment::Lint(findings) => std::iter::once("Lint:".to_string())
+                .chain(findings.iter().map(|finding| format!("  {}", finding)))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Segment::Sizes(totals) => {
+                let mut lines = vec!["Sizes:".to_string()];
+                lines.extend(
+                    totals
+                        .iter()
+                        .take(SUMMARY_DIRECTORIES)
+                        .map(|(directory, bytes)| {
+                            format!(
+                                "  {} in {}",
+                                format_size(*bytes),
+                                directory.to_string_lossy()
+                            )
+                        }),
+                );
+                if totals.len() > SUMMARY_DIRECTORIES {
+                    lines.push(format!(
+                        "  ... and {} more directories",
+                        totals.len() - SUMMARY_DIRECTORIES
+                    ));
+                }
+                lines.join("\n")
+            }
+            Segment::ExecutionOrder(steps) => std::iter::once("Execution order:".to_string())
+                .chain(steps.iter().enumerate().map(|(i, (old, new))| {
+                    format!(
+                        "  {}. {} -> {}",
+                        i + 1,
+                        old.to_string_lossy(),
+                        new.to_string_lossy()
+                    )
+                }))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+/// Render the segments as shown in the confirmation prompt
+pub fn to_text(segments: &[Segment], preview: Preview) -> String {
+    segments
+        .iter()
+        .map(|segment| segment.to_text(preview))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Render changes with a line per directory and removal, followed by the renamings
+fn changes_to_text(changes: &[Change], preview: Preview) -> String {
+    let mut lines = Vec::new();
+    let mut rows: Vec<(String, String)> = Vec::new();
+    for change in changes {
+        match change {
+            Change::CreateDirectory(directory) => {
+                lines.push(format!("Create directory {}", directory.to_string_lossy()))
+            }
+            Change::RemoveDirectory(directory) => lines.push(format!(
+                "Remove empty directory {}",
+                directory.to_string_lossy()
+            )),
+            Change::RemoveDuplicate { old, existing } => lines.push(format!(
+                "Remove {}, identical to {}",
+                old.to_string_lossy(),
+                existing.to_string_lossy()
+            )),
+            Change::Trash(path) => {
+                lines.push(format!("Move {} to the trash", path.to_string_lossy()))
+            }
+            Change::Rename {
+                old,
+                new,
+                kind,
+                size,
+            } => {
+                let annotation = kind.map(str::to_string).or_else(|| size.map(format_size));
+                rows.push((
+                    old.to_string_lossy().to_string(),
+                    match annotation {
+                        Some(annotation) => format!("{} ({})", new.to_string_lossy(), annotation),
+                        None => new.to_string_lossy().to_string(),
+                    },
+                ))
+            }
+        }
+    }
+    if !rows.is_empty() {
+        lines.push(match preview {
+            Preview::List => rows
+                .iter()
+                .map(|(old, new)| format!("{} -> {}", old, new))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Preview::Table => table::table(("Old", "New"), &rows, table::terminal_width()),
+        });
+    }
+    lines.join("\n")
+}
+
+/// The total size of the renamed regular files in each directory, largest first
+pub fn sizes_by_directory(mapping: &[(PathBuf, PathBuf)]) -> Vec<(PathBuf, u64)> {
+    let mut totals: BTreeMap<&Path, u64> = BTreeMap::new();
+    for (old, _) in mapping {
+        let bytes = std::fs::symlink_metadata(old)
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .map_or(0, |metadata| metadata.len());
+        *totals.entry(parent_directory(old)).or_default() += bytes;
+    }
+    let mut totals: Vec<(PathBuf, u64)> = totals
+        .into_iter()
+        .map(|(directory, bytes)| (directory.to_path_buf(), bytes))
+        .collect();
+    // the sort is stable, so directories of the same size stay ordered by name
+    totals.sort_by_key(|(_, bytes)| std::cmp::Reverse(*bytes));
+    totals
+}
+
+/// Group renamings by the directory of the renamed file, ordered by directory
+pub fn group_by_directory(
+    mapping: &[(PathBuf, PathBuf)],
+) -> BTreeMap<&Path, Vec<&(PathBuf, PathBuf)>> {
+    let mut groups: BTreeMap<&Path, Vec<_>> = BTreeMap::new();
+    for renaming in mapping {
+        let directory = renaming.0.parent().unwrap_or_else(|| Path::new(""));
+        groups.entry(directory).or_default().push(renaming);
+    }
+    groups
+}
+
+/// Render the plan as a Markdown report for signing it off in a pull request or ticket:
+/// statistics, the renamings grouped by directory, the other changes, and the warnings among
+/// `segments`. `steps` is the number of renaming steps if they differ from `mapping`, and
+/// `plan_file` is the file the plan is saved to, which can be applied unchanged.
+pub fn to_markdown(
+    changes: &[Change],
+    mapping: &[(PathBuf, PathBuf)],
+    segments: &[Segment],
+    steps: Option<usize>,
+    plan_file: Option<&Path>,
+) -> String {
+    let groups = group_by_directory(mapping);
+    let count = |matches: fn(&Change) -> bool| changes.iter().filter(|c| matches(c)).count();
+    let mut lines = vec![
+        "# Renaming plan".to_string(),
+        String::new(),
+        format!("Proposed by bumv {}.", env!("CARGO_PKG_VERSION")),
+        String::new(),
+        "## Statistics".to_string(),
+        String::new(),
+        format!(
+            "- Renamings: {} in {} {}",
+            mapping.len(),
+            groups.len(),
+            if groups.len() == 1 {
+                "directory"
+            } else {
+                "directories"
+            }
+        ),
+    ];
+    if let Some(steps) = steps.filter(|steps| *steps != mapping.len()) {
+        lines.push(format!(
+            "- Steps: {}, including temporary renamings to break cycles",
+            steps
+        ));
+    }
+    for (label, n) in [
+        (
+            "Directories created",
+            count(|change| matches!(change, Change::CreateDirectory(_))),
+        ),
+        (
+            "Directories removed",
+            count(|change| matches!(change, Change::RemoveDirectory(_))),
+        ),
+        (
+            "Duplicates removed",
+            count(|change| matches!(change, Change::RemoveDuplicate { .. })),
+        ),
+        (
+            "Moved to the trash",
+            count(|change| matches!(change, Change::Trash(_))),
+        ),
+    ] {
+        if n > 0 {
+            lines.push(format!("- {}: {}", label, n));
+        }
+    }
+
+    let warnings: Vec<String> = segments.iter().flat_map(warning_items).collect();
+    if !warnings.is_empty() {
+        lines.extend([String::new(), "## Warnings".to_string(), String::new()]);
+        lines.extend(warnings.iter().map(|warning| format!("- {}", warning)));
+    }
+
+    lines.extend([String::new(), "## Renamings".to_string()]);
+    for (directory, renamings) in &groups {
+        let directory = match directory.to_string_lossy() {
+            name if name.is_empty() => ".".into(),
+            name => name,
+        };
+        lines.extend([
+            String::new(),
+            format!("### {}", code(&directory)),
+            String::new(),
+            "| Old | New |".to_string(),
+            "| --- | --- |".to_string(),
+        ]);
+        for (old, new) in renamings {
+            let old_name = old.file_name().unwrap_or(old.as_os_str()).to_string_lossy();
+            // the new name is shown as a path if the file moves to another directory
+            let new_name = match new.file_name() {
+                Some(name) if new.parent() == old.parent() => name.to_string_lossy(),
+                _ => new.to_string_lossy(),
+            };
+            lines.push(format!("| {} | {} |", code(&old_name), code(&new_name)));
+        }
+    }
+
+    let other_changes: Vec<String> = changes
+        .iter()
+        .filter_map(|change| match change {
+            Change::CreateDirectory(directory) => Some(format!(
+                "Create directory {}",
+                code(&directory.to_string_lossy())
+            )),
+            Change::RemoveDirectory(directory) => Some(format!(
+                "Remove empty directory {}",
+                code(&directory.to_string_lossy())
+            )),
+            Change::RemoveDuplicate { old, existing } => Some(format!(
+                "Remove {}, identical to {}",
+                code(&old.to_string_lossy()),
+                code(&existing.to_string_lossy())
+            )),
+            Change::Trash(path) => Some(format!(
+                "Move {} to the trash",
+                code(&path.to_string_lossy())
+            )),
+            Change::Rename { .. } => None,
+        })
+        .collect();
+    if !other_changes.is_empty() {
+        lines.extend([String::new(), "## Other changes".to_string(), String::new()]);
+        lines.extend(other_changes.iter().map(|change| format!("- {}", change)));
+    }
+
+    if let Some(plan_file) = plan_file {
+        lines.extend([
+            String::new(),
+            "## Applying the plan".to_string(),
+            String::new(),
+            format!(
+                "If the renaming is declined, the plan is saved to {}. Once signed off, apply it \
+                 unchanged with `bumv --apply {}`.",
+                code(&plan_file.to_string_lossy()),
+                plan_file.to_string_lossy()
+            ),
+        ]);
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// The warnings of a preview segment as single lines, none for the changes and summaries
+fn warning_items(segment: &Segment) -> Vec<String> {
+    match segment {
+        Segment::Changes(_)
+        | Segment::Diff(_)
+        | Segment::Directories(_)
+        | Segment::Sizes(_)
+        | Segment::ExecutionOrder(_) => Vec::new(),
+        Segment::Leaving(summary) => vec![format!(
+            "{} {}, {} in total, will be moved out of the base path",
+            summary.files,
+            if summary.files == 1 { "file" } else { "files" },
+            format_size(summary.bytes)
+        )],
+        Segment::WatchedFolder(folder) => vec![folder
+            .to_string()
+            .lines()
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join(" ")],
+        Segment::Lint(findings) => findings.iter().map(ToString::to_string).collect(),
+    }
+}
+
+/// Format text as a Markdown code span that is safe within a table cell
+fn code(text: &str) -> String {
+    // the span is delimited by more backticks than the longest run of backticks in the text
+    let longest_run = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let ticks = "`".repeat(longest_run + 1);
+    let padding = if text.starts_with('`') || text.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+    format!(
+        "{ticks}{padding}{}{padding}{ticks}",
+        text.replace('|', "\\|")
+    )
+}
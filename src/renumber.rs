@@ -0,0 +1,104 @@
+//! Renaming files to sequence numbers in the order of the edited file list.
+//!
+//! With `--renumber`, the user reorders the lines of the file list instead of editing names.
+//! Files are then named by a template like `track_{n:02}`, numbered by their position in the
+//! reordered list. The extension of each file is kept.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// A name template containing the sequence number as `{n}`, or zero padded as e.g. `{n:03}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenumberTemplate {
+    prefix: String,
+    width: usize,
+    suffix: String,
+}
+
+impl FromStr for RenumberTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(template: &str) -> Result<Self> {
+        let start = template.find("{n").with_context(|| {
+            format!(
+                "The template {} must contain {{n}} or e.g. {{n:03}}",
+                template
+            )
+        })?;
+        let end = start
+            + template[start..]
+                .find('}')
+                .with_context(|| format!("Unterminated placeholder in template {}", template))?;
+        let width = match &template[start + 2..end] {
+            "" => 0,
+            format => format
+                .strip_prefix(":0")
+                .and_then(|width| width.parse().ok())
+                .with_context(|| {
+                    format!("Invalid number format {} in template {}", format, template)
+                })?,
+        };
+        let (prefix, suffix) = (&template[..start], &template[end + 1..]);
+        anyhow::ensure!(
+            !prefix.contains(['/', '{']) && !suffix.contains(['/', '{']),
+            "The template {} must be a file name containing a single placeholder",
+            template
+        );
+        Ok(Self {
+            prefix: prefix.to_string(),
+            width,
+            suffix: suffix.to_string(),
+        })
+    }
+}
+
+impl RenumberTemplate {
+    /// The file name for the given sequence number, without extension
+    pub fn file_stem(&self, n: usize) -> String {
+        format!(
+            "{}{:0width$}{}",
+            self.prefix,
+            n,
+            self.suffix,
+            width = self.width
+        )
+    }
+}
+
+/// Compute the new names of the original files from their order in the edited list.
+/// The edited list must contain exactly the original files. The result is in the order
+/// of the original files, each file keeping its directory and extension.
+pub fn renumber(
+    original: &[PathBuf],
+    edited: &[PathBuf],
+    template: &RenumberTemplate,
+) -> Result<Vec<PathBuf>> {
+    let positions: HashMap<&PathBuf, usize> = edited
+        .iter()
+        .enumerate()
+        .map(|(i, path)| (path, i))
+        .collect();
+    anyhow::ensure!(
+        positions.len() == edited.len(),
+        "Each file must be listed once when renumbering."
+    );
+    original
+        .iter()
+        .map(|path| {
+            let position = positions.get(path).with_context(|| {
+                format!(
+                    "The file {} is missing. When renumbering, only reorder the lines.",
+                    path.to_string_lossy()
+                )
+            })?;
+            let mut file_name = template.file_stem(position + 1);
+            if let Some(extension) = path.extension() {
+                file_name.push('.');
+                file_name.push_str(&extension.to_string_lossy());
+            }
+            Ok(path.with_file_name(file_name))
+        })
+        .collect()
+}
@@ -0,0 +1,62 @@
+//! Session IDs correlating the temp file, the log file, failure reports and JSON output
+//! of one invocation.
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::process;
+
+/// Identifies one invocation of bumv, e.g. `20240101_120000-3fa2`.
+/// It starts with the local time the session was started, so IDs sort chronologically,
+/// followed by a random suffix distinguishing sessions started in the same second.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionId(String);
+
+impl SessionId {
+    pub fn new() -> Self {
+        let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+        // `RandomState` is randomly seeded, which is random enough to tell sessions apart
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(process::id());
+        Self(format!("{}-{:04x}", timestamp, hasher.finish() & 0xffff))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether `id` has the format of a session ID
+    pub fn is_valid(id: &str) -> bool {
+        let (timestamp, suffix) = match id.split_once('-') {
+            Some(parts) => parts,
+            None => return false,
+        };
+        suffix.len() == 4
+            && suffix.chars().all(|c| c.is_ascii_hexdigit())
+            && is_timestamp(timestamp)
+    }
+}
+
+impl Default for SessionId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Whether `timestamp` has the format `YYYYmmdd_HHMMSS` used in session IDs and older log files
+pub fn is_timestamp(timestamp: &str) -> bool {
+    match timestamp.split_once('_') {
+        Some((date, time)) => {
+            date.len() == 8
+                && time.len() == 6
+                && date.chars().chain(time.chars()).all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
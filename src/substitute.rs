@@ -0,0 +1,140 @@
+//! Substitutions proposed in the file list for a subset of the files, given as
+//! `--sub-on GLOB 's/PATTERN/REPLACEMENT/'`.
+
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+/// A sed-like substitution of the names of the files matching a glob pattern.
+/// Patterns containing a `/` are matched against the path relative to the base path,
+/// all other patterns are matched against the file name, like protection patterns.
+#[derive(Debug, Clone)]
+pub struct Substitution {
+    glob: GlobMatcher,
+    by_path: bool,
+    pattern: Regex,
+    /// The replacement, referring to capture groups as `$1` or `${name}`
+    replacement: String,
+    /// Whether all matches are replaced instead of the first one, given by the flag `g`
+    global: bool,
+}
+
+impl Substitution {
+    /// Parse a glob pattern and an expression like `s/PATTERN/REPLACEMENT/FLAGS`.
+    /// Any character may be used as the delimiter instead of `/`, which can be escaped with `\`.
+    /// The flags are `g` to replace all matches and `i` to ignore case.
+    pub fn try_new(glob: &str, expression: &str) -> Result<Self> {
+        let invalid = || {
+            format!(
+                "Invalid substitution {}, expected s/PATTERN/REPLACEMENT/",
+                expression
+            )
+        };
+        let mut chars = expression.chars();
+        anyhow::ensure!(chars.next() == Some('s'), invalid());
+        let delimiter = chars.next().with_context(invalid)?;
+        anyhow::ensure!(!delimiter.is_alphanumeric() && delimiter != '\\', invalid());
+        let parts = split_unescaped(chars.as_str(), delimiter);
+        let [pattern, replacement, flags] =
+            <[String; 3]>::try_from(parts).map_err(|_| anyhow::anyhow!(invalid()))?;
+        let mut global = false;
+        let mut ignore_case = false;
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'i' => ignore_case = true,
+                _ => anyhow::bail!("Unknown flag {} in the substitution {}", flag, expression),
+            }
+        }
+        let pattern = if ignore_case {
+            format!("(?i){}", pattern)
+        } else {
+            pattern
+        };
+        Ok(Self {
+            glob: GlobBuilder::new(glob.trim_start_matches('/'))
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("Invalid glob pattern {}", glob))?
+                .compile_matcher(),
+            by_path: glob.contains('/'),
+            pattern: Regex::new(&pattern)
+                .with_context(|| format!("Invalid pattern in the substitution {}", expression))?,
+            replacement,
+            global,
+        })
+    }
+
+    /// Whether the substitution applies to the file at `path` in `base_path`
+    pub fn applies_to(&self, path: &Path, base_path: &Path) -> bool {
+        if self.by_path {
+            self.glob
+                .is_match(path.strip_prefix(base_path).unwrap_or(path))
+        } else {
+            path.file_name()
+                .is_some_and(|name| self.glob.is_match(name))
+        }
+    }
+
+    /// Substitute in a file name
+    pub fn apply(&self, name: &str) -> String {
+        let limit = if self.global { 0 } else { 1 };
+        self.pattern
+            .replacen(name, limit, self.replacement.as_str())
+            .into_owned()
+    }
+}
+
+/// Split `text` at each `delimiter` not preceded by a backslash. Escaped delimiters are
+/// unescaped, other escapes are kept for the regular expression.
+fn split_unescaped(text: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let part = parts.last_mut().expect("there is always a part");
+        match c {
+            '\\' if chars.peek() == Some(&delimiter) => {
+                part.push(delimiter);
+                chars.next();
+            }
+            '\\' => {
+                part.push(c);
+                part.extend(chars.next());
+            }
+            c if c == delimiter => parts.push(String::new()),
+            c => part.push(c),
+        }
+    }
+    parts
+}
+
+/// Apply the substitutions to the file names in the lines of the file list. `files` are the
+/// listed files of each line. Substitutions apply in the given order, each to the result of
+/// the previous one, if their glob pattern matches the listed file.
+pub fn substitution_proposal(
+    content: &str,
+    files: &[PathBuf],
+    base_path: &Path,
+    substitutions: &[Substitution],
+) -> String {
+    content
+        .lines()
+        .zip(files)
+        .map(|(line, file)| {
+            let line = Path::new(line);
+            let name = match line.file_name() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => return line.to_string_lossy().to_string(),
+            };
+            let substituted = substitutions
+                .iter()
+                .filter(|substitution| substitution.applies_to(file, base_path))
+                .fold(name, |name, substitution| substitution.apply(&name));
+            line.with_file_name(substituted)
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,85 @@
+//! Detection of folders watched by sync clients or file system monitors.
+//! These process every renamed file, so renaming many files there causes a burst of uploads,
+//! which is worth a warning in the confirmation prompt.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A folder watched by a sync client or a file system monitor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchedFolder {
+    /// The root of the watched folder
+    pub root: PathBuf,
+    /// The name of the service watching the folder
+    pub service: &'static str,
+    /// How to pause the service while renaming
+    pub advice: &'static str,
+}
+
+impl fmt::Display for WatchedFolder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Warning: {} is watched by {}, which processes every renamed file.\n  {}",
+            self.root.to_string_lossy(),
+            self.service,
+            self.advice
+        )
+    }
+}
+
+/// Find the folder watched by a sync client or a git file system monitor containing `path`,
+/// if there is one. The closest watched folder is returned.
+pub fn watched_folder(path: &Path) -> Option<WatchedFolder> {
+    let path = path.canonicalize().ok()?;
+    path.ancestors().find_map(|ancestor| {
+        let (service, advice) = watching_service(ancestor)?;
+        Some(WatchedFolder {
+            root: ancestor.to_path_buf(),
+            service,
+            advice,
+        })
+    })
+}
+
+/// The service watching this directory, if it is the root of a watched folder,
+/// along with advice how to pause it
+fn watching_service(directory: &Path) -> Option<(&'static str, &'static str)> {
+    let name = directory.file_name()?.to_string_lossy();
+    // e.g. `~/Library/CloudStorage/OneDrive-Personal` on macOS
+    let cloud_storage = directory
+        .parent()
+        .and_then(Path::file_name)
+        .is_some_and(|parent| parent == "CloudStorage");
+    if directory.join(".dropbox").is_file()
+        || directory.join(".dropbox.cache").is_dir()
+        || (cloud_storage && name.starts_with("Dropbox"))
+    {
+        Some((
+            "Dropbox",
+            "Consider pausing syncing in the Dropbox menu while renaming.",
+        ))
+    } else if name.starts_with("OneDrive") {
+        Some((
+            "OneDrive",
+            "Consider pausing syncing in the OneDrive menu while renaming.",
+        ))
+    } else if name == "iCloudDrive" || directory.ends_with("Library/Mobile Documents") {
+        Some((
+            "iCloud Drive",
+            "iCloud Drive cannot be paused. Consider renaming while offline.",
+        ))
+    } else if cloud_storage {
+        Some((
+            "a cloud storage provider",
+            "Consider pausing syncing while renaming.",
+        ))
+    } else if directory.join(".git/fsmonitor--daemon.ipc").exists() {
+        Some((
+            "the git file system monitor",
+            "Consider stopping it with `git fsmonitor--daemon stop` while renaming.",
+        ))
+    } else {
+        None
+    }
+}
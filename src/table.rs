@@ -0,0 +1,81 @@
+//! Aligned columns of old and new file names, as used in log files and the table preview.
+
+use std::env;
+
+/// Width of the terminal if it is not given by the `COLUMNS` environment variable
+const DEFAULT_WIDTH: usize = 80;
+
+/// Separator of the columns of a table
+const TABLE_SEPARATOR: &str = " | ";
+
+/// Pad the first cell of each row to the width of the widest first cell,
+/// and join the cells with `separator`
+pub fn align_columns(rows: &[(String, String)], separator: &str) -> Vec<String> {
+    let width = rows
+        .iter()
+        .map(|(first, _)| first.chars().count())
+        .max()
+        .unwrap_or(0);
+    rows.iter()
+        .map(|(first, second)| format!("{:width$}{}{}", first, separator, second, width = width))
+        .collect()
+}
+
+/// Render rows as a table with a header, fitting it into `width` characters.
+/// Cells that are too long are truncated at their start, so that the file names stay visible.
+/// A column that fits into half of the width is never truncated.
+pub fn table(header: (&str, &str), rows: &[(String, String)], width: usize) -> String {
+    let length = |cell: &str| cell.chars().count();
+    let first = rows
+        .iter()
+        .map(|(cell, _)| length(cell))
+        .fold(length(header.0), usize::max);
+    let second = rows
+        .iter()
+        .map(|(_, cell)| length(cell))
+        .fold(length(header.1), usize::max);
+    let available = width.saturating_sub(TABLE_SEPARATOR.len());
+    let half = available / 2;
+    let (first, second) = if first + second <= available {
+        (first, second)
+    } else if first <= half {
+        (first, available - first)
+    } else if second <= half {
+        (available - second, second)
+    } else {
+        (half, available - half)
+    };
+    let row = |a: &str, b: &str| (truncate_start(a, first), truncate_start(b, second));
+    let rows: Vec<(String, String)> = std::iter::once(row(header.0, header.1))
+        .chain(std::iter::once(("-".repeat(first), "-".repeat(second))))
+        .chain(rows.iter().map(|(a, b)| row(a, b)))
+        .collect();
+    align_columns(&rows, TABLE_SEPARATOR)
+        .iter()
+        .map(|line| line.trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Shorten `text` to at most `width` characters by replacing its start with `…`
+fn truncate_start(text: &str, width: usize) -> String {
+    let length = text.chars().count();
+    if length <= width {
+        return text.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    std::iter::once('…')
+        .chain(text.chars().skip(length - width + 1))
+        .collect()
+}
+
+/// The width of the terminal, as given by the `COLUMNS` environment variable
+pub fn terminal_width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.trim().parse().ok())
+        .filter(|&columns| columns > 0)
+        .unwrap_or(DEFAULT_WIDTH)
+}
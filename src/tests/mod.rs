@@ -1,10 +1,66 @@
-use crate::{bulk_rename, create_editable_temp_file_content, BumvConfiguration};
+use crate::{
+    archive::member_names,
+    archive_proposal,
+    attributes::parse_condition,
+    break_cycles_and_fix_ordering, bulk_rename, bulk_rename_in_loop,
+    case_collision::{case_collisions, separation_proposal},
+    changes_only_whitespace,
+    config_file::{ConfigFile, ConfigValue},
+    create_directories, create_editable_temp_file_content,
+    diff::{apply_unified_diff, unified_diff_lines, DiffLine},
+    doctor::{diagnose, report},
+    dot::plan_dot,
+    editor::{
+        find_executable,
+        test_support::{
+            edit_in_buffer_file, editor_wrapping_width, parse_editor_command, resolve_editor_name,
+            resolve_remote_editor,
+        },
+    },
+    expand::expand_placeholders,
+    explain::explain_ignores,
+    find_unwritable_directories, format_size,
+    fs_capabilities::FilesystemCapabilities,
+    is_confirmation,
+    json::JsonValue,
+    lint::{lint, LintRule, LintSeverities, Severity},
+    listing::{GitIndex, ListingProvider, Stdin},
+    listing_summary,
+    logs::{clean_logs, is_log_file_name, parse_age},
+    mapping_file::{apply_mapping, field, header, parse_mapping},
+    merge_proposal, notify, parse_dir_mode, partition_by_volume,
+    plan_file::{self, requested_mapping, PlanOptions},
+    protect::ProtectedFiles,
+    render::{group_by_directory, to_text, Change, DirectorySummary, Segment},
+    renumber::RenumberTemplate,
+    roll_back, same_content,
+    session::SessionId,
+    split_case_only_renamings, step_batches,
+    substitute::{substitution_proposal, Substitution},
+    sweeping_base_path,
+    sync::watched_folder,
+    table::{align_columns, table},
+    tour::{create_example_files, run_tour},
+    transfer::{
+        copy_file, file_checksum, move_file, same_device, Sandbox, SecurityContext, TransferOptions,
+    },
+    undo::undo_mapping,
+    unused_temp_file_name, validate_edited_content,
+    walk::{preview, walk},
+    BumvConfiguration, ConfirmDefault, IgnoreSources, LineWidth, Outcome, Overwrite, Preview,
+    RenamingPlan, RenamingRequest, ValidationError,
+};
 use std::{
     cell::RefCell,
+    collections::HashMap,
+    ffi::OsString,
     fs::{self, File},
     io::Write,
+    path::{Path, PathBuf},
     rc::Rc,
+    time::{Duration, SystemTime},
 };
+use structopt::StructOpt;
 use tempfile::{tempdir, TempDir};
 
 fn prompt_function(prompt: String) -> bool {
@@ -59,8 +115,10 @@ fn test_read_directory_files_nonrecursive() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.into_path()),
+        ..Default::default()
     }
-    .file_list();
+    .file_list()
+    .unwrap();
 
     assert_eq!(files.len(), 2);
     assert_eq!(files[0].file_name().unwrap(), "file1.txt");
@@ -79,8 +137,10 @@ fn test_read_directory_files_nonrecursive_no_ignore() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.into_path()),
+        ..Default::default()
     }
-    .file_list();
+    .file_list()
+    .unwrap();
 
     assert_eq!(files.len(), 4);
     assert_eq!(files[0].file_name().unwrap(), ".ignore");
@@ -101,8 +161,10 @@ fn test_read_directory_files_recursive() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.into_path()),
+        ..Default::default()
     }
-    .file_list();
+    .file_list()
+    .unwrap();
 
     assert_eq!(files.len(), 4);
     // assertions take into account temp dir prefixes
@@ -124,8 +186,10 @@ fn test_read_directory_files_recursive_no_ignore() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.into_path()),
+        ..Default::default()
     }
-    .file_list();
+    .file_list()
+    .unwrap();
 
     assert_eq!(files.len(), 6);
     // assertions take into account temp dir prefixes
@@ -149,8 +213,10 @@ fn test_create_temp_file_content() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.into_path()),
+        ..Default::default()
     }
-    .file_list();
+    .file_list()
+    .unwrap();
 
     let content = create_editable_temp_file_content(&files);
 
@@ -182,6 +248,7 @@ fn scenario_test_rename_files() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
     };
 
     let prompted = Rc::new(RefCell::new(false));
@@ -240,6 +307,7 @@ fn scenario_test_rename_files_recursive() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
     };
 
     let prompted = Rc::new(RefCell::new(false));
@@ -254,15 +322,8 @@ fn scenario_test_rename_files_recursive() {
         },
         Box::new(move |prompt: String| {
             println!("prompt:\n{}", prompt);
-            // make test robust to unstable topological sort
-            let (rename_prompt_1, rename_prompt_2) = {
-                let (rename_prompt_a, rename_prompt_b) = prompt.split_once('\n').unwrap();
-                if rename_prompt_a.contains("renamed_file1") {
-                    (rename_prompt_a, rename_prompt_b)
-                } else {
-                    (rename_prompt_b, rename_prompt_a)
-                }
-            };
+            // independent steps are ordered by path
+            let (rename_prompt_1, rename_prompt_2) = prompt.split_once('\n').unwrap();
 
             let (from, to) = rename_prompt_1.split_once(" -> ").unwrap();
             // assertions take into account temp dir prefixes
@@ -301,6 +362,7 @@ fn scenario_test_detect_duplicate_target_names() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
     };
 
     let err = bulk_rename(
@@ -312,7 +374,17 @@ fn scenario_test_detect_duplicate_target_names() {
 
     assert_eq!(
         err.to_string(),
-        "There is a name clash in the edited files."
+        format!(
+            "Line 2: {} is also the target in line 1.",
+            dir.path().join("file2.txt").to_string_lossy()
+        )
+    );
+    let diagnostics = &err.downcast_ref::<ValidationError>().unwrap().diagnostics;
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].line, 2);
+    assert_eq!(
+        diagnostics[0].text,
+        dir.path().join("file2.txt").to_string_lossy()
     );
     assert_no_filenames_changed(&dir);
 }
@@ -328,6 +400,7 @@ fn scenario_test_detect_invalid_editing() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
     };
 
     let err = bulk_rename(
@@ -355,6 +428,7 @@ fn scenario_test_detect_directory_renaming() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
     };
 
     bulk_rename(
@@ -389,6 +463,7 @@ fn scenario_test_detect_changed_files() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
     };
     let path = dir.path().to_path_buf();
 
@@ -422,6 +497,7 @@ fn scenario_test_detect_overwrite_of_file_not_part_of_listing() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
     };
 
     let err = bulk_rename(
@@ -447,6 +523,7 @@ fn scenario_test_detect_overwrite_of_new_file_not_part_of_listing() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
     };
     let path = dir.path().to_path_buf();
 
@@ -476,6 +553,7 @@ fn scenario_test_detect_fix_renaming_order() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
     };
 
     bulk_rename(
@@ -515,10 +593,11 @@ fn direct_cycle_test() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
     };
 
     // Create a direct cycle: file1.txt -> file2.txt, file2.txt -> file1.txt
-    let _ = bulk_rename(
+    bulk_rename(
         config,
         |content| {
             Ok({
@@ -553,10 +632,11 @@ fn longer_cycle_test() {
         no_log: true,
         use_vscode: false,
         base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
     };
 
     // Create a longer cycle: file1.txt -> file2.txt, file2.txt -> file3.txt, file3.txt -> file1.txt
-    let _ = bulk_rename(
+    bulk_rename(
         config,
         |content| {
             Ok({
@@ -583,3 +663,4167 @@ fn longer_cycle_test() {
     assert_eq!(new_content_file2, "file1_content");
     assert_eq!(new_content_file3, "file2_content");
 }
+
+/// Verify that file names exceeding the filesystem's maximum name length are rejected
+/// before any renaming takes place
+#[test]
+fn scenario_test_detect_too_long_file_name() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        recursive: false,
+        no_ignore: false,
+        no_log: true,
+        use_vscode: false,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let too_long_name = "x".repeat(2000);
+
+    let err = bulk_rename(
+        config,
+        |content| Ok(content.replace("file1.txt", &too_long_name)),
+        Box::new(prompt_function),
+    )
+    .unwrap_err();
+
+    assert!(err
+        .to_string()
+        .contains("exceeds the maximum file name length"));
+    assert_no_filenames_changed(&dir);
+}
+
+/// Verify that case-only renamings are split into two steps on case insensitive filesystems
+#[test]
+fn test_split_case_only_renamings() {
+    let capabilities = FilesystemCapabilities {
+        case_sensitive: false,
+        ..Default::default()
+    };
+    let steps = split_case_only_renamings(
+        vec![
+            (PathBuf::from("a.txt"), PathBuf::from("A.txt")),
+            (PathBuf::from("b.txt"), PathBuf::from("c.txt")),
+        ],
+        &capabilities,
+    )
+    .unwrap();
+
+    assert_eq!(
+        steps,
+        vec![
+            (PathBuf::from("a.txt"), PathBuf::from("a.txt.n0.tmp")),
+            (PathBuf::from("a.txt.n0.tmp"), PathBuf::from("A.txt")),
+            (PathBuf::from("b.txt"), PathBuf::from("c.txt")),
+        ]
+    );
+
+    // case sensitive filesystems rename directly
+    let steps = split_case_only_renamings(
+        vec![(PathBuf::from("a.txt"), PathBuf::from("A.txt"))],
+        &FilesystemCapabilities::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        steps,
+        vec![(PathBuf::from("a.txt"), PathBuf::from("A.txt"))]
+    );
+}
+
+/// Verify detection of a target that would be the parent directory of another file
+/// ```
+/// file1.txt
+/// file2.txt
+/// subdir/file3.txt
+/// subdir/file4.txt
+/// ```
+/// to
+/// ```
+/// file1.txt
+/// subdir
+/// subdir/file3.txt
+/// subdir/file4.txt
+/// ```
+#[test]
+fn scenario_test_detect_nested_target_conflict() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        recursive: true,
+        no_ignore: false,
+        no_log: true,
+        use_vscode: false,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+
+    let err = bulk_rename(
+        config,
+        |content| Ok(content.replace("file2.txt", "subdir")),
+        Box::new(prompt_function),
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Line 2: The file {} would be the parent directory of {} in line 3.\n\
+             Line 2: The file {} would be the parent directory of {} in line 4.",
+            dir.path().join("subdir").to_string_lossy(),
+            dir.path()
+                .join("subdir")
+                .join("file3.txt")
+                .to_string_lossy(),
+            dir.path().join("subdir").to_string_lossy(),
+            dir.path()
+                .join("subdir")
+                .join("file4.txt")
+                .to_string_lossy()
+        )
+    );
+    assert_no_filenames_changed(&dir);
+}
+
+/// Verify that directories are created in a separate phase which is shown in the prompt
+#[test]
+fn scenario_test_create_directories_phase() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        recursive: false,
+        no_ignore: false,
+        no_log: true,
+        use_vscode: false,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let dir_path = dir.path().to_path_buf();
+    let new_dir = dir.path().join("new");
+    let new_sub_dir = new_dir.join("dir");
+
+    bulk_rename(
+        config,
+        |content| Ok(content.replace("file1.txt", "new/dir/file1.txt")),
+        Box::new(move |prompt: String| {
+            println!("prompt:\n{}", prompt);
+            let lines: Vec<_> = prompt.lines().collect();
+            assert_eq!(lines.len(), 8);
+            assert_eq!(
+                lines[0],
+                format!("Create directory {}", new_dir.to_string_lossy())
+            );
+            assert_eq!(
+                lines[1],
+                format!("Create directory {}", new_sub_dir.to_string_lossy())
+            );
+            assert!(lines[2].ends_with("new/dir/file1.txt"));
+            // the summary follows after an empty line
+            assert_eq!(
+                lines[3..],
+                [
+                    "".to_string(),
+                    "Summary:".to_string(),
+                    format!("  1 file will leave {}", dir_path.to_string_lossy()),
+                    format!("  1 file will arrive in {}", new_sub_dir.to_string_lossy()),
+                    "  2 directories will be created".to_string(),
+                ]
+            );
+            true
+        }),
+    )
+    .unwrap();
+
+    assert!(!dir.path().join("file1.txt").exists());
+    assert!(dir
+        .path()
+        .join("new")
+        .join("dir")
+        .join("file1.txt")
+        .exists());
+}
+
+/// Verify that created directories are removed again if renaming fails
+#[test]
+fn scenario_test_created_directories_are_rolled_back() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        recursive: false,
+        no_ignore: false,
+        no_log: true,
+        use_vscode: false,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+
+    // the target needs a new directory, but resolves to a file that already exists
+    let err = bulk_rename(
+        config,
+        |content| Ok(content.replace("file1.txt", "new/../ignored.txt")),
+        Box::new(prompt_function),
+    )
+    .unwrap_err();
+
+    assert!(err.to_string().contains("ignored.txt already exists"));
+    assert!(!dir.path().join("new").exists());
+    assert_no_filenames_changed(&dir);
+}
+
+/// Verify that declining the renaming is reported as its own outcome, writes no log file
+/// and saves the rejected plan if requested
+#[test]
+fn scenario_test_abort() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let rejected_plan = dir.path().join("subdir").join("rejected.json");
+    let config = BumvConfiguration {
+        recursive: false,
+        no_ignore: false,
+        no_log: false,
+        use_vscode: false,
+        base_path: Some(dir.path().to_path_buf()),
+        save_rejected_plan: Some(rejected_plan.clone()),
+        ..Default::default()
+    };
+
+    let outcome = bulk_rename(
+        config.clone(),
+        |content| Ok(content.replace("file1.txt", "renamed_file1.txt")),
+        |_| false,
+    )
+    .unwrap();
+
+    assert_eq!(outcome, Outcome::Aborted);
+    assert_eq!(outcome.exit_code(), 2);
+    assert_no_filenames_changed(&dir);
+    let log_files = fs::read_dir(dir.path())
+        .unwrap()
+        .filter(|entry| {
+            let name = entry.as_ref().unwrap().file_name();
+            name.to_string_lossy().starts_with("bumv_")
+        })
+        .count();
+    assert_eq!(log_files, 0);
+
+    // the saved plan can be applied later
+    let document = fs::read_to_string(&rejected_plan).unwrap();
+    let (mapping, _) = requested_mapping(&document).unwrap();
+    assert_eq!(
+        mapping,
+        vec![(
+            dir.path().join("file1.txt"),
+            dir.path().join("renamed_file1.txt")
+        )]
+    );
+    let config = BumvConfiguration {
+        no_log: true,
+        save_rejected_plan: None,
+        apply: Some(rejected_plan),
+        ..config
+    };
+    let outcome = bulk_rename(
+        config,
+        |content| apply_mapping(&content, &mapping),
+        |_| true,
+    )
+    .unwrap();
+    assert_eq!(outcome, Outcome::Renamed);
+    assert!(dir.path().join("renamed_file1.txt").exists());
+}
+
+/// Validate JSON serialization including escaping
+#[test]
+fn test_json_serialization() {
+    let value = JsonValue::object([
+        ("outcome", "error".into()),
+        ("error", "a \"quoted\"\nline\\".into()),
+        ("count", 3usize.into()),
+        ("list", vec![true, false].into()),
+        ("missing", None::<String>.into()),
+    ]);
+
+    assert_eq!(
+        value.to_string(),
+        r#"{"outcome":"error","error":"a \"quoted\"\nline\\","count":3,"list":[true,false],"missing":null}"#
+    );
+}
+
+/// Validate interpretation of the answer to the confirmation prompt
+#[test]
+fn test_is_confirmation() {
+    assert!(is_confirmation("", ConfirmDefault::Yes));
+    assert!(!is_confirmation("", ConfirmDefault::No));
+    assert!(is_confirmation("Y", ConfirmDefault::No));
+    assert!(is_confirmation("yes", ConfirmDefault::No));
+    assert!(!is_confirmation("n", ConfirmDefault::Yes));
+    assert!(!is_confirmation("nope", ConfirmDefault::Yes));
+}
+
+/// Validate that the configuration file fills in options not given on the command line
+#[test]
+fn test_apply_config_file() {
+    let config_file = ConfigFile::parse(
+        "# bumv configuration\nconfirm-default = \"no\" # safer\n\n[unrelated]\nkey = [\"a,b\", \"c\"]\n",
+    )
+    .unwrap();
+
+    let mut config = BumvConfiguration::default();
+    config.apply_config_file(&config_file).unwrap();
+    assert_eq!(config.confirm_default, Some(ConfirmDefault::No));
+
+    // the command line takes precedence
+    let mut config = BumvConfiguration {
+        confirm_default: Some(ConfirmDefault::Yes),
+        ..Default::default()
+    };
+    config.apply_config_file(&config_file).unwrap();
+    assert_eq!(config.confirm_default, Some(ConfirmDefault::Yes));
+
+    assert_eq!(
+        config_file.get("unrelated", "key"),
+        Some(&ConfigValue::Array(vec![
+            "a,b".to_string(),
+            "c".to_string()
+        ]))
+    );
+    assert!(ConfigFile::parse("confirm-default").is_err());
+}
+
+/// Validate that the default flags of the closest matching path pattern are used,
+/// unless the command line gives the same flags
+#[cfg(unix)]
+#[test]
+fn test_path_flags() {
+    let dir = tempdir().unwrap();
+    let base = fs::canonicalize(dir.path()).unwrap();
+    let base_str = base.to_string_lossy();
+    let config_file = ConfigFile::parse(&format!(
+        "[paths]\n\"{}\" = \"--recursive --preview table\"\n\"{}/sub/**\" = [\"--no-log\"]\n\"~/other\" = \"-n\"\n",
+        base_str, base_str
+    ))
+    .unwrap();
+    let flags =
+        |directory: &Path, home: Option<&Path>| config_file.path_flags(directory, home).unwrap();
+    assert_eq!(
+        flags(&base.join("sub"), None),
+        ["--recursive", "--preview", "table"]
+    );
+    assert_eq!(flags(&base.join("sub/deeper"), None), ["--no-log"]);
+    assert_eq!(flags(Path::new("/elsewhere"), None), Vec::<String>::new());
+    assert_eq!(
+        flags(
+            Path::new("/home/user/other/a"),
+            Some(Path::new("/home/user"))
+        ),
+        ["-n"]
+    );
+
+    let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+    let command_line = args(&["bumv", "--preview", "list", &base_str]);
+    let config = BumvConfiguration::from_args_with_path_flags(
+        BumvConfiguration::from_iter(&command_line),
+        command_line,
+        &config_file,
+    )
+    .unwrap();
+    assert!(config.recursive);
+    assert_eq!(config.preview, Preview::List);
+    assert!(!config.no_log);
+}
+
+/// Validate verified copies and checksums
+#[test]
+fn test_copy_file_verified() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let source = dir.path().join("file1.txt");
+    let target = dir.path().join("copy.txt");
+
+    copy_file(
+        &source,
+        &target,
+        &TransferOptions {
+            verify_copy: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(fs::read_to_string(&target).unwrap(), "file1_content");
+    assert_eq!(
+        file_checksum(&source).unwrap(),
+        file_checksum(&target).unwrap()
+    );
+    assert_ne!(
+        file_checksum(&source).unwrap(),
+        file_checksum(&dir.path().join("file2.txt")).unwrap()
+    );
+}
+
+/// Verify that copies keep the SELinux security context with --preserve-context.
+/// Without SELinux, there is no context, which must not fail the copy.
+#[cfg(target_os = "linux")]
+#[test]
+fn test_copy_file_preserves_security_context() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let source = dir.path().join("file1.txt");
+    let target = dir.path().join("copy.txt");
+
+    copy_file(
+        &source,
+        &target,
+        &TransferOptions {
+            security_context: SecurityContext::Preserve,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let context = |path: &Path| xattr::get(path, "security.selinux").ok().flatten();
+    assert_eq!(fs::read_to_string(&target).unwrap(), "file1_content");
+    assert_eq!(context(&target), context(&source));
+}
+
+/// Verify that Finder tags, stored as extended attributes, are preserved by copying on macOS
+#[cfg(target_os = "macos")]
+#[test]
+fn test_copy_file_preserves_finder_tags() {
+    use std::process::Command;
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let source = dir.path().join("file1.txt");
+    let target = dir.path().join("copy.txt");
+    let tags = "com.apple.metadata:_kMDItemUserTags";
+    let status = Command::new("xattr")
+        .args(["-w", tags, "Red"])
+        .arg(&source)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    copy_file(
+        &source,
+        &target,
+        &TransferOptions {
+            verify_copy: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let output = Command::new("xattr")
+        .args(["-p", tags])
+        .arg(&target)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "Red");
+}
+
+/// Verify that a dry run does not touch the filesystem and reports whether files would be renamed
+#[test]
+fn scenario_test_dry_run() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let listing = || {
+        let mut names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        names
+    };
+    let modified = || fs::metadata(dir.path()).unwrap().modified().unwrap();
+    let (names_before, modified_before) = (listing(), modified());
+    let config = BumvConfiguration {
+        recursive: false,
+        no_ignore: false,
+        no_log: false,
+        use_vscode: false,
+        base_path: Some(dir.path().to_path_buf()),
+        dry_run: true,
+        ..Default::default()
+    };
+
+    let outcome = bulk_rename(
+        config.clone(),
+        |content| Ok(content.replace("file1.txt", "renamed_file1.txt")),
+        |_| panic!("a dry run must not prompt"),
+    )
+    .unwrap();
+    assert_eq!(outcome, Outcome::WouldRename);
+    assert_eq!(outcome.exit_code(), 3);
+    assert_no_filenames_changed(&dir);
+    assert!(!dir.path().join("renamed_file1.txt").exists());
+    assert_eq!(listing(), names_before);
+    assert_eq!(modified(), modified_before);
+
+    let outcome = bulk_rename(config, Ok, |_| panic!("a dry run must not prompt")).unwrap();
+    assert_eq!(outcome, Outcome::NothingToRename);
+    assert_eq!(outcome.exit_code(), 0);
+}
+
+/// Validate creating and applying unified diffs of the file list
+#[test]
+fn test_unified_diff_roundtrip() {
+    let original: Vec<String> = (1..=10).map(|i| format!("file{}.txt", i)).collect();
+    let mut edited = original.clone();
+    edited[1] = "renamed2.txt".to_string();
+    edited[2] = "renamed3.txt".to_string();
+    edited[9] = "renamed10.txt".to_string();
+
+    let patch = to_text(
+        &[Segment::Diff(unified_diff_lines(&original, &edited))],
+        Preview::List,
+    );
+    assert_eq!(
+        patch,
+        "--- a/bumv-file-list\n+++ b/bumv-file-list\n\
+         @@ -1,10 +1,10 @@\n file1.txt\n-file2.txt\n-file3.txt\n+renamed2.txt\n+renamed3.txt\n \
+         file4.txt\n file5.txt\n file6.txt\n file7.txt\n file8.txt\n file9.txt\n-file10.txt\n+renamed10.txt"
+    );
+
+    let applied = apply_unified_diff(&original.join("\n"), &patch).unwrap();
+    assert_eq!(applied, edited.join("\n"));
+
+    // a diff created for a different file list is rejected
+    let err = apply_unified_diff(&original[1..].join("\n"), &patch).unwrap_err();
+    assert!(err.to_string().contains("does not match the file list"));
+}
+
+/// Verify renaming driven by a diff instead of an editor, and the diff shown in the prompt
+#[test]
+fn scenario_test_apply_diff() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        recursive: false,
+        no_ignore: false,
+        no_log: true,
+        use_vscode: false,
+        base_path: Some(dir.path().to_path_buf()),
+        diff: true,
+        ..Default::default()
+    };
+    let file1 = dir.path().join("file1.txt").to_string_lossy().to_string();
+    let file2 = dir.path().join("file2.txt").to_string_lossy().to_string();
+    let renamed = dir.path().join("renamed.txt").to_string_lossy().to_string();
+    let patch = format!(
+        "--- a/bumv-file-list\n+++ b/bumv-file-list\n@@ -1,2 +1,2 @@\n-{}\n+{}\n {}\n",
+        file1, renamed, file2
+    );
+    let expected_prompt = patch.trim_end().to_string();
+
+    bulk_rename(
+        config,
+        move |content| apply_unified_diff(&content, &patch),
+        move |prompt| {
+            assert_eq!(prompt, expected_prompt);
+            true
+        },
+    )
+    .unwrap();
+
+    assert!(!dir.path().join("file1.txt").exists());
+    assert!(dir.path().join("renamed.txt").exists());
+}
+
+/// Verify that protected files can be listed but not renamed
+#[test]
+fn scenario_test_detect_renaming_of_protected_file() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        recursive: true,
+        no_ignore: false,
+        no_log: true,
+        use_vscode: false,
+        base_path: Some(dir.path().to_path_buf()),
+        protect: vec!["subdir/*3.txt".to_string()],
+        ..Default::default()
+    };
+
+    // renaming other files is fine
+    bulk_rename(
+        config.clone(),
+        |content| Ok(content.replace("file1.txt", "renamed_file1.txt")),
+        Box::new(prompt_function),
+    )
+    .unwrap();
+    assert!(dir.path().join("renamed_file1.txt").exists());
+
+    let err = bulk_rename(
+        config,
+        |content| Ok(content.replace("file3.txt", "renamed_file3.txt")),
+        Box::new(prompt_function),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "The file {} is protected and must not be renamed.",
+            dir.path()
+                .join("subdir")
+                .join("file3.txt")
+                .to_string_lossy()
+        )
+    );
+    assert!(dir.path().join("subdir").join("file3.txt").exists());
+}
+
+/// Validate matching of protection patterns by file name and by relative path
+#[test]
+fn test_protected_files() {
+    let protected = ProtectedFiles::try_new(
+        Path::new("."),
+        &["Cargo.toml".to_string(), "/src/*.rs".to_string()],
+    )
+    .unwrap();
+
+    assert!(protected.is_protected(Path::new("./Cargo.toml")));
+    assert!(protected.is_protected(Path::new("./nested/Cargo.toml")));
+    assert!(protected.is_protected(Path::new("./src/main.rs")));
+    assert!(!protected.is_protected(Path::new("./src/tests/mod.rs")));
+    assert!(!protected.is_protected(Path::new("./README.md")));
+}
+
+/// Verify that the listing printed by --list is exactly the content presented for editing
+#[test]
+fn test_editable_file_list_matches_editor_content() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        recursive: true,
+        no_ignore: false,
+        no_log: true,
+        use_vscode: false,
+        base_path: Some(dir.path().to_path_buf()),
+        list: true,
+        ..Default::default()
+    };
+    let listing = config.editable_file_list().unwrap();
+
+    bulk_rename(
+        config,
+        move |content| {
+            assert_eq!(content, listing);
+            Ok(content)
+        },
+        Box::new(prompt_function),
+    )
+    .unwrap();
+}
+
+/// Verify that bumv only prints the listing when used in a pipe without a terminal on either
+/// side, unless a diff is applied, other output is requested or renaming is confirmed in
+/// advance with --yes
+#[test]
+fn test_lists_only_when_stdout_is_not_a_terminal() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let mut config = BumvConfiguration {
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    assert!(!config.lists_only(true, true));
+    assert!(!config.lists_only(true, false));
+    assert!(!config.lists_only(false, true));
+    assert!(config.lists_only(false, false));
+    assert_eq!(
+        config.listing().unwrap(),
+        config.editable_file_list().unwrap()
+    );
+
+    config.dry_run = true;
+    assert!(!config.lists_only(false, false));
+    config.dry_run = false;
+    config.diff = true;
+    assert!(!config.lists_only(false, false));
+    config.diff = false;
+
+    config.json = true;
+    assert!(!config.lists_only(false, false));
+    let file1 = dir.path().join("file1.txt");
+    assert_eq!(
+        config.listing().unwrap(),
+        format!(
+            "{{\"files\":[{},{}]}}",
+            JsonValue::from(file1.to_str().unwrap()),
+            JsonValue::from(dir.path().join("file2.txt").to_str().unwrap())
+        )
+    );
+    config.json = false;
+
+    config.yes = true;
+    assert!(!config.lists_only(false, false));
+    config.yes = false;
+
+    config.apply_diff = Some(dir.path().join("rename.patch"));
+    assert!(!config.lists_only(false, false));
+    config.list = true;
+    assert!(config.lists_only(true, true));
+}
+
+/// Verify that --attr-filter lists the files by their attributes in .gitattributes, with
+/// deeper attribute files taking precedence
+#[test]
+fn test_attr_filter() {
+    let dir = tempdir().unwrap();
+    fs::create_dir_all(dir.path().join(".git/info")).unwrap();
+    fs::create_dir_all(dir.path().join("assets/generated")).unwrap();
+    fs::write(
+        dir.path().join(".gitattributes"),
+        "# assets\n*.png asset kind=image\n/docs.md export-ignore\nassets/** -diff\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("assets/generated/.gitattributes"),
+        "*.png !asset\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join(".git/info/attributes"),
+        "logo.png kind=logo\n",
+    )
+    .unwrap();
+    for file in [
+        "docs.md",
+        "readme.md",
+        "assets/logo.png",
+        "assets/icon.png",
+        "assets/generated/chart.png",
+    ] {
+        fs::write(dir.path().join(file), "").unwrap();
+    }
+    let listed = |filters: &[&str]| {
+        let config = BumvConfiguration {
+            recursive: true,
+            base_path: Some(dir.path().to_path_buf()),
+            attr_filter: filters
+                .iter()
+                .map(|filter| parse_condition(filter).unwrap())
+                .collect(),
+            ..Default::default()
+        };
+        config
+            .file_list()
+            .unwrap()
+            .iter()
+            .map(|path| {
+                path.strip_prefix(dir.path())
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(listed(&["asset"]), ["assets/icon.png", "assets/logo.png"]);
+    assert_eq!(listed(&["kind=logo"]), ["assets/logo.png"]);
+    assert_eq!(
+        listed(&["kind=image", "-diff"]),
+        ["assets/generated/chart.png", "assets/icon.png"]
+    );
+    assert_eq!(listed(&["export-ignore"]), ["docs.md"]);
+    assert_eq!(listed(&["!export-ignore", "!kind"]), ["readme.md"]);
+    assert!(parse_condition("").is_err());
+    assert!(parse_condition("a b").is_err());
+}
+
+/// Validate reading the files to rename from stdin, separated by lines or NUL bytes
+#[test]
+fn test_stdin_listing() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let file1 = dir.path().join("file1.txt");
+    let file3 = dir.path().join("subdir/file3.txt");
+    let lines = format!(
+        "{}\r\n\n{}\n{}\n{}\n",
+        file1.display(),
+        file3.display(),
+        file1.display(),
+        dir.path().join("missing.txt").display()
+    );
+    let stdin = Stdin::read(lines.as_bytes()).unwrap();
+    assert_eq!(stdin.paths().len(), 4);
+    let config = BumvConfiguration {
+        stdin_listing: Some(stdin),
+        ..Default::default()
+    };
+    assert_eq!(config.file_list().unwrap(), [file1.clone(), file3.clone()]);
+    assert!(config.check_arguments().is_err());
+
+    let separated = format!("{}\0{}\0", file3.display(), file1.display());
+    let stdin = Stdin::read(separated.as_bytes()).unwrap();
+    assert_eq!(stdin.paths(), [file3, file1]);
+}
+
+/// Verify that --git-index lists the files tracked by git, also in the working tree
+#[test]
+fn test_git_index_listing() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let git = |args: &[&str]| {
+        std::process::Command::new("git")
+            .arg("-C")
+            .arg(dir.path())
+            .args(args)
+            .output()
+            .is_ok_and(|output| output.status.success())
+    };
+    if !git(&["init", "-q"]) {
+        // git is not installed
+        return;
+    }
+    assert!(git(&[
+        "add",
+        "file1.txt",
+        "ignored.txt",
+        "subdir/file3.txt"
+    ]));
+    let listed = |recursive: bool| {
+        let mut files: Vec<PathBuf> = GitIndex {
+            directory: dir.path(),
+            recursive,
+        }
+        .files()
+        .unwrap()
+        .collect();
+        files.sort();
+        files
+    };
+    assert_eq!(
+        listed(true),
+        [
+            dir.path().join("file1.txt"),
+            dir.path().join("ignored.txt"),
+            dir.path().join("subdir/file3.txt"),
+        ]
+    );
+    fs::remove_file(dir.path().join("ignored.txt")).unwrap();
+    assert_eq!(listed(false), [dir.path().join("file1.txt")]);
+    // files outside of a sparse checkout are marked skip-worktree
+    assert!(git(&["update-index", "--skip-worktree", "file1.txt"]));
+    assert_eq!(listed(true), [dir.path().join("subdir/file3.txt")]);
+    assert!(GitIndex {
+        directory: &dir.path().join("missing"),
+        recursive: true,
+    }
+    .files()
+    .is_err());
+}
+
+/// Verify permissions of created directories, inherited from the parent or given explicitly
+#[cfg(unix)]
+#[test]
+fn scenario_test_created_directory_modes() {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o751)).unwrap();
+    let config = BumvConfiguration {
+        recursive: false,
+        no_ignore: false,
+        no_log: true,
+        use_vscode: false,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    bulk_rename(
+        config.clone(),
+        |content| Ok(content.replace("file1.txt", "inherited/file1.txt")),
+        Box::new(prompt_function),
+    )
+    .unwrap();
+    assert_eq!(mode(&dir.path().join("inherited")), 0o751);
+
+    let config = BumvConfiguration {
+        dir_mode: Some(parse_dir_mode("0700").unwrap()),
+        ..config
+    };
+    bulk_rename(
+        config,
+        |content| Ok(content.replace("file2.txt", "explicit/nested/file2.txt")),
+        Box::new(prompt_function),
+    )
+    .unwrap();
+    assert_eq!(mode(&dir.path().join("explicit")), 0o700);
+    assert_eq!(mode(&dir.path().join("explicit").join("nested")), 0o700);
+}
+
+/// Validate parsing of octal directory modes
+#[test]
+fn test_parse_dir_mode() {
+    assert_eq!(parse_dir_mode("0755").unwrap(), 0o755);
+    assert_eq!(parse_dir_mode("0o2775").unwrap(), 0o2775);
+    assert!(parse_dir_mode("0955").is_err());
+    assert!(parse_dir_mode("17777").is_err());
+}
+
+/// Verify detection of a listed file disappearing while the program is running
+#[test]
+fn scenario_test_detect_removed_files() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        recursive: true,
+        no_ignore: false,
+        no_log: true,
+        use_vscode: false,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let path = dir.path().to_path_buf();
+
+    let err = bulk_rename(
+        config,
+        |content| Ok(content.replace("file1.txt", "renamed_file1.txt")),
+        Box::new(move |prompt| {
+            println!("prompt:\n{}", prompt);
+            fs::remove_file(path.join("subdir").join("file4.txt")).unwrap();
+            true
+        }),
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "The files in the directory changed while you were editing them."
+    );
+    assert!(dir.path().join("file1.txt").exists());
+}
+
+/// Verify that temporary names are created for any file name, and root paths are rejected
+/// instead of causing a panic
+#[test]
+fn test_unused_temp_file_name() {
+    let mut counter = 0;
+    assert_eq!(
+        unused_temp_file_name(Path::new("dir/file.txt"), &mut counter).unwrap(),
+        PathBuf::from("dir/file.txt.n0.tmp")
+    );
+    assert_eq!(counter, 1);
+    assert!(unused_temp_file_name(Path::new("/"), &mut counter).is_err());
+
+    #[cfg(unix)]
+    {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+        let non_utf8 = Path::new(OsStr::from_bytes(b"dir/\xff.txt"));
+        let temp_file = unused_temp_file_name(non_utf8, &mut counter).unwrap();
+        assert_eq!(temp_file.as_os_str().as_bytes(), b"dir/\xff.txt.n1.tmp");
+    }
+}
+
+/// Verify that FIFOs are listed with --no-ignore, marked in the file list and the confirmation
+/// prompt and renamed
+#[cfg(unix)]
+#[test]
+fn scenario_test_rename_fifo() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let status = std::process::Command::new("mkfifo")
+        .arg(dir.path().join("pipe"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    assert!(!config
+        .file_list()
+        .unwrap()
+        .contains(&dir.path().join("pipe")));
+    let config = BumvConfiguration {
+        no_ignore: true,
+        ..config
+    };
+    let files = config.file_list().unwrap();
+    assert!(files.contains(&dir.path().join("pipe")));
+    assert!(listing_summary(&files, dir.path()).ends_with(", including 1 special file"));
+    let marked = format!("{}\t# FIFO", dir.path().join("pipe").to_string_lossy());
+    assert!(config.listing().unwrap().lines().any(|line| line == marked));
+
+    let prompt = Rc::new(RefCell::new(String::new()));
+    let prompt_clone = prompt.clone();
+    bulk_rename(
+        config,
+        |content| {
+            assert!(content.contains("/pipe\t# FIFO"));
+            Ok(content.replace("/pipe", "/renamed_pipe"))
+        },
+        move |message| {
+            *prompt_clone.borrow_mut() = message;
+            true
+        },
+    )
+    .unwrap();
+
+    assert!(prompt.borrow().contains("renamed_pipe (FIFO)"));
+    assert!(!dir.path().join("pipe").exists());
+    assert!(dir.path().join("renamed_pipe").exists());
+}
+
+/// Verify renaming files given as arguments instead of a base path
+#[test]
+fn scenario_test_rename_explicit_files() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().join("subdir").join("file4.txt")),
+        files: vec![
+            dir.path().join("file1.txt"),
+            // ignore files do not apply to explicitly given files
+            dir.path().join("ignored.txt"),
+            dir.path().join("file1.txt"),
+        ],
+        ..Default::default()
+    };
+
+    bulk_rename(
+        config.clone(),
+        |content| {
+            let lines: Vec<_> = content.lines().collect();
+            assert_eq!(lines.len(), 3);
+            assert!(lines[0].ends_with("/file1.txt"));
+            assert!(lines[1].ends_with("/ignored.txt"));
+            assert!(lines[2].ends_with("/subdir/file4.txt"));
+            Ok(content.replace("ignored.txt", "not_ignored.txt"))
+        },
+        Box::new(prompt_function),
+    )
+    .unwrap();
+
+    assert!(!dir.path().join("ignored.txt").exists());
+    assert!(dir.path().join("not_ignored.txt").exists());
+
+    // the renamed file is missing now
+    let err = bulk_rename(config, Ok, Box::new(prompt_function)).unwrap_err();
+    assert!(err
+        .to_string()
+        .ends_with("/ignored.txt is not a file. Either give a base path or files to rename."));
+}
+
+/// Verify that a single file given as base path is renamed like with `mv`, without treating
+/// its directory as outside of the base path
+#[test]
+fn scenario_test_rename_single_file() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let file = dir.path().join("subdir").join("file3.txt");
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(file.clone()),
+        ..Default::default()
+    };
+    let prompt = Rc::new(RefCell::new(String::new()));
+    let prompt_clone = prompt.clone();
+
+    let outcome = bulk_rename(
+        config,
+        |content| {
+            assert_eq!(content, file.to_string_lossy());
+            Ok(content.replace("file3.txt", "renamed.txt"))
+        },
+        move |message| {
+            *prompt_clone.borrow_mut() = message;
+            true
+        },
+    )
+    .unwrap();
+
+    assert_eq!(outcome, Outcome::Renamed);
+    assert!(!prompt.borrow().contains("Leaving the base path"));
+    assert!(!file.exists());
+    assert!(dir.path().join("subdir").join("renamed.txt").exists());
+    assert!(dir.path().join("subdir").join("file4.txt").exists());
+}
+
+#[test]
+fn test_editor_resolution_order() {
+    assert_eq!(
+        resolve_editor_name(false, &[("EDITOR", "vim"), ("BUMV_EDITOR", "nano")]),
+        "nano"
+    );
+    assert_eq!(
+        resolve_editor_name(false, &[("VISUAL", "emacs"), ("EDITOR", "vim")]),
+        "emacs"
+    );
+    assert_eq!(resolve_editor_name(false, &[("EDITOR", "vim")]), "vim");
+    // empty variables are skipped
+    assert_eq!(
+        resolve_editor_name(false, &[("BUMV_EDITOR", " "), ("EDITOR", "vim")]),
+        "vim"
+    );
+    // --use-vscode takes precedence over the environment
+    assert_ne!(
+        resolve_editor_name(true, &[("BUMV_EDITOR", "nano")]),
+        "nano"
+    );
+}
+
+#[test]
+fn test_find_executable() {
+    let dir = tempdir().unwrap();
+    let editor = dir.path().join("my-editor");
+    File::create(&editor).unwrap();
+    let path_variable = Some(dir.path().as_os_str().to_owned());
+
+    assert_eq!(
+        find_executable("my-editor", path_variable.clone()),
+        Some(editor.clone())
+    );
+    assert_eq!(find_executable("missing-editor", path_variable), None);
+    assert_eq!(find_executable("my-editor", None), None);
+    // names with a path separator are not looked up in PATH
+    assert_eq!(
+        find_executable(editor.to_str().unwrap(), None),
+        Some(editor)
+    );
+}
+
+#[test]
+fn test_editor_command_with_arguments() {
+    let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        parse_editor_command("subl -w", &[]),
+        ("subl".to_string(), args(&["-w"]))
+    );
+    assert_eq!(
+        parse_editor_command(r#""C:\Program Files\Sublime Text\subl.exe" -w"#, &[]),
+        (
+            r"C:\Program Files\Sublime Text\subl.exe".to_string(),
+            args(&["-w"])
+        )
+    );
+    // unquoted paths with spaces are recognized if the executable exists
+    let subl = Path::new(r"C:\Program Files\Sublime Text\subl.exe");
+    assert_eq!(
+        parse_editor_command(r"C:\Program Files\Sublime Text\subl.exe -w", &[subl]),
+        (subl.to_str().unwrap().to_string(), args(&["-w"]))
+    );
+    assert_eq!(
+        parse_editor_command("emacsclient -c -a 'emacs -nw'", &[]),
+        ("emacsclient".to_string(), args(&["-c", "-a", "emacs -nw"]))
+    );
+}
+
+#[test]
+fn test_parse_age() {
+    assert_eq!(
+        parse_age("30d").unwrap(),
+        Duration::from_secs(30 * 24 * 60 * 60)
+    );
+    assert_eq!(
+        parse_age("2w").unwrap(),
+        Duration::from_secs(14 * 24 * 60 * 60)
+    );
+    assert_eq!(parse_age("90s").unwrap(), Duration::from_secs(90));
+    assert!(parse_age("30").is_err());
+    assert!(parse_age("d").is_err());
+    assert!(parse_age("30y").is_err());
+}
+
+/// Verify that only log files are removed, respecting their age and the recursive flag
+#[test]
+fn test_clean_logs() {
+    assert!(is_log_file_name("bumv_20240101_120000.log"));
+    assert!(is_log_file_name("bumv_20240101_120000-3fa2.log"));
+    assert!(!is_log_file_name("bumv_20240101_120000-3fa2_failure.json"));
+    assert!(!is_log_file_name("bumv_notes.log"));
+    assert!(!is_log_file_name("bumv_20240101_120000.log.bak"));
+
+    let dir = tempdir().unwrap();
+    let now = SystemTime::now();
+    let old_log = dir.path().join("bumv_20240101_120000.log");
+    let new_log = dir.path().join("bumv_20240301_120000.log");
+    let nested_log = dir.path().join("subdir").join("bumv_20240101_120000.log");
+    let other = dir.path().join("bumv_notes.log");
+    fs::create_dir(dir.path().join("subdir")).unwrap();
+    for (path, age) in [
+        (&old_log, 40),
+        (&new_log, 10),
+        (&nested_log, 40),
+        (&other, 40),
+    ] {
+        File::create(path)
+            .unwrap()
+            .set_modified(now - Duration::from_secs(age * 24 * 60 * 60))
+            .unwrap();
+    }
+
+    let removed = clean_logs(dir.path(), false, Some(parse_age("30d").unwrap()), now).unwrap();
+    assert_eq!(removed, vec![old_log.clone()]);
+    assert!(new_log.exists() && nested_log.exists() && other.exists());
+
+    let removed = clean_logs(dir.path(), true, None, now).unwrap();
+    assert_eq!(removed, vec![new_log, nested_log]);
+    assert!(other.exists());
+}
+
+/// Verify that batches do not end while a file has a temporary name
+#[test]
+fn test_step_batches() {
+    let step = |old: &str, new: &str| (PathBuf::from(old), PathBuf::from(new));
+    let steps = vec![
+        step("a", "a.n0.tmp"),
+        step("b", "a"),
+        step("a.n0.tmp", "b"),
+        step("c", "d"),
+        step("e", "f"),
+    ];
+    assert_eq!(step_batches(&steps, None), vec![&steps[..]]);
+    assert_eq!(
+        step_batches(&steps, Some(1)),
+        vec![&steps[..3], &steps[3..4], &steps[4..]]
+    );
+    assert_eq!(
+        step_batches(&steps, Some(4)),
+        vec![&steps[..4], &steps[4..]]
+    );
+}
+
+/// Verify that declining a later batch stops renaming and logs only the completed renamings
+#[test]
+fn scenario_test_confirm_every() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        recursive: true,
+        no_ignore: true,
+        base_path: Some(dir.path().to_path_buf()),
+        confirm_every: Some(2),
+        ..Default::default()
+    };
+    let prompts = Rc::new(RefCell::new(Vec::new()));
+    let prompts_clone = prompts.clone();
+
+    let outcome = bulk_rename(
+        config,
+        |content| Ok(content.replace(".txt", ".md")),
+        move |prompt| {
+            prompts_clone.borrow_mut().push(prompt);
+            // confirm the plan and the second batch, decline the third
+            prompts_clone.borrow().len() < 3
+        },
+    )
+    .unwrap();
+
+    assert_eq!(outcome, Outcome::PartiallyRenamed);
+    assert_eq!(outcome.exit_code(), 2);
+    let prompts = prompts.borrow();
+    assert_eq!(prompts.len(), 3);
+    assert!(prompts[2].starts_with("Renamed 4 of 5 steps. Next batch:"));
+    // files are renamed in sorted order
+    for name in [
+        "file1.md",
+        "file2.md",
+        "ignored.md",
+        "subdir/file3.md",
+        "subdir/file4.txt",
+    ] {
+        assert!(dir.path().join(name).exists());
+    }
+    let log_file = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.to_string_lossy().contains("bumv_"))
+        .unwrap();
+    let log = fs::read_to_string(log_file).unwrap();
+    let (steps, mapping): (Vec<&str>, Vec<&str>) = log
+        .lines()
+        .skip(1)
+        .filter(|line| !line.starts_with("# state "))
+        .partition(|line| line.starts_with("# step"));
+    assert_eq!(mapping.len(), 4);
+    assert_eq!(steps.len(), 4);
+    assert!(!log.contains("file4"));
+}
+
+/// Verify that only the renamings in confirmed directories are executed
+#[test]
+fn scenario_test_confirm_per_dir() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        recursive: true,
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        confirm_per_dir: true,
+        ..Default::default()
+    };
+
+    let outcome = bulk_rename(
+        config.clone(),
+        |content| Ok(content.replace(".txt", ".md")),
+        |prompt: String| {
+            println!("prompt:\n{}", prompt);
+            // decline the subdirectory
+            !prompt.contains("/subdir:")
+        },
+    )
+    .unwrap();
+
+    assert_eq!(outcome, Outcome::PartiallyRenamed);
+    for name in [
+        "file1.md",
+        "file2.md",
+        "subdir/file3.txt",
+        "subdir/file4.txt",
+    ] {
+        assert!(dir.path().join(name).exists());
+    }
+
+    // confirming all directories renames everything
+    let outcome = bulk_rename(
+        config.clone(),
+        |content| Ok(content.replace(".txt", ".md")),
+        prompt_function,
+    )
+    .unwrap();
+    assert_eq!(outcome, Outcome::Renamed);
+    assert!(dir.path().join("subdir/file3.md").exists());
+
+    // a confirmed renaming must not depend on a declined one
+    let err = bulk_rename(
+        config,
+        |content| {
+            Ok(content
+                .replace("file1.md", "renamed.md")
+                .replace("subdir/file3.md", "file1.md"))
+        },
+        |prompt: String| prompt.contains("/subdir:"),
+    )
+    .unwrap_err();
+    assert!(
+        format!("{:#}", err).starts_with("The confirmed renamings conflict with the declined ones")
+    );
+    assert!(dir.path().join("file1.md").exists());
+    assert!(dir.path().join("subdir/file3.md").exists());
+}
+
+#[test]
+fn test_renumber_template() {
+    let template: RenumberTemplate = "track_{n:03}_final".parse().unwrap();
+    assert_eq!(template.file_stem(7), "track_007_final");
+    assert_eq!(template.file_stem(1234), "track_1234_final");
+    let template: RenumberTemplate = "{n}".parse().unwrap();
+    assert_eq!(template.file_stem(7), "7");
+    assert!("track".parse::<RenumberTemplate>().is_err());
+    assert!("track_{n:3}".parse::<RenumberTemplate>().is_err());
+    assert!("track_{n".parse::<RenumberTemplate>().is_err());
+    assert!("dir/{n}".parse::<RenumberTemplate>().is_err());
+    assert!("{n}_{n}".parse::<RenumberTemplate>().is_err());
+}
+
+/// Verify that files are numbered in the order of the reordered lines
+#[test]
+fn scenario_test_renumber() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        renumber: Some("track_{n:02}".parse().unwrap()),
+        ..Default::default()
+    };
+
+    // editing names is not allowed
+    let err = bulk_rename(
+        config.clone(),
+        |content| Ok(content.replace("file1", "renamed")),
+        prompt_function,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("only reorder the lines"));
+    assert_no_filenames_changed(&dir);
+
+    bulk_rename(
+        config,
+        |content| {
+            let mut lines: Vec<_> = content.lines().collect();
+            lines.reverse();
+            Ok(lines.join("\n"))
+        },
+        prompt_function,
+    )
+    .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("track_01.txt")).unwrap(),
+        "file2_content"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("track_02.txt")).unwrap(),
+        "file1_content"
+    );
+}
+
+/// Verify that moving much data out of the base path is confirmed separately
+#[test]
+fn scenario_test_confirm_leaving_base_path() {
+    let dir = tempdir().unwrap();
+    let base = dir.path().join("base");
+    let destination = dir.path().join("destination");
+    fs::create_dir_all(&base).unwrap();
+    fs::create_dir_all(&destination).unwrap();
+    // a sparse file, which is large without taking up space
+    File::create(base.join("large.bin"))
+        .unwrap()
+        .set_len(1 << 30)
+        .unwrap();
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(base.clone()),
+        ..Default::default()
+    };
+    let prompts = Rc::new(RefCell::new(Vec::new()));
+    let prompts_clone = prompts.clone();
+
+    let outcome = bulk_rename(
+        config,
+        |content| Ok(content.replace("base/large.bin", "destination/large.bin")),
+        move |prompt| {
+            prompts_clone.borrow_mut().push(prompt);
+            // confirm the renaming, but not the move out of the base path
+            prompts_clone.borrow().len() == 1
+        },
+    )
+    .unwrap();
+
+    assert_eq!(outcome, Outcome::Aborted);
+    let prompts = prompts.borrow();
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[0].contains("Leaving the base path:\n  1 file, 1.0 GiB in total"));
+    assert!(prompts[1].contains(&destination.to_string_lossy().to_string()));
+    assert!(base.join("large.bin").exists());
+}
+
+/// Verify that with --from-root and --to-root, the files are listed relative to the source
+/// tree, moved to the same names in the target tree and cannot leave it
+#[test]
+fn scenario_test_to_root() {
+    let dir = tempdir().unwrap();
+    let from_root = dir.path().join("old");
+    let to_root = dir.path().join("new");
+    fs::create_dir_all(from_root.join("sub")).unwrap();
+    File::create(from_root.join("file1.txt")).unwrap();
+    File::create(from_root.join("sub/file2.txt")).unwrap();
+    let config = BumvConfiguration {
+        no_log: true,
+        recursive: true,
+        from_root: Some(from_root.clone()),
+        to_root: Some(to_root.clone()),
+        ..Default::default()
+    };
+
+    let error = bulk_rename(
+        config.clone(),
+        |content| Ok(content.replace("sub/file2.txt", "../file2.txt")),
+        |_| true,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("leaves the target root"));
+    assert!(from_root.join("sub/file2.txt").exists());
+
+    let outcome = bulk_rename(
+        config,
+        |content| {
+            assert_eq!(content, "file1.txt\nsub/file2.txt");
+            Ok(content.replace("file1.txt", "renamed/file1.txt"))
+        },
+        |_| true,
+    )
+    .unwrap();
+
+    assert_eq!(outcome, Outcome::Renamed);
+    assert!(to_root.join("renamed/file1.txt").exists());
+    assert!(to_root.join("sub/file2.txt").exists());
+    assert!(!from_root.join("file1.txt").exists());
+}
+
+/// Verify that with --loop, new sessions over the renamed files are started until a session
+/// does not rename any files
+#[test]
+fn scenario_test_loop() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: true,
+        repeat: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let mut outcomes = Vec::new();
+
+    let outcome = bulk_rename_in_loop(
+        config,
+        |_| {
+            Ok(|content: String| {
+                Ok(content
+                    .replace("renamed.txt", "final.txt")
+                    .replace("file1.txt", "renamed.txt"))
+            })
+        },
+        prompt_function,
+        |_, result, _| outcomes.push(result.as_ref().ok().copied()),
+    )
+    .unwrap();
+
+    // the second session renames the file renamed in the first one, the third one nothing
+    assert_eq!(outcome, Outcome::NothingToRename);
+    assert_eq!(
+        outcomes,
+        vec![
+            Some(Outcome::Renamed),
+            Some(Outcome::Renamed),
+            Some(Outcome::NothingToRename)
+        ]
+    );
+    assert!(!dir.path().join("file1.txt").exists());
+    assert!(!dir.path().join("renamed.txt").exists());
+    assert!(dir.path().join("final.txt").exists());
+}
+
+/// Verify that a report of the completed, failed and pending steps is written if renaming fails
+#[test]
+fn scenario_test_failure_report() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let path = dir.path().to_path_buf();
+
+    let err = bulk_rename(
+        config,
+        |content| {
+            Ok(content
+                .replace("file1.txt", "renamed.txt")
+                .replace("file2.txt", "also_ignored.txt"))
+        },
+        move |_| {
+            // a file created after confirmation makes one of the steps fail
+            File::create(path.join("also_ignored.txt")).unwrap();
+            true
+        },
+    )
+    .unwrap_err();
+
+    assert!(err.to_string().contains("also_ignored.txt already exists"));
+    let report_path = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.to_string_lossy().ends_with("_failure.json"))
+        .unwrap();
+    let report = fs::read_to_string(report_path).unwrap();
+    let file2 = JsonValue::from(dir.path().join("file2.txt").to_str().unwrap()).to_string();
+    assert!(report.contains(&format!("\"failed\":{{\"from\":{},", file2)));
+    assert!(report.contains("already exists"));
+    // the other step was either completed or is pending
+    let file1 = JsonValue::from(dir.path().join("file1.txt").to_str().unwrap()).to_string();
+    let completed = report.contains(&format!("\"completed\":[{{\"from\":{},", file1));
+    let pending = report.contains(&format!("\"pending\":[{{\"from\":{},", file1));
+    assert!(completed != pending);
+    assert_eq!(completed, dir.path().join("renamed.txt").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_buffer_file() {
+    let dir = tempdir().unwrap();
+    let buffer_file = dir.path().join("buffer.txt");
+    // the editor appends a line to the buffer file, which is passed as the last argument
+    let edited = edit_in_buffer_file(
+        "sh -c 'echo ./renamed.txt >> \"$0\"'",
+        &buffer_file,
+        "./file1.txt\n",
+    )
+    .unwrap();
+    assert_eq!(edited, "./file1.txt\n./renamed.txt\n");
+    assert!(!buffer_file.exists());
+
+    // an existing file is neither overwritten nor removed
+    fs::write(&buffer_file, "notes").unwrap();
+    assert!(edit_in_buffer_file("true", &buffer_file, "./file1.txt\n").is_err());
+    assert_eq!(fs::read_to_string(&buffer_file).unwrap(), "notes");
+}
+
+#[test]
+fn test_remote_editor() {
+    let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        resolve_remote_editor(&[("NVIM", "/tmp/nvim.sock"), ("INSIDE_EMACS", "vterm")]).unwrap(),
+        (
+            "nvr".to_string(),
+            args(&["--servername", "/tmp/nvim.sock", "--remote-wait"])
+        )
+    );
+    assert_eq!(
+        resolve_remote_editor(&[("INSIDE_EMACS", "vterm")]).unwrap(),
+        ("emacsclient".to_string(), args(&[]))
+    );
+    // a new Kakoune client would not open the file list in the running instance
+    assert!(resolve_remote_editor(&[("KAKOUNE_SESSION", "1234")]).is_err());
+    assert!(resolve_remote_editor(&[("EDITOR", "vim")]).is_err());
+}
+
+/// Verify that editors wrapping long lines are detected, and that long lines are warned about
+#[test]
+fn test_line_width() {
+    assert_eq!(editor_wrapping_width("vim", 80), None);
+    assert_eq!(editor_wrapping_width("nano", 80), None);
+    assert_eq!(editor_wrapping_width("nano -b", 80), Some(72));
+    assert_eq!(
+        editor_wrapping_width("nano --breaklonglines -r 60", 80),
+        Some(60)
+    );
+    assert_eq!(editor_wrapping_width("nano -b --fill=-20", 80), Some(60));
+    assert_eq!(editor_wrapping_width("nano -b -w", 80), None);
+    assert_eq!(editor_wrapping_width("/usr/bin/pico", 100), Some(92));
+
+    assert_eq!(LineWidth::new(None, None), None);
+    let line_width = LineWidth::new(Some(10), Some(72)).unwrap();
+    assert_eq!(line_width.long_lines_warning("short.txt\nshort2.txt"), None);
+    let warning = line_width
+        .long_lines_warning("short.txt\ndirectory/long.txt")
+        .unwrap();
+    assert!(warning.starts_with("Warning: 1 line is longer than 10 characters."));
+    assert!(!warning.contains("Your editor wraps them"));
+    let warning = LineWidth::new(Some(80), Some(10))
+        .unwrap()
+        .long_lines_warning("directory/long.txt")
+        .unwrap();
+    assert!(warning.contains("Your editor wraps them"));
+}
+
+#[test]
+fn test_validate_edited_content() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let file = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+    let original = [file("file1.txt"), file("file2.txt")].join("\n");
+
+    assert!(validate_edited_content(&original, &original, false, Overwrite::Abort).is_empty());
+    assert_eq!(
+        validate_edited_content(&original, &file("file1.txt"), false, Overwrite::Abort),
+        vec!["The file list contains 1 files, but 2 files are expected."]
+    );
+    assert_eq!(
+        validate_edited_content(
+            &original,
+            &[file("a.txt"), file("a.txt")].join("\n"),
+            false,
+            Overwrite::Abort
+        ),
+        vec![format!(
+            "Line 2: {} is also the target in line 1.",
+            file("a.txt")
+        )]
+    );
+    assert_eq!(
+        validate_edited_content(
+            &original,
+            &[file("ignored.txt"), file("file1.txt")].join("\n"),
+            false,
+            Overwrite::Abort
+        ),
+        vec![format!("Line 1: {} already exists.", file("ignored.txt"))]
+    );
+    // existing targets are moved to the trash with --overwrite trash
+    assert!(validate_edited_content(
+        &original,
+        &[file("ignored.txt"), file("file1.txt")].join("\n"),
+        false,
+        Overwrite::Trash
+    )
+    .is_empty());
+}
+
+/// Verify that each nested repository uses its own ignore rules, and that nested repositories
+/// are skipped with --no-cross-repo
+#[test]
+fn test_nested_repositories() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    fs::create_dir_all(root.join(".git")).unwrap();
+    fs::create_dir_all(root.join("nested").join(".git")).unwrap();
+    fs::create_dir_all(root.join("submodule")).unwrap();
+    // submodules have a .git file instead of a directory
+    fs::write(
+        root.join("submodule").join(".git"),
+        "gitdir: ../.git/modules/submodule",
+    )
+    .unwrap();
+    fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+    fs::write(root.join("nested").join(".gitignore"), "*.tmp\n").unwrap();
+    for file in [
+        "a.log",
+        "a.tmp",
+        "nested/b.log",
+        "nested/b.tmp",
+        "submodule/c.txt",
+    ] {
+        File::create(root.join(file)).unwrap();
+    }
+    let mut config = BumvConfiguration {
+        recursive: true,
+        base_path: Some(root.to_path_buf()),
+        ..Default::default()
+    };
+    assert_eq!(
+        config.file_list().unwrap(),
+        vec![
+            root.join("a.tmp"),
+            root.join("nested").join("b.log"),
+            root.join("submodule").join("c.txt"),
+        ]
+    );
+
+    config.no_cross_repo = true;
+    assert_eq!(config.file_list().unwrap(), vec![root.join("a.tmp")]);
+}
+
+/// Verify that the log contains the performed steps, marking steps via temporary files as internal
+#[test]
+fn scenario_test_log_contains_steps() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+
+    bulk_rename(
+        config,
+        |content| {
+            Ok(content
+                .replace("file1.txt", "swap")
+                .replace("file2.txt", "file1.txt")
+                .replace("swap", "file2.txt"))
+        },
+        prompt_function,
+    )
+    .unwrap();
+
+    let log_file = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.to_string_lossy().contains("bumv_"))
+        .unwrap();
+    let log = fs::read_to_string(log_file).unwrap();
+    let lines: Vec<&str> = log.lines().skip(1).collect();
+    assert_eq!(lines.len(), 7);
+    assert!(!lines[0].starts_with('#') && !lines[1].starts_with('#'));
+    assert!(lines[2].starts_with("# state ") && lines[3].starts_with("# state "));
+    let steps = &lines[4..];
+    assert!(steps[0].starts_with("# step 1: ") && steps[0].ends_with(".tmp (internal)"));
+    assert!(steps[1].starts_with("# step 2: ") && !steps[1].ends_with("(internal)"));
+    assert!(steps[2].starts_with("# step 3: ") && steps[2].contains(".tmp -> "));
+    assert!(steps[2].ends_with(" (internal)"));
+}
+
+/// Verify detection of targets on another device, using /proc as a different filesystem
+#[cfg(target_os = "linux")]
+#[test]
+fn test_same_device() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let file1 = dir.path().join("file1.txt");
+    assert!(same_device(
+        &file1,
+        &dir.path().join("subdir").join("new").join("file.txt")
+    )
+    .unwrap());
+    assert!(!same_device(&file1, Path::new("/proc/file1.txt")).unwrap());
+
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        no_rename_across_devices: true,
+        ..Default::default()
+    };
+    let err = bulk_rename(
+        config,
+        |content| {
+            Ok(content.replace(
+                dir.path().join("file1.txt").to_str().unwrap(),
+                "/proc/file1.txt",
+            ))
+        },
+        prompt_function,
+    )
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .ends_with("would copy it to another device."));
+    assert_no_filenames_changed(&dir);
+}
+
+#[test]
+fn test_session_id() {
+    let session = SessionId::new();
+    assert!(SessionId::is_valid(session.as_str()));
+    assert_eq!(session.as_str().len(), "20240101_120000-3fa2".len());
+    assert!(!SessionId::is_valid("20240101_120000"));
+    assert!(!SessionId::is_valid("20240101_120000-xyz1"));
+}
+
+/// Verify that the log file is named after the session
+#[test]
+fn scenario_test_log_file_contains_session_id() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let session = config.session.clone();
+
+    bulk_rename(
+        config,
+        |content| Ok(content.replace("file1.txt", "renamed.txt")),
+        prompt_function,
+    )
+    .unwrap();
+
+    assert!(dir.path().join(format!("bumv_{}.log", session)).exists());
+}
+
+/// Verify that the file list is presented in the order of the sort command
+#[cfg(unix)]
+#[test]
+fn scenario_test_sort_cmd() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let mut config = BumvConfiguration {
+        recursive: true,
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        sort_cmd: Some("sort -r".to_string()),
+        ..Default::default()
+    };
+
+    bulk_rename(
+        config.clone(),
+        |content| {
+            let names: Vec<_> = content
+                .lines()
+                .map(|line| Path::new(line).file_name().unwrap().to_str().unwrap())
+                .collect();
+            assert_eq!(names, ["file4.txt", "file3.txt", "file2.txt", "file1.txt"]);
+            Ok(content)
+        },
+        prompt_function,
+    )
+    .unwrap();
+
+    // the command must neither drop nor invent files
+    config.sort_cmd = Some("head -n 1".to_string());
+    let err = config.editable_file_list().unwrap_err();
+    assert!(err
+        .to_string()
+        .ends_with("must output each listed file exactly once."));
+    config.sort_cmd = Some("false".to_string());
+    assert!(config.editable_file_list().is_err());
+}
+
+/// Verify that the prompt shows the renamings in the order of the file list, followed by the
+/// execution order with `verbose` if it differs
+#[cfg(unix)]
+#[test]
+fn scenario_test_preview_in_listing_order() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        sort_cmd: Some("sort -r".to_string()),
+        ..Default::default()
+    };
+    let file1 = dir.path().join("file1.txt").to_string_lossy().to_string();
+    let file2 = dir.path().join("file2.txt").to_string_lossy().to_string();
+    let swap = |content: String| {
+        Ok(content
+            .replace("file1.txt", "swap")
+            .replace("file2.txt", "file1.txt")
+            .replace("swap", "file2.txt"))
+    };
+    let mut prompt = String::new();
+    bulk_rename(config.clone(), swap, |text| {
+        prompt = text;
+        false
+    })
+    .unwrap();
+    assert_eq!(
+        prompt,
+        format!("{f2} -> {f1}\n{f1} -> {f2}", f1 = file1, f2 = file2)
+    );
+
+    let config = BumvConfiguration {
+        verbose: true,
+        ..config
+    };
+    bulk_rename(config, swap, |text| {
+        prompt = text;
+        false
+    })
+    .unwrap();
+    assert_eq!(
+        prompt,
+        format!(
+            "{f2} -> {f1}\n{f1} -> {f2}\n\nExecution order:\n  1. {f1} -> {f1}.n0.tmp\n  \
+             2. {f2} -> {f1}\n  3. {f1}.n0.tmp -> {f2}",
+            f1 = file1,
+            f2 = file2
+        )
+    );
+}
+
+/// Verify that log files and failure reports are not listed unless requested
+#[test]
+fn test_bumv_files_are_excluded() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let log_file = dir.path().join("bumv_20240101_120000-3fa2.log");
+    let old_log_file = dir.path().join("bumv_20240101_120000.log");
+    let report = dir.path().join("bumv_20240101_120000-3fa2_failure.json");
+    for path in [&log_file, &old_log_file, &report] {
+        File::create(path).unwrap();
+    }
+    let mut config = BumvConfiguration {
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    assert_eq!(
+        config.file_list().unwrap(),
+        vec![dir.path().join("file1.txt"), dir.path().join("file2.txt")]
+    );
+
+    config.include_bumv_files = true;
+    assert_eq!(
+        config.file_list().unwrap(),
+        vec![
+            log_file,
+            report,
+            old_log_file,
+            dir.path().join("file1.txt"),
+            dir.path().join("file2.txt"),
+        ]
+    );
+}
+
+/// Verify that folders synced by Dropbox and git repositories with a running file system monitor
+/// are detected, and that the closest watched folder is reported
+#[test]
+fn test_watched_folder() {
+    let dir = tempdir().unwrap();
+    let dropbox = dir.path().canonicalize().unwrap().join("Dropbox");
+    let repository = dropbox.join("project");
+    fs::create_dir_all(repository.join(".git")).unwrap();
+    fs::create_dir_all(repository.join("src")).unwrap();
+    assert_eq!(watched_folder(&repository.join("src")), None);
+
+    File::create(dropbox.join(".dropbox")).unwrap();
+    let folder = watched_folder(&repository.join("src")).unwrap();
+    assert_eq!(
+        (folder.service, folder.root.as_path()),
+        ("Dropbox", dropbox.as_path())
+    );
+
+    File::create(repository.join(".git/fsmonitor--daemon.ipc")).unwrap();
+    let folder = watched_folder(&repository.join("src")).unwrap();
+    assert_eq!(folder.root, repository);
+    assert!(folder.to_string().contains("git fsmonitor--daemon stop"));
+}
+
+/// Verify that with --abbreviate, the directory shared by all files is abbreviated in the file
+/// list and expanded again, while other names are taken literally
+#[test]
+fn scenario_test_abbreviate() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: true,
+        abbreviate: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let outside = tempdir().unwrap();
+    let moved = outside.path().join("moved.txt");
+
+    let outcome = bulk_rename(
+        config,
+        |content| {
+            assert_eq!(content, "§/file1.txt\n§/file2.txt");
+            Ok(format!("§/renamed.txt\n{}", moved.to_string_lossy()))
+        },
+        |_| true,
+    )
+    .unwrap();
+
+    assert_eq!(outcome, Outcome::Renamed);
+    assert!(dir.path().join("renamed.txt").exists());
+    assert!(moved.exists());
+}
+
+/// Verify that substitutions only apply to the file names of matching files, in the given order
+#[test]
+fn test_substitution_proposal() {
+    let files: Vec<PathBuf> = ["base/a.test.js", "base/lib/b.test.js", "base/c.test.ts"]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    let content = create_editable_temp_file_content(&files);
+    let substitutions = [
+        Substitution::try_new("*.test.js", r"s/\.test\././").unwrap(),
+        Substitution::try_new("lib/*", "s|(b)|${1}_$1|").unwrap(),
+    ];
+    assert_eq!(
+        substitution_proposal(&content, &files, Path::new("base"), &substitutions),
+        "base/a.js\nbase/lib/b_b.js\nbase/c.test.ts"
+    );
+
+    assert_eq!(
+        Substitution::try_new("*", r"s/\//-/g")
+            .unwrap()
+            .apply("a/b/c"),
+        "a-b-c"
+    );
+    assert_eq!(
+        Substitution::try_new("*", "s/A/b/i").unwrap().apply("aa"),
+        "ba"
+    );
+    assert!(Substitution::try_new("*", "s/a/b").is_err());
+    assert!(Substitution::try_new("*", "s/a/b/x").is_err());
+    assert!(Substitution::try_new("*", "s/(/b/").is_err());
+}
+
+/// Verify that with --include-empty-dirs, empty directories are listed with a trailing /,
+/// can be renamed and receive files, and are removed with --allow-delete
+#[test]
+fn scenario_test_include_empty_dirs() {
+    let dir = tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+    File::create(dir.path().join("file1.txt")).unwrap();
+    for name in ["gone", "keep", "old_empty"] {
+        fs::create_dir(dir.path().join(name)).unwrap();
+    }
+    let config = BumvConfiguration {
+        no_log: true,
+        include_empty_dirs: true,
+        allow_delete: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+
+    let outcome = bulk_rename(
+        config,
+        |content| {
+            assert_eq!(
+                content,
+                format!(
+                    "{}\n{}/\n{}/\n{}/",
+                    path("file1.txt"),
+                    path("gone"),
+                    path("keep"),
+                    path("old_empty")
+                )
+            );
+            Ok(format!(
+                "{}\n#{}/\n{}/\n{}/",
+                path("keep/file1.txt"),
+                path("gone"),
+                path("keep"),
+                path("new_empty")
+            ))
+        },
+        |_| true,
+    )
+    .unwrap();
+
+    assert_eq!(outcome, Outcome::Renamed);
+    assert!(dir.path().join("keep/file1.txt").is_file());
+    assert!(dir.path().join("new_empty").is_dir());
+    assert!(!dir.path().join("old_empty").exists());
+    assert!(!dir.path().join("gone").exists());
+}
+
+/// Verify that with --allow-delete, a directory listed with a leading # is only removed if its
+/// line is prefixed once more
+#[test]
+fn scenario_test_allow_delete_listed_with_prefix() {
+    // a relative base path starting with #, so that the listed names start with it
+    let dir = tempfile::Builder::new()
+        .prefix("#bumv-test")
+        .tempdir_in(".")
+        .unwrap();
+    let base = PathBuf::from(dir.path().file_name().unwrap());
+    for name in ["kept", "gone"] {
+        fs::create_dir(dir.path().join(name)).unwrap();
+    }
+    let config = BumvConfiguration {
+        no_log: true,
+        include_empty_dirs: true,
+        allow_delete: true,
+        base_path: Some(base.clone()),
+        ..Default::default()
+    };
+    let gone = format!("{}/", base.join("gone").to_string_lossy());
+    let outcome = bulk_rename(
+        config,
+        |content| {
+            assert!(content.starts_with('#'));
+            Ok(content.replace(&gone, &format!("#{}", gone)))
+        },
+        |_| true,
+    )
+    .unwrap();
+    assert_eq!(outcome, Outcome::Renamed);
+    assert!(dir.path().join("kept").is_dir());
+    assert!(!dir.path().join("gone").exists());
+}
+
+/// Verify that the performed steps are appended to the batch file as NUL separated records
+#[test]
+fn scenario_test_emit_batch() {
+    let dir = tempdir().unwrap();
+    let batch_dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let batch_file = batch_dir.path().join("renames.batch");
+    let config = BumvConfiguration {
+        no_log: true,
+        emit_batch: Some(batch_file.clone()),
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+
+    for (old, new) in [("file1.txt", "renamed.txt"), ("renamed.txt", "again.txt")] {
+        let outcome = bulk_rename(
+            config.clone(),
+            |content| Ok(content.replace(old, new)),
+            |_| true,
+        )
+        .unwrap();
+        assert_eq!(outcome, Outcome::Renamed);
+    }
+
+    let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+    assert_eq!(
+        fs::read_to_string(&batch_file).unwrap(),
+        format!(
+            "{}\0{}\0{}\0{}\0",
+            path("file1.txt"),
+            path("renamed.txt"),
+            path("renamed.txt"),
+            path("again.txt")
+        )
+    );
+}
+
+/// Verify that existing targets outside of the listing are detected before confirmation
+#[test]
+fn scenario_test_existing_target_detected_before_confirmation() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+
+    let err = bulk_rename(
+        config,
+        |content| {
+            Ok(content
+                .replace("file1.txt", "renamed.txt")
+                .replace("file2.txt", "subdir/file3.txt"))
+        },
+        |_| panic!("the user must not be asked to confirm"),
+    )
+    .unwrap_err();
+
+    assert!(err
+        .to_string()
+        .ends_with("subdir/file3.txt already exists."));
+    assert_no_filenames_changed(&dir);
+}
+
+/// Verify that files are compared byte by byte, beyond the first chunk
+#[test]
+fn test_same_content() {
+    let dir = tempdir().unwrap();
+    let content = vec![b'x'; 200 * 1024];
+    let mut changed = content.clone();
+    *changed.last_mut().unwrap() = b'y';
+    let (a, b, c) = (
+        dir.path().join("a"),
+        dir.path().join("b"),
+        dir.path().join("c"),
+    );
+    fs::write(&a, &content).unwrap();
+    fs::write(&b, &content).unwrap();
+    fs::write(&c, &changed).unwrap();
+    assert!(same_content(&a, &b).unwrap());
+    assert!(!same_content(&a, &c).unwrap());
+    assert!(!same_content(&a, dir.path()).unwrap());
+}
+
+/// Verify that with --dedupe-existing, files are removed if their target exists with identical
+/// content, while targets with different content are still refused
+#[test]
+fn scenario_test_dedupe_existing() {
+    let dir = tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("done")).unwrap();
+    fs::write(dir.path().join("a.txt"), "a").unwrap();
+    fs::write(dir.path().join("b.txt"), "b").unwrap();
+    fs::write(dir.path().join("done/a.txt"), "a").unwrap();
+    fs::write(dir.path().join("done/b.txt"), "changed").unwrap();
+    let config = BumvConfiguration {
+        no_log: true,
+        dedupe_existing: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+
+    let err = bulk_rename(
+        config.clone(),
+        |content| {
+            Ok(content
+                .replace("a.txt", "done/a.txt")
+                .replace("b.txt", "done/b.txt"))
+        },
+        |_| panic!("the user must not be asked to confirm"),
+    )
+    .unwrap_err();
+    assert!(err.to_string().ends_with("done/b.txt already exists."));
+
+    let prompts = Rc::new(RefCell::new(Vec::new()));
+    let prompts_clone = prompts.clone();
+    let outcome = bulk_rename(
+        config,
+        |content| {
+            Ok(content
+                .replace("a.txt", "done/a.txt")
+                .replace("b.txt", "c.txt"))
+        },
+        move |prompt| {
+            prompts_clone.borrow_mut().push(prompt);
+            true
+        },
+    )
+    .unwrap();
+
+    assert_eq!(outcome, Outcome::Renamed);
+    assert!(prompts.borrow()[0].contains(&format!(
+        "Remove {}, identical to {}",
+        dir.path().join("a.txt").to_string_lossy(),
+        dir.path().join("done/a.txt").to_string_lossy()
+    )));
+    assert!(!dir.path().join("a.txt").exists());
+    assert_eq!(
+        fs::read_to_string(dir.path().join("done/a.txt")).unwrap(),
+        "a"
+    );
+    assert_eq!(fs::read_to_string(dir.path().join("c.txt")).unwrap(), "b");
+
+    // the contents are compared again before removing, and removals are written to the batch
+    fs::write(dir.path().join("x.txt"), "x").unwrap();
+    fs::write(dir.path().join("y.txt"), "y").unwrap();
+    fs::write(dir.path().join("done/x.txt"), "x").unwrap();
+    fs::write(dir.path().join("done/y.txt"), "y").unwrap();
+    let batch_dir = tempdir().unwrap();
+    let batch_file = batch_dir.path().join("renames.batch");
+    let config = BumvConfiguration {
+        no_log: true,
+        dedupe_existing: true,
+        emit_batch: Some(batch_file.clone()),
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    bulk_rename(
+        config,
+        |content| {
+            Ok(content
+                .replace("x.txt", "done/x.txt")
+                .replace("y.txt", "done/y.txt"))
+        },
+        |_| {
+            // the target changes after the plan was made
+            fs::write(dir.path().join("done/x.txt"), "changed").unwrap();
+            true
+        },
+    )
+    .unwrap();
+    assert_eq!(fs::read_to_string(dir.path().join("x.txt")).unwrap(), "x");
+    assert!(!dir.path().join("y.txt").exists());
+    assert_eq!(
+        fs::read_to_string(&batch_file).unwrap(),
+        format!("{}\0\0", dir.path().join("y.txt").to_string_lossy())
+    );
+}
+
+/// Verify that the tour can be completed, renaming and swapping the example files
+#[test]
+fn scenario_test_tour() {
+    let dir = tempdir().unwrap();
+    create_example_files(dir.path()).unwrap();
+    assert!(dir.path().join("IMG_0001.jpg").exists());
+    assert!(dir.path().join("first.txt").exists());
+
+    let listings = Rc::new(RefCell::new(Vec::new()));
+    let listings_clone = listings.clone();
+    run_tour(
+        move |content| {
+            listings_clone.borrow_mut().push(content.clone());
+            Ok(match listings_clone.borrow().len() {
+                1 => content.replace("IMG_", "holiday_"),
+                2 => content
+                    .replace("first.txt", "swap")
+                    .replace("second.txt", "first.txt")
+                    .replace("swap", "second.txt"),
+                _ => content.replace("holiday_", "IMG_"),
+            })
+        },
+        prompt_function,
+    )
+    .unwrap();
+
+    let listings = listings.borrow();
+    assert_eq!(listings.len(), 3);
+    assert!(listings[1].contains("holiday_0001.jpg"));
+    assert!(listings[2].contains("holiday_0001.jpg"));
+    // the temporary directory of the tour is removed
+    let tour_directory = Path::new(listings[0].lines().next().unwrap())
+        .parent()
+        .unwrap();
+    assert!(!tour_directory.exists());
+}
+
+#[test]
+fn test_table() {
+    let rows = |rows: &[(&str, &str)]| -> Vec<(String, String)> {
+        rows.iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect()
+    };
+    assert_eq!(
+        align_columns(&rows(&[("a.txt", "b.txt"), ("äöü.txt", "c.txt")]), "\t"),
+        vec!["a.txt  \tb.txt", "äöü.txt\tc.txt"]
+    );
+    assert_eq!(
+        table(("Old", "New"), &rows(&[("a.txt", "b.txt")]), 80),
+        "Old   | New\n----- | -----\na.txt | b.txt"
+    );
+    // the longer column is truncated at its start, the shorter one is kept
+    assert_eq!(
+        table(("Old", "New"), &rows(&[("a.txt", "dir/subdir/b.txt")]), 20),
+        "Old   | New\n----- | ------------\na.txt | …ubdir/b.txt"
+    );
+    // both columns are truncated to half of the width
+    assert_eq!(
+        table(("Old", "New"), &rows(&[("dir/a.txt", "dir/b.txt")]), 13),
+        "Old   | New\n----- | -----\n….txt | ….txt"
+    );
+}
+
+#[test]
+fn test_parse_mapping() {
+    let pair = |old: &str, new: &str| (PathBuf::from(old), PathBuf::from(new));
+    assert_eq!(
+        parse_mapping("Old,New\r\na.txt,b.txt\r\n\"c, d.txt\",\"say \"\"hi\"\".txt\"\n").unwrap(),
+        vec![pair("a.txt", "b.txt"), pair("c, d.txt", "say \"hi\".txt")]
+    );
+    // tab separated, without header
+    assert_eq!(
+        parse_mapping("a,1.txt\tb.txt\n\nc.txt\td.txt").unwrap(),
+        vec![pair("a,1.txt", "b.txt"), pair("c.txt", "d.txt")]
+    );
+    let err = parse_mapping("a.txt,b.txt\nc.txt").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Row 2 of the mapping file has 1 columns, expected 2 (old and new name)."
+    );
+    assert!(parse_mapping("\"a.txt,b.txt").is_err());
+
+    // in log files, only the padding is trimmed, and names like comments are quoted
+    let rows: Vec<(String, String)> = [("#notes.txt", "notes.txt"), ("a ", "b"), ("c", "d ")]
+        .iter()
+        .map(|(old, new)| (field(old).into_owned(), field(new).into_owned()))
+        .collect();
+    let log = std::iter::once(header())
+        .chain(std::iter::once("# removed directory e".to_string()))
+        .chain(align_columns(&rows, "\t"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert_eq!(
+        parse_mapping(&log).unwrap(),
+        vec![
+            pair("#notes.txt", "notes.txt"),
+            pair("a ", "b"),
+            pair("c", "d ")
+        ]
+    );
+}
+
+#[test]
+fn test_apply_mapping() {
+    let mapping = parse_mapping("a.txt,x.txt\n./sub/b.txt,sub/y.txt").unwrap();
+    assert_eq!(
+        apply_mapping("./a.txt\n./c.txt\n./sub/b.txt", &mapping).unwrap(),
+        "x.txt\n./c.txt\nsub/y.txt"
+    );
+    let err = apply_mapping("./a.txt", &mapping).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "The file ./sub/b.txt of the mapping file is not listed."
+    );
+    let mapping = parse_mapping("a.txt,x.txt\n./a.txt,y.txt").unwrap();
+    assert!(apply_mapping("./a.txt", &mapping).is_err());
+    // names of listed files are written as listed, so that swaps are recognized
+    let mapping = parse_mapping("a.txt,c.txt\nc.txt,a.txt").unwrap();
+    assert_eq!(
+        apply_mapping("./a.txt\n./c.txt", &mapping).unwrap(),
+        "./c.txt\n./a.txt"
+    );
+}
+
+/// Verify renaming by a mapping file
+#[test]
+fn scenario_test_mapping_file() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let mapping = parse_mapping(&format!(
+        "old,new\n{},{}",
+        dir.path().join("file1.txt").to_string_lossy(),
+        dir.path().join("renamed.txt").to_string_lossy()
+    ))
+    .unwrap();
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+
+    bulk_rename(
+        config,
+        |content| apply_mapping(&content, &mapping),
+        prompt_function,
+    )
+    .unwrap();
+
+    assert!(!dir.path().join("file1.txt").exists());
+    assert!(dir.path().join("renamed.txt").exists());
+    assert!(dir.path().join("file2.txt").exists());
+}
+
+#[test]
+fn test_expand_placeholders() {
+    use chrono::TimeZone;
+
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let file1 = dir.path().join("file1.txt");
+    let modified = chrono::Local
+        .with_ymd_and_hms(2024, 3, 1, 12, 30, 0)
+        .unwrap();
+    File::options()
+        .write(true)
+        .open(&file1)
+        .unwrap()
+        .set_modified(modified.into())
+        .unwrap();
+
+    assert_eq!(
+        expand_placeholders("dir/{stem}_{mtime}.{ext}", &file1).unwrap(),
+        "dir/file1_20240301.txt"
+    );
+    assert_eq!(
+        expand_placeholders("{mtime:%Y-%m-%d_%H%M} {name}", &file1).unwrap(),
+        "2024-03-01_1230 file1.txt"
+    );
+    assert_eq!(expand_placeholders("plain", &file1).unwrap(), "plain");
+    assert!(expand_placeholders("{size}", &file1).is_err());
+    assert!(expand_placeholders("{stem", &file1).is_err());
+    assert!(expand_placeholders("{mtime:%Q}", &file1).is_err());
+}
+
+/// Verify that placeholders are expanded per file, unless disabled
+#[test]
+fn scenario_test_expand_placeholders() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let edit = |content: String| {
+        Ok(content
+            .lines()
+            .map(|line| line.replace(".txt", "_{stem}.{ext}"))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    };
+
+    bulk_rename(config.clone(), edit, |_| true).unwrap();
+    assert!(dir.path().join("file1_file1.txt").exists());
+    assert!(dir.path().join("file2_file2.txt").exists());
+
+    let config = BumvConfiguration {
+        no_expand: true,
+        ..config
+    };
+    bulk_rename(config, edit, |_| true).unwrap();
+    assert!(dir.path().join("file1_file1_{stem}.{ext}").exists());
+}
+
+#[test]
+fn test_lint() {
+    let mapping = vec![
+        (PathBuf::from("dir/a.txt"), PathBuf::from("dir/a.md")),
+        (PathBuf::from("dir/b.txt"), PathBuf::from("dir/../b.txt")),
+        (PathBuf::from("dir/c.txt"), PathBuf::from("dir/.c.txt")),
+        (PathBuf::from("dir/d.txt"), PathBuf::from("dir/D.txt")),
+        (
+            PathBuf::from("dir/e.txt"),
+            PathBuf::from(format!("dir/{}.txt", "e".repeat(100))),
+        ),
+        (PathBuf::from("dir/f.txt"), PathBuf::from("dir/sub/./g.txt")),
+    ];
+    let findings = lint(&mapping, &[Path::new("dir")], &LintSeverities::default());
+    let rules: Vec<(&str, LintRule)> = findings
+        .iter()
+        .map(|finding| (finding.old.to_str().unwrap(), finding.rule))
+        .collect();
+    assert_eq!(
+        rules,
+        vec![
+            ("dir/a.txt", LintRule::ExtensionChange),
+            ("dir/b.txt", LintRule::OutsideBasePath),
+            ("dir/c.txt", LintRule::HiddenFile),
+            ("dir/d.txt", LintRule::CaseOnly),
+            ("dir/e.txt", LintRule::LongName),
+        ]
+    );
+    assert!(findings
+        .iter()
+        .all(|finding| finding.severity == Severity::Warn));
+    assert_eq!(
+        findings[0].to_string(),
+        "warning [extension-change]: dir/a.txt -> dir/a.md changes the file extension"
+    );
+
+    let config_file =
+        ConfigFile::parse("[lint]\nextension-change = \"ignore\"\ncase-only = \"error\"\n")
+            .unwrap();
+    let severities = LintSeverities::from_config_file(&config_file).unwrap();
+    let findings = lint(&mapping, &[Path::new("dir")], &severities);
+    assert!(!findings
+        .iter()
+        .any(|finding| finding.rule == LintRule::ExtensionChange));
+    assert_eq!(severities.get(LintRule::CaseOnly), Severity::Error);
+
+    assert!(LintSeverities::from_config_file(
+        &ConfigFile::parse("[lint]\nunknown-rule = \"warn\"").unwrap()
+    )
+    .is_err());
+    assert!(LintSeverities::from_config_file(
+        &ConfigFile::parse("[lint]\nlong-name = \"fatal\"").unwrap()
+    )
+    .is_err());
+}
+
+/// Verify that lint warnings are shown in the prompt and lint errors reject the plan
+#[test]
+fn scenario_test_lint() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let mut config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let edit = |content: String| Ok(content.replace("file1.txt", "file1.md"));
+
+    let mut prompt = String::new();
+    bulk_rename(config.clone(), edit, |message| {
+        prompt = message;
+        false
+    })
+    .unwrap();
+    assert!(prompt.ends_with(&format!(
+        "Lint:\n  warning [extension-change]: {} -> {} changes the file extension",
+        dir.path().join("file1.txt").to_string_lossy(),
+        dir.path().join("file1.md").to_string_lossy()
+    )));
+
+    config
+        .apply_config_file(&ConfigFile::parse("[lint]\nextension-change = \"error\"").unwrap())
+        .unwrap();
+    let error = bulk_rename(config, edit, |_| true).unwrap_err();
+    assert!(error.to_string().contains("error [extension-change]"));
+    assert!(dir.path().join("file1.txt").exists());
+}
+
+#[test]
+fn test_sweeping_base_path() {
+    let home = tempdir().unwrap();
+    let root = if cfg!(windows) { "C:\\" } else { "/" };
+    let root = Path::new(root);
+
+    assert!(sweeping_base_path(root, None, true, false).is_some());
+    assert!(sweeping_base_path(root, None, false, true).is_none());
+    assert!(sweeping_base_path(home.path(), Some(home.path()), true, true).is_some());
+    assert!(sweeping_base_path(home.path(), Some(home.path()), true, false).is_none());
+    assert!(sweeping_base_path(home.path(), Some(home.path()), false, true).is_none());
+    let subdir = home.path().join("subdir");
+    fs::create_dir(&subdir).unwrap();
+    assert!(sweeping_base_path(&subdir, Some(home.path()), true, true).is_none());
+    // the same directory given differently
+    assert!(sweeping_base_path(&subdir.join(".."), Some(home.path()), true, true).is_some());
+}
+
+/// Verify that renaming recursively in a filesystem root is refused unless confirmed by a flag
+#[test]
+fn scenario_test_refuse_filesystem_root() {
+    let root = if cfg!(windows) { "C:\\" } else { "/" };
+    let config = BumvConfiguration {
+        recursive: true,
+        base_path: Some(PathBuf::from(root)),
+        ..Default::default()
+    };
+    let error = bulk_rename(
+        config,
+        |_| panic!("the editor must not be opened"),
+        |_| true,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("--i-know-what-im-doing"));
+}
+
+/// Verify that many interdependent renamings are ordered correctly, including long cycles
+#[test]
+fn test_break_many_cycles() {
+    let dir = tempdir().unwrap();
+    let path = |i: usize| dir.path().join(format!("file{}", i));
+    let swaps = 5000;
+    let rotation = 5000;
+    let mut renames = Vec::new();
+    for i in (0..2 * swaps).step_by(2) {
+        renames.push((path(i), path(i + 1)));
+        renames.push((path(i + 1), path(i)));
+    }
+    for i in 0..rotation {
+        let next = 2 * swaps + (i + 1) % rotation;
+        renames.push((path(2 * swaps + i), path(next)));
+    }
+    // a chain ending in a free name
+    renames.push((path(3 * swaps + rotation), path(3 * swaps + rotation + 1)));
+
+    let steps = break_cycles_and_fix_ordering(&renames).unwrap();
+
+    // simulate the steps, tracking which original file is at which path
+    let mut files: HashMap<PathBuf, PathBuf> = renames
+        .iter()
+        .map(|(old, _)| (old.clone(), old.clone()))
+        .collect();
+    for (old, new) in &steps {
+        let file = files.remove(old).expect("the source must exist");
+        assert!(
+            files.insert(new.clone(), file).is_none(),
+            "{:?} exists",
+            new
+        );
+    }
+    for (old, new) in &renames {
+        assert_eq!(&files[new], old);
+    }
+    assert_eq!(steps.len(), renames.len() + swaps + 1);
+}
+
+/// Verify that the order of the steps only depends on the paths, not on the order of the mapping
+#[test]
+fn test_deterministic_step_order() {
+    let dir = tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name);
+    let renames = vec![
+        (path("d"), path("e")),
+        (path("a"), path("b")),
+        (path("b"), path("a")),
+        (path("c"), path("d")),
+        (path("x"), path("y")),
+    ];
+    let expected: Vec<(PathBuf, PathBuf)> = vec![
+        (path("a"), path("a.n0.tmp")),
+        (path("b"), path("a")),
+        (path("d"), path("e")),
+        (path("c"), path("d")),
+        (path("x"), path("y")),
+        (path("a.n0.tmp"), path("b")),
+    ];
+    assert_eq!(break_cycles_and_fix_ordering(&renames).unwrap(), expected);
+    let mut reversed = renames;
+    reversed.reverse();
+    assert_eq!(break_cycles_and_fix_ordering(&reversed).unwrap(), expected);
+}
+
+#[test]
+fn test_plan_dot() {
+    let mapping = vec![
+        (PathBuf::from("a"), PathBuf::from("b")),
+        (PathBuf::from("b"), PathBuf::from("a")),
+        (PathBuf::from("c \"1\""), PathBuf::from("d")),
+    ];
+    let steps = vec![
+        (PathBuf::from("a"), PathBuf::from("a.n0.tmp")),
+        (PathBuf::from("b"), PathBuf::from("a")),
+        (PathBuf::from("c \"1\""), PathBuf::from("d")),
+        (PathBuf::from("a.n0.tmp"), PathBuf::from("b")),
+    ];
+    assert_eq!(
+        plan_dot(&mapping, &steps),
+        r#"digraph bumv {
+  rankdir=LR;
+  node [shape=box];
+  // cycle: a -> b -> a
+  "a" [color=red];
+  "a.n0.tmp" [style=dashed, label="a.n0.tmp (temporary)"];
+  "b" [color=red];
+  "c \"1\"";
+  "d";
+  "a" -> "a.n0.tmp" [label="1"];
+  "b" -> "a" [label="2"];
+  "c \"1\"" -> "d" [label="3"];
+  "a.n0.tmp" -> "b" [label="4"];
+  "a" -> "b" [style=dashed, color=gray, label="requested"];
+}
+"#
+    );
+}
+
+/// Verify that the plan graph can be written to the base path without affecting the renaming
+#[test]
+fn scenario_test_plan_dot() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        plan_dot: Some(dir.path().join("plan.dot")),
+        ..Default::default()
+    };
+    let outcome = bulk_rename(
+        config,
+        |content| {
+            Ok(content
+                .replace("file1.txt", "SWAP")
+                .replace("file2.txt", "file1.txt")
+                .replace("SWAP", "file2.txt"))
+        },
+        |_| true,
+    )
+    .unwrap();
+    assert_eq!(outcome, Outcome::Renamed);
+    let dot = fs::read_to_string(dir.path().join("plan.dot")).unwrap();
+    assert!(dot.starts_with("digraph bumv {"));
+    assert!(dot.contains("(temporary)"));
+}
+
+/// Verify that the Markdown report groups the renamings by directory and names the saved plan,
+/// which can be applied unchanged once signed off
+#[test]
+fn scenario_test_report_md() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let report = dir.path().join("report.md");
+    let saved_plan = dir.path().join("plan.json");
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        recursive: true,
+        report_md: Some(report.clone()),
+        save_rejected_plan: Some(saved_plan.clone()),
+        ..Default::default()
+    };
+    let edit = |content: String| {
+        Ok(content
+            .replace("file1.txt", "a|b.txt")
+            .replace("file3.txt", "new/file3.txt"))
+    };
+    let outcome = bulk_rename(config.clone(), edit, |_| false).unwrap();
+    assert_eq!(outcome, Outcome::Aborted);
+    assert_no_filenames_changed(&dir);
+
+    let report = fs::read_to_string(report).unwrap();
+    assert!(report.starts_with("# Renaming plan\n"));
+    assert!(report.contains("- Renamings: 2 in 2 directories\n- Directories created: 1\n"));
+    assert!(report.contains("| `file1.txt` | `a\\|b.txt` |"));
+    assert!(report.contains(&format!(
+        "| `file3.txt` | `{}` |",
+        dir.path().join("subdir/new/file3.txt").to_string_lossy()
+    )));
+    assert!(report.contains(&format!("`bumv --apply {}`", saved_plan.to_string_lossy())));
+
+    let content = fs::read_to_string(&saved_plan).unwrap();
+    let (mapping, _) = requested_mapping(&content).unwrap();
+    let config = BumvConfiguration {
+        apply: Some(saved_plan),
+        report_md: None,
+        save_rejected_plan: None,
+        ..config
+    };
+    let outcome = bulk_rename(
+        config,
+        |content| apply_mapping(&content, &mapping),
+        |_| true,
+    )
+    .unwrap();
+    assert_eq!(outcome, Outcome::Renamed);
+    assert!(dir.path().join("a|b.txt").exists());
+    assert!(dir.path().join("subdir/new/file3.txt").exists());
+}
+
+#[test]
+fn test_partition_by_volume() {
+    let step = |old: &str, new: &str| (PathBuf::from(old), PathBuf::from(new));
+    // the first path component stands for the volume
+    let device_of = |path: &Path| {
+        path.components().next().and_then(|c| {
+            c.as_os_str()
+                .to_str()?
+                .strip_prefix("usb")?
+                .parse::<u64>()
+                .ok()
+        })
+    };
+    let steps = vec![
+        step("usb1/a", "usb1/b"),
+        step("usb2/a", "usb2/a.n0.tmp"),
+        step("usb1/c", "usb1/d"),
+        // a chain spanning two volumes stays together on the volume of its first source
+        step("usb2/x", "usb1/y"),
+        step("usb2/b", "usb2/a"),
+        step("usb1/z", "usb2/x"),
+        step("usb2/a.n0.tmp", "usb2/b"),
+    ];
+    let (ordered, volumes) = partition_by_volume(steps, device_of);
+    assert_eq!(volumes, vec![2, 5]);
+    assert_eq!(
+        ordered,
+        vec![
+            step("usb1/a", "usb1/b"),
+            step("usb1/c", "usb1/d"),
+            step("usb2/a", "usb2/a.n0.tmp"),
+            step("usb2/x", "usb1/y"),
+            step("usb2/b", "usb2/a"),
+            step("usb1/z", "usb2/x"),
+            step("usb2/a.n0.tmp", "usb2/b"),
+        ]
+    );
+
+    let (_, volumes) = partition_by_volume(Vec::new(), device_of);
+    assert!(volumes.is_empty());
+}
+
+/// With --per-component, each group of dependent steps is renamed on its own,
+/// and the steps of a group are undone if one of them fails
+#[test]
+fn test_per_component() {
+    let step = |old: &str, new: &str| (PathBuf::from(old), PathBuf::from(new));
+    let steps = vec![
+        step("a", "a.n0.tmp"),
+        step("c", "d"),
+        step("b", "a"),
+        step("a.n0.tmp", "b"),
+    ];
+    let (ordered, components) = partition_by_volume(steps, Path::to_path_buf);
+    assert_eq!(components, vec![3, 1]);
+    assert_eq!(ordered[3], step("c", "d"));
+
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let sandbox = Sandbox::open(dir.path()).unwrap();
+    let options = TransferOptions::default();
+    let performed = vec![
+        (dir.path().join("file1.txt"), dir.path().join("new1.txt")),
+        (dir.path().join("file2.txt"), dir.path().join("file1.txt")),
+    ];
+    for (old, new) in &performed {
+        move_file(old, new, &options, &sandbox).unwrap();
+    }
+    assert_eq!(roll_back(&performed, &options, &sandbox), 2);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("file1.txt")).unwrap(),
+        "file1_content"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("file2.txt")).unwrap(),
+        "file2_content"
+    );
+    assert!(!dir.path().join("new1.txt").exists());
+}
+
+/// Verify that copies and created directories get the original owner, if privileges allow it
+#[cfg(unix)]
+#[test]
+fn test_preserve_owner() {
+    use std::os::unix::fs::{chown, MetadataExt};
+    let owner = |path: &Path| {
+        let metadata = fs::metadata(path).unwrap();
+        (metadata.uid(), metadata.gid())
+    };
+
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let source = dir.path().join("file1.txt");
+    if chown(&source, Some(4321), Some(4321)).is_err() {
+        // changing owners requires root privileges
+        return;
+    }
+    let options = TransferOptions {
+        preserve_owner: true,
+        ..Default::default()
+    };
+    let target = dir.path().join("copy.txt");
+    copy_file(&source, &target, &options).unwrap();
+    assert_eq!(owner(&target), (4321, 4321));
+
+    chown(dir.path(), Some(4322), Some(4322)).unwrap();
+    let directories = vec![
+        dir.path().join("new"),
+        dir.path().join("new").join("nested"),
+    ];
+    create_directories(&directories, None, true, false, &mut Vec::new()).unwrap();
+    assert_eq!(owner(&directories[0]), (4322, 4322));
+    assert_eq!(owner(&directories[1]), (4322, 4322));
+}
+
+/// Set the modification time of a file to the given local date
+fn set_modification_date(path: &Path, year: i32, month: u32, day: u32) {
+    use chrono::TimeZone;
+    let modified = chrono::Local
+        .with_ymd_and_hms(year, month, day, 12, 0, 0)
+        .unwrap();
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(modified.into())
+        .unwrap();
+}
+
+#[test]
+fn test_archive_proposal() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let file1 = dir.path().join("file1.txt");
+    let file3 = dir.path().join("subdir").join("file3.txt");
+    let archived = dir.path().join("archive").join("2023-12").join("old.txt");
+    fs::create_dir_all(archived.parent().unwrap()).unwrap();
+    File::create(&archived).unwrap();
+    set_modification_date(&file1, 2024, 3, 1);
+    set_modification_date(&file3, 2024, 4, 30);
+    set_modification_date(&archived, 2023, 12, 24);
+
+    let proposal = archive_proposal(
+        &[file1, file3, archived.clone()],
+        dir.path(),
+        "archive/%Y-%m",
+    )
+    .unwrap();
+    assert_eq!(
+        proposal,
+        vec![
+            dir.path().join("archive/2024-03/file1.txt"),
+            dir.path().join("archive/2024-04/subdir/file3.txt"),
+            archived,
+        ]
+    );
+    assert!(archive_proposal(&proposal[..1], dir.path(), "archive/%Q").is_err());
+}
+
+/// Verify that the archive proposal is shown in the editor and renamed as edited
+#[test]
+fn scenario_test_archive_to() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    set_modification_date(&dir.path().join("file1.txt"), 2024, 3, 1);
+    set_modification_date(&dir.path().join("file2.txt"), 2024, 5, 1);
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        archive_to: Some("archive/%Y-%m".to_string()),
+        ..Default::default()
+    };
+    bulk_rename(
+        config,
+        |content| {
+            assert!(content.contains("archive/2024-03/file1.txt"));
+            // keep file2.txt where it is
+            Ok(content.replace(
+                &dir.path()
+                    .join("archive/2024-05/file2.txt")
+                    .to_string_lossy()
+                    .to_string(),
+                &dir.path().join("file2.txt").to_string_lossy(),
+            ))
+        },
+        |_| true,
+    )
+    .unwrap();
+    assert!(dir.path().join("archive/2024-03/file1.txt").exists());
+    assert!(dir.path().join("file2.txt").exists());
+}
+
+#[test]
+fn test_merge_proposal() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("source");
+    let destination = dir.path().join("destination");
+    for (path, content) in [
+        ("source/new.txt", "new"),
+        ("source/sub/same.txt", "same"),
+        ("source/different.txt", "a"),
+        ("destination/sub/same.txt", "same"),
+        ("destination/different.txt", "b"),
+    ] {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    let files = vec![
+        source.join("different.txt"),
+        source.join("new.txt"),
+        source.join("sub").join("same.txt"),
+    ];
+    let path = |path: PathBuf| path.to_string_lossy().to_string();
+    assert_eq!(
+        merge_proposal(&files, &source, &destination).unwrap(),
+        [
+            format!(
+                "# {} exists with different content. Edit the next line to move the file anyway.",
+                path(destination.join("different.txt"))
+            ),
+            path(source.join("different.txt")),
+            path(destination.join("new.txt")),
+            format!(
+                "# {} exists with the same content. Edit the next line to move the file anyway.",
+                path(destination.join("sub").join("same.txt"))
+            ),
+            path(source.join("sub").join("same.txt")),
+        ]
+        .join("\n")
+    );
+
+    // a destination inside the base path, with the files listed relative to it
+    let files = vec![
+        PathBuf::from("./bumv-merge-destination/kept.txt"),
+        PathBuf::from("./sub/moved.txt"),
+    ];
+    assert_eq!(
+        merge_proposal(&files, Path::new("."), Path::new("bumv-merge-destination")).unwrap(),
+        "./bumv-merge-destination/kept.txt\nbumv-merge-destination/sub/moved.txt"
+    );
+}
+
+/// Verify that merging moves files without collisions and keeps the notes out of the renaming
+#[test]
+fn scenario_test_merge_into() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let destination = tempdir().unwrap();
+    fs::write(destination.path().join("file1.txt"), "existing").unwrap();
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        merge_into: Some(destination.path().to_path_buf()),
+        ..Default::default()
+    };
+    bulk_rename(
+        config,
+        |content| {
+            assert!(content.contains("exists with different content"));
+            Ok(content)
+        },
+        |_| true,
+    )
+    .unwrap();
+    assert!(dir.path().join("file1.txt").exists());
+    assert_eq!(
+        fs::read_to_string(destination.path().join("file1.txt")).unwrap(),
+        "existing"
+    );
+    assert!(destination.path().join("file2.txt").exists());
+    // subdirectories are merged as well
+    assert!(destination.path().join("subdir").join("file3.txt").exists());
+}
+
+/// Verify that directories in which files cannot be renamed are detected before editing
+#[cfg(unix)]
+#[test]
+fn scenario_test_unwritable_directory() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let subdir = dir.path().join("subdir");
+    fs::set_permissions(&subdir, fs::Permissions::from_mode(0o555)).unwrap();
+    let files = vec![dir.path().join("file1.txt"), subdir.join("file3.txt")];
+    let unwritable = find_unwritable_directories(&files);
+    if unwritable.is_empty() {
+        // root may write to any directory
+        fs::set_permissions(&subdir, fs::Permissions::from_mode(0o755)).unwrap();
+        return;
+    }
+    assert_eq!(unwritable.keys().collect::<Vec<_>>(), vec![&subdir]);
+    assert_eq!(
+        FilesystemCapabilities::probe(&subdir).unwrap(),
+        FilesystemCapabilities::default()
+    );
+    // the check leaves no probe files behind
+    assert!(!fs::read_dir(dir.path()).unwrap().any(|entry| entry
+        .unwrap()
+        .file_name()
+        .to_string_lossy()
+        .starts_with(".bumv")));
+
+    let config = BumvConfiguration {
+        recursive: true,
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let error = bulk_rename(
+        config.clone(),
+        |content| Ok(content.replace("file3.txt", "renamed.txt")),
+        |_| true,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("is not writable"));
+    // files in writable directories can still be renamed
+    bulk_rename(
+        config,
+        |content| Ok(content.replace("file1.txt", "renamed.txt")),
+        |_| true,
+    )
+    .unwrap();
+    assert!(dir.path().join("renamed.txt").exists());
+
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(subdir.clone()),
+        ..Default::default()
+    };
+    assert!(bulk_rename(
+        config.clone(),
+        |_| panic!("the editor must not be opened"),
+        |_| true
+    )
+    .is_err());
+    // a dry run does not need to write
+    let config = BumvConfiguration {
+        dry_run: true,
+        ..config
+    };
+    bulk_rename(
+        config,
+        |content| Ok(content.replace("file3.txt", "renamed.txt")),
+        |_| true,
+    )
+    .unwrap();
+    assert!(subdir.join("file3.txt").exists());
+    fs::set_permissions(&subdir, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+fn test_listing_summary() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let files = vec![
+        dir.path().join("file1.txt"),
+        dir.path().join("file2.txt"),
+        dir.path().join("subdir").join("file3.txt"),
+    ];
+    let size: u64 = files
+        .iter()
+        .map(|file| fs::metadata(file).unwrap().len())
+        .sum();
+    assert_eq!(
+        listing_summary(&files, dir.path()),
+        format!(
+            "Listing 3 files in 2 directories, {} B in total, nested up to 1 level deep",
+            size
+        )
+    );
+    assert_eq!(
+        listing_summary(&files[..1], dir.path()),
+        format!(
+            "Listing 1 file in 1 directory, {} B in total, nested up to 0 levels deep",
+            fs::metadata(&files[0]).unwrap().len()
+        )
+    );
+
+    assert_eq!(format_size(1023), "1023 B");
+    assert_eq!(format_size(1536), "1.5 KiB");
+    assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+}
+
+#[test]
+fn test_explain_ignores() {
+    let dir = tempdir().unwrap();
+    let base = dir.path();
+    fs::create_dir(base.join(".git")).unwrap();
+    fs::write(
+        base.join(".gitignore"),
+        "# build output\n*.tmp\n!keep.tmp\n",
+    )
+    .unwrap();
+    fs::create_dir(base.join("subdir")).unwrap();
+    fs::write(base.join("subdir").join(".ignore"), "drafts/\n").unwrap();
+    fs::create_dir(base.join("subdir").join("drafts")).unwrap();
+    for file in [
+        "file.txt",
+        "debug.tmp",
+        "keep.tmp",
+        ".hidden",
+        "bumv_20240101_120000.log",
+        "subdir/file.txt",
+        "subdir/drafts/draft.txt",
+    ] {
+        File::create(base.join(file)).unwrap();
+    }
+    let all_sources = IgnoreSources {
+        ignore_files: true,
+        git_exclude: true,
+        git_global: true,
+    };
+    let explain = |file: &str, recursive: bool| {
+        explain_ignores(&base.join(file), base, all_sources, recursive, false).unwrap()
+    };
+
+    assert!(explain("file.txt", false).ends_with("is listed, as no ignore rule matches it."));
+    let gitignore = base.join(".gitignore");
+    assert!(explain("debug.tmp", false)
+        .ends_with(&format!("is ignored by {}:2:*.tmp", gitignore.display())));
+    assert!(explain("keep.tmp", false).ends_with(&format!(
+        "is re-included by {}:3:!keep.tmp",
+        gitignore.display()
+    )));
+    assert!(explain(".hidden", false).contains("is hidden"));
+    assert!(explain("bumv_20240101_120000.log", false).contains("--include-bumv-files"));
+    assert!(explain("subdir/file.txt", false).contains("--recursive"));
+    assert!(explain("subdir/file.txt", true).ends_with("is listed, as no ignore rule matches it."));
+    assert!(explain("subdir/drafts/draft.txt", true).contains(&format!(
+        "as its directory {} is ignored by {}:1:drafts/",
+        base.join("subdir").join("drafts").display(),
+        base.join("subdir").join(".ignore").display()
+    )));
+    assert!(explain_ignores(
+        &base.join("debug.tmp"),
+        base,
+        IgnoreSources {
+            ignore_files: false,
+            ..all_sources
+        },
+        false,
+        false
+    )
+    .unwrap()
+    .contains("--no-ignore"));
+}
+
+/// The performed steps of a session are reported with their strategy and duration.
+#[test]
+fn scenario_test_step_statistics() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let mut steps = Vec::new();
+
+    bulk_rename_in_loop(
+        config,
+        |_| Ok(|content: String| Ok(content.replace("file1.txt", "new/file1.txt"))),
+        |_| true,
+        |_, _, report| steps.extend(report.steps.iter().map(|step| step.to_json())),
+    )
+    .unwrap();
+
+    let strategy = |step: &JsonValue| match step {
+        JsonValue::Object(entries) => entries[0].1.clone(),
+        _ => panic!("steps are objects"),
+    };
+    assert_eq!(
+        steps.iter().map(strategy).collect::<Vec<_>>(),
+        vec![JsonValue::from("create_dir"), JsonValue::from("rename")]
+    );
+    let rename = steps[1].to_string();
+    assert!(rename.contains("file1.txt\",\"to\":"));
+    assert!(rename.contains("duration_ms"));
+}
+
+#[test]
+fn test_separation_proposal() {
+    let files: Vec<PathBuf> = [
+        "docs/README.md",
+        "docs/readme (2).md",
+        "docs/readme.md",
+        "Makefile",
+        "makefile",
+    ]
+    .iter()
+    .map(PathBuf::from)
+    .collect();
+    assert_eq!(
+        case_collisions(&files),
+        vec![
+            vec![Path::new("docs/README.md"), Path::new("docs/readme.md")],
+            vec![Path::new("Makefile"), Path::new("makefile")],
+        ]
+    );
+    let content = "docs/README.md\ndocs/readme (2).md\ndocs/readme.md\nMakefile\nmakefile";
+    assert_eq!(
+        separation_proposal(content, &files),
+        "docs/README.md\ndocs/readme (2).md\ndocs/readme (3).md\nMakefile\nmakefile (2)"
+    );
+}
+
+#[test]
+fn test_doctor() {
+    let dir = tempdir().unwrap();
+    let config = BumvConfiguration {
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let check = |name: &str| {
+        diagnose(&config, &ConfigFile::default())
+            .into_iter()
+            .find(|check| check.name == name)
+            .unwrap()
+    };
+
+    assert!(check("permissions").ok);
+    assert!(check("filesystem").ok);
+    let files = check("files");
+    assert!(!files.ok);
+    assert!(files.detail.contains("--explain-ignores"));
+
+    create_test_files(&dir);
+    let files = check("files");
+    assert!(files.ok);
+    assert!(report(&[files]).starts_with("ok       files: 2 files are listed"));
+}
+
+/// Renamings within the base path cannot be redirected out of it by a symlink
+#[cfg(unix)]
+#[test]
+fn test_sandbox() {
+    let dir = tempdir().unwrap();
+    let outside = tempdir().unwrap();
+    create_test_files(&dir);
+    File::create(outside.path().join("file.txt")).unwrap();
+    std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+    let sandbox = Sandbox::open(dir.path()).unwrap();
+    let options = TransferOptions::default();
+
+    move_file(
+        &dir.path().join("file1.txt"),
+        &dir.path().join("subdir").join("moved.txt"),
+        &options,
+        &sandbox,
+    )
+    .unwrap();
+    assert!(dir.path().join("subdir").join("moved.txt").exists());
+
+    assert!(move_file(
+        &dir.path().join("link").join("file.txt"),
+        &dir.path().join("link").join("renamed.txt"),
+        &options,
+        &sandbox,
+    )
+    .is_err());
+    assert!(outside.path().join("file.txt").exists());
+    assert!(!outside.path().join("renamed.txt").exists());
+
+    // each directory of the given files is protected, not only the first one
+    let other = tempdir().unwrap();
+    std::os::unix::fs::symlink(outside.path(), other.path().join("link")).unwrap();
+    let sandbox = Sandbox::open_all(&[dir.path(), other.path()]).unwrap();
+    assert!(move_file(
+        &other.path().join("link").join("file.txt"),
+        &other.path().join("link").join("renamed.txt"),
+        &options,
+        &sandbox,
+    )
+    .is_err());
+    assert!(outside.path().join("file.txt").exists());
+}
+
+#[test]
+fn test_walk() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let png = dir.path().join("image.png");
+    let mut header = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+    header.extend_from_slice(&640u32.to_be_bytes());
+    header.extend_from_slice(&480u32.to_be_bytes());
+    fs::write(&png, header).unwrap();
+    assert!(preview(&png).ends_with("PNG image, 640x480 pixels"));
+    assert!(preview(&dir.path().join("file1.txt")).ends_with("  | file1_content"));
+
+    let content = "a/file1.txt\na/file2.txt\na/file3.txt\na/file4.txt";
+    let mut answers = vec!["renamed.txt", "", "b/moved.txt", "."].into_iter();
+    let mut questions = Vec::new();
+    let edited = walk(content, |question| {
+        questions.push(question.to_string());
+        Ok(answers.next().unwrap().to_string())
+    })
+    .unwrap();
+    assert_eq!(
+        edited,
+        "a/renamed.txt\na/file2.txt\nb/moved.txt\na/file4.txt"
+    );
+    assert!(questions[3].contains("[4/4] a/file4.txt"));
+    assert!(questions[0].ends_with("New name (Enter keeps it, . keeps all remaining names): "));
+
+    let edited = walk(content, |_| Ok(".".to_string())).unwrap();
+    assert_eq!(edited, content);
+}
+
+/// The summary of a session is posted to the webhook as JSON
+#[test]
+fn test_notify_webhook() {
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some(value) = header.to_lowercase().strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        (&stream)
+            .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        (request_line, String::from_utf8(body).unwrap())
+    });
+
+    let summary = JsonValue::object([("outcome", "renamed".into())]);
+    notify(&url, &summary).unwrap();
+    let (request_line, body) = server.join().unwrap();
+    assert!(request_line.starts_with("POST /hook "));
+    assert_eq!(body, r#"{"outcome":"renamed"}"#);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let unreachable = format!("http://{}/hook", listener.local_addr().unwrap());
+    drop(listener);
+    assert!(notify(&unreachable, &summary).is_err());
+}
+
+/// The names computed by --sub-on are shown in a table, marking unchanged and clashing names
+#[test]
+fn test_show_computed() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        base_path: Some(dir.path().to_path_buf()),
+        sub_on: vec!["*.txt".to_string(), "s/[12]/0/".to_string()],
+        ..Default::default()
+    };
+    let table = config.computed_names().unwrap();
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("Original"));
+    assert!(lines[2].ends_with("file0.txt (clash)"));
+    assert!(lines[3].ends_with("file0.txt (clash)"));
+
+    let config = BumvConfiguration {
+        sub_on: vec!["*.md".to_string(), "s/a/b/".to_string()],
+        ..config
+    };
+    let table = config.computed_names().unwrap();
+    assert!(table.ends_with("file2.txt (unchanged)"));
+
+    let config = BumvConfiguration {
+        sub_on: Vec::new(),
+        ..config
+    };
+    assert!(config.computed_names().is_err());
+}
+
+#[test]
+fn test_rename_in_archive() {
+    use std::io::Read;
+    let dir = tempdir().unwrap();
+    let zip_path = dir.path().join("photos.zip");
+    let mut writer = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+    let options = zip::write::FileOptions::default();
+    writer.add_directory("2023/", options).unwrap();
+    writer.start_file("2023/a.jpg", options).unwrap();
+    writer.write_all(b"a").unwrap();
+    writer.start_file("b.jpg", options).unwrap();
+    writer.write_all(b"b").unwrap();
+    writer.finish().unwrap();
+
+    let tar_path = dir.path().join("photos.tar");
+    let mut builder = tar::Builder::new(File::create(&tar_path).unwrap());
+    for (name, content) in [("2023/a.jpg", b"a"), ("b.jpg", b"b")] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(1);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, name, content.as_slice())
+            .unwrap();
+    }
+    builder.finish().unwrap();
+    drop(builder);
+
+    for path in [&zip_path, &tar_path] {
+        assert_eq!(member_names(path).unwrap(), vec!["2023/a.jpg", "b.jpg"]);
+        let config = BumvConfiguration {
+            in_archive: Some(path.clone()),
+            ..Default::default()
+        };
+
+        // a clash is detected like for files
+        let result = bulk_rename(
+            config.clone(),
+            |_| Ok("2023/a.jpg\n2023/a.jpg".to_string()),
+            |_| true,
+        );
+        assert!(result.is_err());
+        let result = bulk_rename(
+            config.clone(),
+            |_| Ok("2023/a.jpg\n../b.jpg".to_string()),
+            |_| true,
+        );
+        assert!(result.is_err());
+        // directories of the current directory, like src, do not matter within the archive
+        let err = bulk_rename(
+            config.clone(),
+            |_| Ok("src/a.jpg\nsrc".to_string()),
+            |_| true,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("would be the parent directory of src/a.jpg"));
+        assert_eq!(member_names(path).unwrap(), vec!["2023/a.jpg", "b.jpg"]);
+
+        let outcome = bulk_rename(
+            config,
+            |_| Ok("2023/a.jpg\n2023/b.jpg".to_string()),
+            |_| true,
+        )
+        .unwrap();
+        assert_eq!(outcome, Outcome::Renamed);
+        assert_eq!(
+            member_names(path).unwrap(),
+            vec!["2023/a.jpg", "2023/b.jpg"]
+        );
+    }
+
+    let mut archive = zip::ZipArchive::new(File::open(&zip_path).unwrap()).unwrap();
+    let mut content = String::new();
+    archive
+        .by_name("2023/b.jpg")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, "b");
+}
+
+#[test]
+fn test_preview_segments() {
+    let original: Vec<String> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+    let edited: Vec<String> = ["a", "x/b", "c"].iter().map(|s| s.to_string()).collect();
+    let lines = unified_diff_lines(&original, &edited);
+    assert_eq!(
+        lines[2..],
+        [
+            DiffLine::Hunk {
+                start: 1,
+                length: 3
+            },
+            DiffLine::Context("a".to_string()),
+            DiffLine::Removed("b".to_string()),
+            DiffLine::Added("x/b".to_string()),
+            DiffLine::Context("c".to_string()),
+        ]
+    );
+
+    let mapping = vec![
+        (PathBuf::from("d/a"), PathBuf::from("e/a")),
+        (PathBuf::from("d/b"), PathBuf::from("e/b")),
+        (PathBuf::from("c"), PathBuf::from("d/c")),
+    ];
+    let groups = group_by_directory(&mapping);
+    assert_eq!(
+        groups.keys().copied().collect::<Vec<_>>(),
+        [Path::new(""), Path::new("d")]
+    );
+    assert_eq!(groups[Path::new("d")].len(), 2);
+
+    let summary = DirectorySummary::new(&mapping, 1).unwrap();
+    assert_eq!(
+        summary.leaving,
+        [(PathBuf::from("."), 1), (PathBuf::from("d"), 2)]
+    );
+    assert_eq!(
+        summary.arriving,
+        [(PathBuf::from("d"), 1), (PathBuf::from("e"), 2)]
+    );
+    assert!(DirectorySummary::new(&mapping[..0], 0).is_none());
+
+    let segments = [
+        Segment::Changes(vec![
+            Change::CreateDirectory(PathBuf::from("e")),
+            Change::Rename {
+                old: PathBuf::from("d/a"),
+                new: PathBuf::from("e/a"),
+                kind: None,
+                size: None,
+            },
+        ]),
+        Segment::Directories(summary),
+    ];
+    assert_eq!(
+        to_text(&segments, Preview::List),
+        "Create directory e\nd/a -> e/a\n\nSummary:\n  1 file will leave .\n  2 files will leave d\n  \
+         1 file will arrive in d\n  2 files will arrive in e\n  1 directory will be created"
+    );
+}
+
+/// Verify that the segments of a plan are available to frontends and rendered as the preview
+#[test]
+fn test_plan_segments() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let request =
+        RenamingRequest::try_new(config, |content| Ok(content.replace("file1", "renamed")))
+            .unwrap();
+    let plan = RenamingPlan::try_new(request).unwrap();
+    let segments = plan.segments();
+    assert!(matches!(
+        &segments[0],
+        Segment::Changes(changes) if changes == &[Change::rename(
+            &dir.path().join("file1.txt"),
+            &dir.path().join("renamed.txt")
+        )]
+    ));
+    assert_eq!(to_text(&segments, Preview::List), plan.preview());
+}
+
+/// Verify that --show-sizes shows the size of each renamed file and the totals per directory
+#[test]
+fn scenario_test_show_sizes() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    fs::write(dir.path().join("subdir/file4.txt"), vec![0; 3 * 1024]).unwrap();
+    let config = BumvConfiguration {
+        no_log: true,
+        recursive: true,
+        show_sizes: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let mut prompt = String::new();
+    bulk_rename(
+        config,
+        |content| Ok(content.replace("file", "renamed")),
+        |mapping| {
+            prompt = mapping;
+            false
+        },
+    )
+    .unwrap();
+
+    let subdir = dir.path().join("subdir");
+    assert!(prompt.contains(&format!(
+        "{} -> {} (3.0 KiB)",
+        subdir.join("file4.txt").to_string_lossy(),
+        subdir.join("renamed4.txt").to_string_lossy()
+    )));
+    assert!(prompt.contains(&format!(
+        "Sizes:\n  3.0 KiB in {}\n  26 B in {}",
+        subdir.to_string_lossy(),
+        dir.path().to_string_lossy()
+    )));
+}
+
+/// Validate that log files can be used as mapping files, unless their format is too new
+#[test]
+fn test_mapping_format_version() {
+    let log = format!(
+        "{}\n# created directory docs\na.txt     \tdocs/a.txt\nlong_b.txt\tb.txt\n# step 1: a.txt -> docs/a.txt",
+        header()
+    );
+    assert_eq!(
+        parse_mapping(&log).unwrap(),
+        vec![
+            (PathBuf::from("a.txt"), PathBuf::from("docs/a.txt")),
+            (PathBuf::from("long_b.txt"), PathBuf::from("b.txt")),
+        ]
+    );
+
+    let err = parse_mapping("# bumv mapping format 3, bumv 9.0.0\na.txt\tb.txt").unwrap_err();
+    assert!(err.to_string().contains("bumv 9.0.0 in format version 3"));
+    assert!(parse_mapping("# bumv mapping format x\na.txt\tb.txt").is_err());
+}
+
+/// Validate undoing the renamings of a log file, which is refused once a renamed file changed
+#[test]
+fn scenario_test_undo() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: false,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    bulk_rename(
+        config,
+        |content| Ok(content.replace("file1.txt", "renamed_file1.txt")),
+        prompt_function,
+    )
+    .unwrap();
+    let log_file = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.to_string_lossy().contains("bumv_"))
+        .unwrap();
+    let log = fs::read_to_string(&log_file).unwrap();
+    let renamed = dir.path().join("renamed_file1.txt");
+    assert_eq!(
+        undo_mapping(&log).unwrap(),
+        vec![(renamed.clone(), dir.path().join("file1.txt"))]
+    );
+
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(renamed.clone()),
+        undo: Some(log_file),
+        ..Default::default()
+    };
+    bulk_rename(
+        config,
+        |content| apply_mapping(&content, &undo_mapping(&log)?),
+        prompt_function,
+    )
+    .unwrap();
+    assert_no_filenames_changed(&dir);
+
+    fs::rename(dir.path().join("file1.txt"), &renamed).unwrap();
+    fs::write(&renamed, "changed").unwrap();
+    let err = undo_mapping(&log).unwrap_err();
+    assert!(err.to_string().ends_with("changed since it was renamed."));
+    assert!(undo_mapping("a.txt\tb.txt").is_err());
+}
+
+/// A dry run reports the planned steps in the JSON output, without renaming
+#[test]
+fn scenario_test_dry_run_plan() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: true,
+        dry_run: true,
+        json: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let mut plan = None;
+
+    let outcome = bulk_rename_in_loop(
+        config,
+        |_| {
+            Ok(|content: String| {
+                Ok(content
+                    .replace("file1.txt", "swap")
+                    .replace("file2.txt", "file1.txt")
+                    .replace("swap", "new/file2.txt"))
+            })
+        },
+        |_| panic!("a dry run does not ask for confirmation"),
+        |_, _, report| plan = report.plan.clone(),
+    )
+    .unwrap();
+
+    assert_eq!(outcome, Outcome::WouldRename);
+    assert_no_filenames_changed(&dir);
+    let plan = plan.unwrap().to_string();
+    assert!(plan.starts_with("{\"create_directories\":[\""));
+    assert!(plan.contains("new\"],\"remove_directories\":[],\"remove_duplicates\":[],"));
+    assert_eq!(plan.matches("\"internal\":false").count(), 2);
+}
+
+/// Verify that --plan-out writes the plan including temporary steps instead of renaming
+#[test]
+fn scenario_test_plan_out() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let plan_file = dir.path().join("plan.json");
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        plan_out: Some(plan_file.clone()),
+        ..Default::default()
+    };
+    let outcome = bulk_rename(
+        config,
+        |content| {
+            Ok(content
+                .replace("file1.txt", "swap")
+                .replace("file2.txt", "file1.txt")
+                .replace("swap", "file2.txt"))
+        },
+        |_| panic!("the plan is written without asking for confirmation"),
+    )
+    .unwrap();
+
+    assert_eq!(outcome, Outcome::WouldRename);
+    assert_no_filenames_changed(&dir);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("file1.txt")).unwrap(),
+        "file1_content"
+    );
+    let document = fs::read_to_string(plan_file).unwrap();
+    assert!(document.starts_with(&format!(
+        "{{\"format_version\":{},\"bumv_version\":\"",
+        plan_file::FORMAT_VERSION
+    )));
+    assert!(document.contains(",\"plan\":{\"create_directories\":[],"));
+    assert_eq!(document.matches("\"renamings\":[{\"from\":").count(), 1);
+    assert_eq!(document.matches("\"internal\":true").count(), 2);
+    assert_eq!(document.matches("\"internal\":false").count(), 1);
+    assert!(document.ends_with("}\n"));
+}
+
+/// Verify that --copy leaves the originals in place, and refuses copying to a copied file
+#[test]
+fn scenario_test_copy() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: true,
+        copy: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let err = bulk_rename(
+        config.clone(),
+        |content| {
+            Ok(content
+                .replace("file1.txt", "swap")
+                .replace("file2.txt", "file1.txt")
+                .replace("swap", "file2.txt"))
+        },
+        |_| true,
+    )
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .ends_with("file2.txt is copied itself, so it cannot be the target of another copy."));
+
+    let outcome = bulk_rename(
+        config,
+        |content| Ok(content.replace("file", "copy")),
+        |_| true,
+    )
+    .unwrap();
+    assert_eq!(outcome, Outcome::Renamed);
+    assert_no_filenames_changed(&dir);
+    for (original, copy) in [("file1.txt", "copy1.txt"), ("file2.txt", "copy2.txt")] {
+        assert_eq!(
+            fs::read_to_string(dir.path().join(copy)).unwrap(),
+            fs::read_to_string(dir.path().join(original)).unwrap()
+        );
+    }
+}
+
+/// Verify that existing targets are only planned to be moved to the trash with --overwrite trash
+#[test]
+fn scenario_test_overwrite_trash() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let plan_file = dir.path().join("plan.json");
+    let mut config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        plan_out: Some(plan_file.clone()),
+        ..Default::default()
+    };
+    let overwrite = |content: String| Ok(content.replace("file1.txt", "ignored.txt"));
+    let err = bulk_rename(config.clone(), overwrite, |_| true).unwrap_err();
+    assert!(err.to_string().ends_with("ignored.txt already exists."));
+
+    config.overwrite = Overwrite::Trash;
+    let outcome = bulk_rename(config, overwrite, |_| true).unwrap();
+    assert_eq!(outcome, Outcome::WouldRename);
+    let document = fs::read_to_string(plan_file).unwrap();
+    assert!(document.contains(&format!(
+        "\"trash\":[\"{}\"]",
+        dir.path().join("ignored.txt").to_string_lossy()
+    )));
+    assert!(dir.path().join("ignored.txt").exists());
+    // the plan is applied with --overwrite trash without giving it again
+    let (_, options) = requested_mapping(&document).unwrap();
+    let mut config = BumvConfiguration::default();
+    options.apply_to(&mut config);
+    assert_eq!(config.overwrite, Overwrite::Trash);
+    assert!(!config.dedupe_existing);
+}
+
+/// Verify that a plan written with --plan-out can be applied later, unless the files changed
+#[test]
+fn scenario_test_apply_plan() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let plan_file = dir.path().join("plan.json");
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        plan_out: Some(plan_file.clone()),
+        ..Default::default()
+    };
+    let swap = |content: String| {
+        Ok(content
+            .replace("file1.txt", "swap")
+            .replace("file2.txt", "file1.txt")
+            .replace("swap", "new/file2.txt"))
+    };
+    bulk_rename(config.clone(), swap, |_| false).unwrap();
+    let document = fs::read_to_string(&plan_file).unwrap();
+    let (mapping, options) = requested_mapping(&document).unwrap();
+    assert_eq!(options, PlanOptions::default());
+    assert_eq!(
+        mapping,
+        vec![
+            (
+                dir.path().join("file1.txt"),
+                dir.path().join("new/file2.txt")
+            ),
+            (dir.path().join("file2.txt"), dir.path().join("file1.txt")),
+        ]
+    );
+
+    let config = BumvConfiguration {
+        plan_out: None,
+        apply: Some(plan_file),
+        ..config
+    };
+    let outcome = bulk_rename(
+        config,
+        |content| apply_mapping(&content, &mapping),
+        |_| true,
+    )
+    .unwrap();
+    assert_eq!(outcome, Outcome::Renamed);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("new/file2.txt")).unwrap(),
+        "file1_content"
+    );
+    let err = requested_mapping(&document).unwrap_err();
+    assert!(err.to_string().ends_with("exists already."));
+
+    let too_new = r#"{"format_version":99,"bumv_version":"9.0.0","plan":{}}"#;
+    let err = requested_mapping(too_new).unwrap_err();
+    assert!(err.to_string().contains("bumv 9.0.0 in format version 99"));
+}
+
+/// Validate parsing JSON documents, e.g. plans written with --plan-out
+#[test]
+fn test_json_parse() {
+    let value = JsonValue::object([
+        ("path", "a \"quoted\"\\name\n".into()),
+        ("internal", true.into()),
+        ("steps", vec![JsonValue::Null, 2usize.into()].into()),
+    ]);
+    assert_eq!(JsonValue::parse(&value.to_string()).unwrap(), value);
+    let parsed = JsonValue::parse(" { \"a\" : [ -1.5e2 , \"\\u00e9\\ud83d\\ude00\" ] } ").unwrap();
+    let a = parsed.get("a").and_then(JsonValue::as_array).unwrap();
+    assert_eq!(a[0].as_f64(), Some(-150.0));
+    assert_eq!(a[1].as_str(), Some("\u{e9}\u{1f600}"));
+    assert_eq!(parsed.get("b"), None);
+    for invalid in ["", "{", "[1,]", "{\"a\" 1}", "\"\\x\"", "nul", "1 2"] {
+        assert!(JsonValue::parse(invalid).is_err(), "{}", invalid);
+    }
+}
+
+/// No step is started once the time limit is exceeded, and the pending steps are reported
+#[test]
+fn scenario_test_timeout() {
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        timeout: Some(Duration::ZERO),
+        ..Default::default()
+    };
+
+    let err = bulk_rename(
+        config,
+        |content| Ok(content.replace("file1.txt", "new/file1.txt")),
+        |_| true,
+    )
+    .unwrap_err();
+
+    assert!(err.to_string().contains("--timeout"));
+    assert_no_filenames_changed(&dir);
+    // the created directory is removed again
+    assert!(!dir.path().join("new").exists());
+    let report = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.to_string_lossy().ends_with("_failure.json"))
+        .unwrap();
+    assert!(fs::read_to_string(report)
+        .unwrap()
+        .contains("\"completed\":[],"));
+}
+
+/// Verify that names ending in a space and names ending in a dot are validated separately
+#[test]
+fn test_validate_trailing_spaces_and_dots() {
+    let capabilities = FilesystemCapabilities {
+        trailing_dots: false,
+        ..Default::default()
+    };
+    assert!(capabilities.validate_file_name("notes ").is_ok());
+    assert!(capabilities.validate_file_name("notes.").is_err());
+    let capabilities = FilesystemCapabilities {
+        trailing_spaces: false,
+        ..Default::default()
+    };
+    assert!(capabilities.validate_file_name("notes ").is_err());
+    assert!(capabilities.validate_file_name("notes.").is_ok());
+}
+
+/// Verify that names are validated against the capabilities of all filesystems involved
+#[test]
+fn test_common_capabilities() {
+    let common = FilesystemCapabilities::common([
+        FilesystemCapabilities {
+            max_name_length: 143,
+            illegal_characters: vec![':'],
+            ..Default::default()
+        },
+        FilesystemCapabilities {
+            case_sensitive: false,
+            illegal_characters: vec!['?', ':'],
+            trailing_dots: false,
+            ..Default::default()
+        },
+    ]);
+    assert_eq!(
+        common,
+        FilesystemCapabilities {
+            case_sensitive: false,
+            max_name_length: 143,
+            illegal_characters: vec![':', '?'],
+            trailing_dots: false,
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        FilesystemCapabilities::common([]),
+        FilesystemCapabilities::default()
+    );
+}
+
+/// Verify that the filesystem is probed in a temporary directory that is removed again, and
+/// that the result is kept for later sessions
+#[test]
+fn test_probe_capabilities_in_temporary_directory() {
+    let dir = tempdir().unwrap();
+    let capabilities = FilesystemCapabilities::probe(dir.path()).unwrap();
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    assert_eq!(
+        FilesystemCapabilities::probe(dir.path()).unwrap(),
+        capabilities
+    );
+    // detecting without creating files finds the case sensitivity from the existing files
+    File::create(dir.path().join("Notes.txt")).unwrap();
+    assert_eq!(
+        FilesystemCapabilities::detect(dir.path()).case_sensitive,
+        capabilities.case_sensitive
+    );
+}
+
+/// Renamings that only change whitespace at the ends of names are listed and can be declined
+#[test]
+fn scenario_test_whitespace_only_renamings() {
+    assert!(changes_only_whitespace(
+        Path::new("docs/a.txt"),
+        Path::new(" docs/a.txt ")
+    ));
+    assert!(!changes_only_whitespace(
+        Path::new("a.txt"),
+        Path::new("a .txt")
+    ));
+    assert!(!changes_only_whitespace(
+        Path::new("a.txt"),
+        Path::new("a.txt")
+    ));
+
+    let dir = tempdir().unwrap();
+    create_test_files(&dir);
+    let config = BumvConfiguration {
+        no_log: true,
+        base_path: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let prompts = RefCell::new(Vec::new());
+
+    let outcome = bulk_rename(
+        config,
+        |content| {
+            Ok(content
+                .replace("file1.txt", "renamed.txt")
+                .replace("file2.txt", "file2.txt "))
+        },
+        |prompt: String| {
+            prompts.borrow_mut().push(prompt.clone());
+            // decline the whitespace change, confirm the rest
+            !prompt.contains("(whitespace)")
+        },
+    )
+    .unwrap();
+
+    assert_eq!(outcome, Outcome::Renamed);
+    let prompts = prompts.into_inner();
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[0].contains("file2.txt \" (whitespace)"));
+    assert!(!prompts[1].contains("file2.txt"));
+    assert!(dir.path().join("renamed.txt").exists());
+    assert!(dir.path().join("file2.txt").exists());
+    assert!(!dir.path().join("file2.txt ").exists());
+}
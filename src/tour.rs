@@ -0,0 +1,81 @@
+//! A guided tour through renaming sessions in a throwaway directory.
+
+use crate::{bulk_rename, BumvConfiguration, Outcome};
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+/// A step of the tour: files to create, and an explanation shown before the editor is opened
+struct TourStep {
+    title: &'static str,
+    explanation: &'static str,
+    files: &'static [&'static str],
+}
+
+const TOUR_STEPS: [TourStep; 3] = [
+    TourStep {
+        title: "Renaming files",
+        explanation: "Your editor will open with a list of example files, one per line.\n\
+            Edit the names, e.g. replace `IMG_` with `holiday_`, then save and close the editor.\n\
+            bumv shows the planned renamings and asks for confirmation.",
+        files: &["IMG_0001.jpg", "IMG_0002.jpg", "IMG_0003.jpg"],
+    },
+    TourStep {
+        title: "Swapping names",
+        explanation: "Renamings may form cycles. Swap the names of `first.txt` and `second.txt`.\n\
+            bumv renames one of them to a temporary name first, so no file is overwritten.",
+        files: &["first.txt", "second.txt"],
+    },
+    TourStep {
+        title: "Undoing a renaming",
+        explanation: "Renamings are undone just like they are done. The files of the first step\n\
+            are listed again with their new names. Change them back to `IMG_...`.\n\
+            The log file of each renaming (bumv_*.log) tells you the original names.",
+        files: &[],
+    },
+];
+
+/// Create the example files of the tour in `directory`
+pub fn create_example_files(directory: &Path) -> Result<()> {
+    for file in TOUR_STEPS.iter().flat_map(|step| step.files) {
+        fs::write(directory.join(file), format!("Example file {}\n", file))?;
+    }
+    Ok(())
+}
+
+/// Run the tour in a temporary directory, which is removed afterwards.
+/// `edit_function` and `prompt_function` are passed as parameters to allow for testing.
+pub fn run_tour(
+    edit_function: impl Fn(String) -> Result<String>,
+    mut prompt_function: impl FnMut(String) -> bool,
+) -> Result<()> {
+    let directory = tempfile::Builder::new().prefix("bumv-tour").tempdir()?;
+    create_example_files(directory.path())?;
+    println!(
+        "Welcome to the bumv tour. Example files were created in {}.\n\
+        Nothing outside of this directory is renamed, and it is removed after the tour.",
+        directory.path().to_string_lossy()
+    );
+    for (i, step) in TOUR_STEPS.iter().enumerate() {
+        println!(
+            "\n== Step {} of {}: {} ==\n{}\n",
+            i + 1,
+            TOUR_STEPS.len(),
+            step.title,
+            step.explanation
+        );
+        let config = BumvConfiguration {
+            base_path: Some(directory.path().to_path_buf()),
+            // logs are excluded from the listing, so they can be looked at safely
+            ..Default::default()
+        };
+        match bulk_rename(config, &edit_function, &mut prompt_function) {
+            Ok(Outcome::NothingToRename) => println!("You did not change any names, that's fine."),
+            Ok(_) => {}
+            // errors are part of learning, so the tour continues
+            Err(e) => println!("bumv rejected the renaming: {:#}", e),
+        }
+    }
+    println!("\nThat's it! Run `bumv --help` to see all options.");
+    Ok(())
+}
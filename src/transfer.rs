@@ -0,0 +1,479 @@
+//! Copy based file transfers, used when a file cannot simply be renamed,
+//! e.g. because the target is located on a different device.
+
+use anyhow::{Context, Result};
+use cap_std::ambient_authority;
+use cap_std::fs::Dir;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{ErrorKind, Read};
+use std::path::{Component, Path, PathBuf};
+#[cfg(target_os = "linux")]
+use std::process::Command;
+
+/// Options controlling how files are transferred
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransferOptions {
+    /// Compare checksums of source and copy before the source is removed
+    pub verify_copy: bool,
+    /// Give copies the owner and group of the source, which requires privileges
+    pub preserve_owner: bool,
+    /// The SELinux security context copies get
+    pub security_context: SecurityContext,
+    /// Copy files instead of moving them, leaving the originals in place, given by --copy
+    pub keep_source: bool,
+}
+
+/// The SELinux security context of copies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SecurityContext {
+    /// The context of new files in the target directory, like for any other new file
+    #[default]
+    Inherit,
+    /// The context of the source, given by --preserve-context
+    Preserve,
+    /// The default context of the target path by the policy, applied with `restorecon`,
+    /// given by --reset-context
+    Reset,
+}
+
+/// How a file was moved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    Rename,
+    /// Copying the file, and removing the original if it was moved to another device
+    Copy,
+}
+
+impl Strategy {
+    /// Identifier of the strategy in JSON output
+    pub fn name(&self) -> &'static str {
+        match self {
+            Strategy::Rename => "rename",
+            Strategy::Copy => "copy",
+        }
+    }
+}
+
+/// Directory handles for the base path, or the directories of the given files, opened before
+/// renaming. Files within one of them are renamed relative to its handle, resolving their paths
+/// like `openat` and `renameat` without leaving the directory. So replacing a directory by a
+/// symlink or renaming it concurrently cannot redirect renamings out of the tree.
+pub struct Sandbox {
+    directories: Vec<(PathBuf, Dir)>,
+}
+
+impl Sandbox {
+    pub fn open(root: &Path) -> Result<Self> {
+        Self::open_all(&[root])
+    }
+
+    /// Open a handle for each of the `roots`, except for those that do not exist yet, e.g. a
+    /// target root that is created while renaming
+    pub fn open_all(roots: &[&Path]) -> Result<Self> {
+        let directories = roots
+            .iter()
+            .enumerate()
+            .filter(|(i, root)| *i == 0 || root.is_dir())
+            .map(|(_, root)| {
+                let directory = Dir::open_ambient_dir(root, ambient_authority())
+                    .with_context(|| format!("Failed to open the directory {}", root.display()))?;
+                Ok((root.to_path_buf(), directory))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { directories })
+    }
+
+    /// The path relative to `root`, if the path is within the directory.
+    /// Paths leaving it with `..` are not, as they are renamed out of the base path on purpose.
+    fn relative<'a>(root: &Path, path: &'a Path) -> Option<&'a Path> {
+        let relative = path.strip_prefix(root).ok()?;
+        relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+            .then_some(relative)
+    }
+
+    /// Rename `old` to `new` relative to the handle of a directory containing both,
+    /// otherwise by their paths
+    fn rename(&self, old: &Path, new: &Path) -> std::io::Result<()> {
+        for (root, directory) in &self.directories {
+            match (Self::relative(root, old), Self::relative(root, new)) {
+                (Some(old), Some(new)) if !old.as_os_str().is_empty() => {
+                    return directory.rename(old, directory, new)
+                }
+                _ => {}
+            }
+        }
+        fs::rename(old, new)
+    }
+}
+
+/// Move a file by renaming it. If the target is located on a different device,
+/// fall back to copying the file and removing the original.
+/// Within the `sandbox`, files are renamed relative to its directory handle.
+/// Returns the strategy that was used.
+pub fn move_file(
+    old: &Path,
+    new: &Path,
+    options: &TransferOptions,
+    sandbox: &Sandbox,
+) -> Result<Strategy> {
+    match sandbox.rename(old, new) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            if let Some(kind) = special_file_kind(old) {
+                anyhow::bail!(
+                    "{} is a {}, which cannot be copied to another device.",
+                    old.display(),
+                    kind
+                );
+            }
+            if old.is_dir() {
+                // only empty directories are listed, so there is nothing to copy
+                fs::create_dir(new)
+                    .with_context(|| format!("Failed to create the directory {}", new.display()))?;
+                if let Err(e) = apply_security_context(old, new, options.security_context) {
+                    let _ = fs::remove_dir(new);
+                    return Err(e.context("The original was kept."));
+                }
+                fs::remove_dir(old).with_context(|| {
+                    format!(
+                        "Created {}, but failed to remove the original directory",
+                        new.display()
+                    )
+                })?;
+                return Ok(Strategy::Copy);
+            }
+            copy_file(old, new, options)?;
+            fs::remove_file(old).with_context(|| {
+                format!(
+                    "Copied {} to {}, but failed to remove the original",
+                    old.display(),
+                    new.display()
+                )
+            })?;
+            Ok(Strategy::Copy)
+        }
+        result => result
+            .map(|()| Strategy::Rename)
+            .with_context(|| format!("Failed to rename {} to {}", old.display(), new.display())),
+    }
+}
+
+/// Copy a file to its new name, leaving the original in place, as done with --copy.
+/// Empty directories are created anew, special files cannot be copied.
+pub fn duplicate_file(old: &Path, new: &Path, options: &TransferOptions) -> Result<Strategy> {
+    if let Some(kind) = special_file_kind(old) {
+        anyhow::bail!("{} is a {}, which cannot be copied.", old.display(), kind);
+    }
+    if old.is_dir() {
+        // only empty directories are listed, so there is nothing to copy
+        fs::create_dir(new)
+            .with_context(|| format!("Failed to create the directory {}", new.display()))?;
+        if let Err(e) = apply_security_context(old, new, options.security_context) {
+            let _ = fs::remove_dir(new);
+            return Err(e);
+        }
+    } else {
+        copy_file(old, new, options)?;
+    }
+    Ok(Strategy::Copy)
+}
+
+/// Remove a copy made by `duplicate_file`
+pub fn remove_copy(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        fs::remove_dir(path)
+    } else {
+        fs::remove_file(path)
+    }
+    .with_context(|| format!("Failed to remove the copy {}", path.display()))
+}
+
+/// Copy a file, verifying the copy if requested. A copy that fails verification is removed.
+///
+/// On macOS, `fs::copy` clones the file or copies it with `fcopyfile(COPYFILE_ALL)`, which
+/// preserves extended attributes. These include Finder tags and labels, the Finder info and
+/// resource forks, so files moved to another volume keep them.
+/// On Windows, the access control list is copied, see `copy_permissions`.
+pub fn copy_file(source: &Path, target: &Path, options: &TransferOptions) -> Result<()> {
+    fs::copy(source, target).with_context(|| {
+        format!(
+            "Failed to copy {} to {}",
+            source.display(),
+            target.display()
+        )
+    })?;
+    if let Err(e) = copy_permissions(source, target) {
+        let _ = fs::remove_file(target);
+        return Err(e.context("The original was kept."));
+    }
+    if let Err(e) = apply_security_context(source, target, options.security_context) {
+        let _ = fs::remove_file(target);
+        return Err(e.context("The original was kept."));
+    }
+    if options.preserve_owner {
+        if let Err(e) = copy_owner(source, target) {
+            let _ = fs::remove_file(target);
+            return Err(e.context("The original was kept."));
+        }
+    }
+    if options.verify_copy {
+        let source_checksum = file_checksum(source)?;
+        let target_checksum = file_checksum(target)?;
+        if source_checksum != target_checksum {
+            let _ = fs::remove_file(target);
+            anyhow::bail!(
+                "The copy of {} at {} does not match the original. The original was kept.",
+                source.display(),
+                target.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Give `target` the owner and group of `source`.
+/// This requires running as root or with the capability `CAP_CHOWN`.
+#[cfg(unix)]
+pub fn copy_owner(source: &Path, target: &Path) -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(source)
+        .with_context(|| format!("Failed to read the metadata of {}", source.display()))?;
+    std::os::unix::fs::chown(target, Some(metadata.uid()), Some(metadata.gid())).with_context(
+        || {
+            format!(
+                "Failed to give {} the owner of {}. Preserving owners requires root privileges.",
+                target.display(),
+                source.display()
+            )
+        },
+    )
+}
+
+/// Owners are not supported on this platform
+#[cfg(not(unix))]
+pub fn copy_owner(_source: &Path, _target: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Give `target` the access control list (DACL) of `source`. `fs::copy` only copies the
+/// read-only attribute, so copies would get the default permissions of their new directory.
+/// Permissions the source inherited are inherited from the new directory instead, unless
+/// inheritance is disabled for the source, e.g. on file shares with explicit permissions.
+#[cfg(windows)]
+pub fn copy_permissions(source: &Path, target: &Path) -> Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::ERROR_SUCCESS;
+    use windows_sys::Win32::Security::Authorization::{
+        GetNamedSecurityInfoW, SetNamedSecurityInfoW, SE_FILE_OBJECT,
+    };
+    use windows_sys::Win32::Security::{
+        GetSecurityDescriptorControl, ACL, DACL_SECURITY_INFORMATION,
+        PROTECTED_DACL_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR, SE_DACL_PROTECTED,
+        UNPROTECTED_DACL_SECURITY_INFORMATION,
+    };
+    use windows_sys::Win32::System::Memory::LocalFree;
+
+    let wide = |path: &Path| -> Vec<u16> {
+        path.as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect()
+    };
+    let (mut source_name, mut target_name) = (wide(source), wide(target));
+    let mut dacl: *mut ACL = std::ptr::null_mut();
+    let mut descriptor: PSECURITY_DESCRIPTOR = std::ptr::null_mut();
+    // SAFETY: the name is NUL terminated, and the descriptor returned on success, which owns the
+    // DACL, is freed with `LocalFree` after the DACL was applied to the target
+    unsafe {
+        let error = GetNamedSecurityInfoW(
+            source_name.as_mut_ptr(),
+            SE_FILE_OBJECT,
+            DACL_SECURITY_INFORMATION,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut dacl,
+            std::ptr::null_mut(),
+            &mut descriptor,
+        );
+        if error != ERROR_SUCCESS {
+            return Err(std::io::Error::from_raw_os_error(error as i32)).with_context(|| {
+                format!("Failed to read the permissions of {}", source.display())
+            });
+        }
+        let (mut control, mut revision) = (0, 0);
+        let protection = if GetSecurityDescriptorControl(descriptor, &mut control, &mut revision)
+            != 0
+            && control & SE_DACL_PROTECTED != 0
+        {
+            PROTECTED_DACL_SECURITY_INFORMATION
+        } else {
+            UNPROTECTED_DACL_SECURITY_INFORMATION
+        };
+        let error = SetNamedSecurityInfoW(
+            target_name.as_mut_ptr(),
+            SE_FILE_OBJECT,
+            DACL_SECURITY_INFORMATION | protection,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            dacl,
+            std::ptr::null(),
+        );
+        LocalFree(descriptor as _);
+        if error != ERROR_SUCCESS {
+            return Err(std::io::Error::from_raw_os_error(error as i32)).with_context(|| {
+                format!(
+                    "Failed to give {} the permissions of {}",
+                    target.display(),
+                    source.display()
+                )
+            });
+        }
+    }
+    Ok(())
+}
+
+/// On other platforms, `fs::copy` copies the permission bits
+#[cfg(not(windows))]
+pub fn copy_permissions(_source: &Path, _target: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Give the copy `target` of `source` the SELinux security context chosen by `context`
+#[cfg(target_os = "linux")]
+fn apply_security_context(source: &Path, target: &Path, context: SecurityContext) -> Result<()> {
+    const ATTRIBUTE: &str = "security.selinux";
+    match context {
+        SecurityContext::Inherit => Ok(()),
+        SecurityContext::Preserve => {
+            let label = match xattr::get(source, ATTRIBUTE) {
+                // filesystems without extended attributes have no labels to preserve
+                Err(e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => None,
+                result => result.with_context(|| {
+                    format!(
+                        "Failed to read the security context of {}",
+                        source.display()
+                    )
+                })?,
+            };
+            match label {
+                Some(label) => xattr::set(target, ATTRIBUTE, &label).with_context(|| {
+                    format!(
+                        "Failed to give {} the security context of {}",
+                        target.display(),
+                        source.display()
+                    )
+                }),
+                None => Ok(()),
+            }
+        }
+        SecurityContext::Reset => {
+            let status = Command::new("restorecon")
+                .arg(target)
+                .status()
+                .context("Failed to run restorecon, which is needed for --reset-context")?;
+            anyhow::ensure!(
+                status.success(),
+                "Failed to reset the security context of {} with restorecon ({})",
+                target.display(),
+                status
+            );
+            Ok(())
+        }
+    }
+}
+
+/// SELinux is only supported on Linux
+#[cfg(not(target_os = "linux"))]
+fn apply_security_context(_source: &Path, _target: &Path, _context: SecurityContext) -> Result<()> {
+    Ok(())
+}
+
+/// Compute a checksum of the content of a file, to verify a copy made by this process.
+/// This detects corruption, but is not suitable for cryptographic purposes. As different
+/// contents may have the same checksum, it must not decide whether a file is removed as a
+/// duplicate, which compares the contents byte by byte instead.
+pub fn file_checksum(path: &Path) -> Result<u64> {
+    let mut file = File::open(path)
+        .with_context(|| format!("Failed to open {} for verification", path.display()))?;
+    // `DefaultHasher::new` uses fixed keys, so checksums of equal content are equal
+    let mut hasher = DefaultHasher::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.write(&buffer[..read]);
+    }
+    Ok(hasher.finish())
+}
+
+/// The kind of a special file, i.e. a FIFO, a socket or a device node. These can be renamed,
+/// but not copied. Returns `None` for regular files, directories and symlinks.
+#[cfg(unix)]
+pub fn special_file_kind(path: &Path) -> Option<&'static str> {
+    use std::os::unix::fs::FileTypeExt;
+    let file_type = fs::symlink_metadata(path).ok()?.file_type();
+    if file_type.is_fifo() {
+        Some("FIFO")
+    } else if file_type.is_socket() {
+        Some("socket")
+    } else if file_type.is_block_device() {
+        Some("block device")
+    } else if file_type.is_char_device() {
+        Some("character device")
+    } else {
+        None
+    }
+}
+
+/// Special files are not listed on this platform
+#[cfg(not(unix))]
+pub fn special_file_kind(_path: &Path) -> Option<&'static str> {
+    None
+}
+
+/// Whether renaming `source` to `target` stays on the same device, i.e. does not need a copy.
+/// For a target that does not exist yet, its closest existing ancestor is checked.
+#[cfg(unix)]
+pub fn same_device(source: &Path, target: &Path) -> Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    let source_device = fs::metadata(source)
+        .with_context(|| format!("Failed to read the metadata of {}", source.display()))?
+        .dev();
+    Ok(device_of(target)? == source_device)
+}
+
+/// The device a path is stored on. For a path that does not exist yet, this is the device of its
+/// closest existing ancestor.
+#[cfg(unix)]
+pub fn device_of(path: &Path) -> Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+    let existing_ancestor = path
+        .ancestors()
+        .map(|ancestor| {
+            if ancestor.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                ancestor
+            }
+        })
+        .find(|ancestor| ancestor.exists())
+        .with_context(|| format!("No existing parent directory of {}", path.display()))?;
+    Ok(fs::metadata(existing_ancestor)?.dev())
+}
+
+/// Devices cannot be detected on this platform, so renamings are assumed to stay on the device
+#[cfg(not(unix))]
+pub fn same_device(_source: &Path, _target: &Path) -> Result<bool> {
+    Ok(true)
+}
+
+/// Devices cannot be detected on this platform, so all paths are assumed to be on one device
+#[cfg(not(unix))]
+pub fn device_of(_path: &Path) -> Result<u64> {
+    Ok(0)
+}
@@ -0,0 +1,94 @@
+//! Undoing the renamings of a log file with `--undo LOG_FILE`. The log file records the size
+//! and modification time of each renamed file, so the inverted renamings are only planned if
+//! none of the files changed since.
+
+use crate::mapping_file;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Start of the lines of log files recording the state of a renamed file
+const STATE_PREFIX: &str = "# state ";
+
+/// The first format version of log files recording the states of renamed files
+const FIRST_VERSION_WITH_STATES: u32 = 2;
+
+/// The size and modification time of a file, to tell whether it changed since it was renamed.
+/// Directories change whenever files in them change, so only their existence is recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileState {
+    size: u64,
+    /// Nanoseconds since the Unix epoch
+    modified: u128,
+}
+
+impl FileState {
+    pub fn of(path: &Path) -> std::io::Result<Self> {
+        let metadata = fs::symlink_metadata(path)?;
+        if metadata.is_dir() {
+            return Ok(Self {
+                size: 0,
+                modified: 0,
+            });
+        }
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos())
+            .unwrap_or_default();
+        Ok(Self {
+            size: metadata.len(),
+            modified,
+        })
+    }
+}
+
+/// The line of the log file recording the state of the renamed file at `path`
+pub fn state_line(path: &Path, state: FileState) -> String {
+    format!(
+        "{}{}\t{}\t{}",
+        STATE_PREFIX,
+        state.size,
+        state.modified,
+        path.to_string_lossy()
+    )
+}
+
+fn parse_state_line(line: &str) -> Option<(PathBuf, FileState)> {
+    let mut fields = line.strip_prefix(STATE_PREFIX)?.splitn(3, '\t');
+    let size = fields.next()?.parse().ok()?;
+    let modified = fields.next()?.parse().ok()?;
+    Some((PathBuf::from(fields.next()?), FileState { size, modified }))
+}
+
+/// The renamings from the new names in the log file back to the old ones.
+/// Fails if a renamed file is missing or changed since it was renamed, or if the log file
+/// was written by a version of bumv that did not record the states of the files.
+pub fn undo_mapping(log: &str) -> Result<Vec<(PathBuf, PathBuf)>> {
+    anyhow::ensure!(
+        mapping_file::format_version(log)?
+            .is_some_and(|version| version >= FIRST_VERSION_WITH_STATES),
+        "The file is not a log file of bumv 0.2.1 or later, which record the states of the \
+         renamed files needed to tell whether they changed since."
+    );
+    let mapping = mapping_file::parse_mapping(log)?;
+    let states: HashMap<PathBuf, FileState> = log.lines().filter_map(parse_state_line).collect();
+    for (_, new) in &mapping {
+        let recorded = states.get(new).with_context(|| {
+            format!(
+                "The log file does not record the state of {}.",
+                new.to_string_lossy()
+            )
+        })?;
+        let current = FileState::of(new)
+            .with_context(|| format!("{} does not exist anymore.", new.to_string_lossy()))?;
+        anyhow::ensure!(
+            current == *recorded,
+            "{} changed since it was renamed.",
+            new.to_string_lossy()
+        );
+    }
+    Ok(mapping.into_iter().map(|(old, new)| (new, old)).collect())
+}
@@ -0,0 +1,124 @@
+//! Renaming file by file with `--walk`. Instead of editing the file list in an editor, each file
+//! is presented with a short preview, and its new name is asked for in the terminal.
+//! The answers make up the edited file list, which is confirmed as usual at the end.
+
+use crate::format_size;
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// The answer that keeps the names of the remaining files. `.` is never a valid file name.
+const KEEP_REMAINING: &str = ".";
+
+/// Number of lines shown of text files
+const PREVIEW_LINES: usize = 3;
+
+/// Walk through the lines of the file list, asking for the new name of each file with `ask`.
+/// An empty answer keeps the name, `.` keeps the names of all remaining files. A name without
+/// a `/` replaces the file name, otherwise it replaces the whole path.
+pub fn walk(content: &str, mut ask: impl FnMut(&str) -> Result<String>) -> Result<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut edited = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        let question = format!(
+            "\n[{}/{}] {}\n{}\nNew name (Enter keeps it, {} keeps all remaining names): ",
+            i + 1,
+            lines.len(),
+            line,
+            preview(Path::new(line)),
+            KEEP_REMAINING
+        );
+        let answer = ask(&question)?;
+        let answer = answer.trim();
+        if answer == KEEP_REMAINING {
+            edited.extend(lines[i..].iter().map(|line| line.to_string()));
+            break;
+        }
+        edited.push(if answer.is_empty() {
+            line.to_string()
+        } else if answer.contains('/') {
+            answer.to_string()
+        } else {
+            Path::new(line)
+                .with_file_name(answer)
+                .to_string_lossy()
+                .to_string()
+        });
+    }
+    Ok(edited.join("\n"))
+}
+
+/// Describe a file for choosing its name: its size and modification time, the dimensions of
+/// images, and the first lines of text files
+pub fn preview(path: &Path) -> String {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return "  (not found)".to_string(),
+    };
+    if metadata.is_dir() {
+        return "  directory".to_string();
+    }
+    let modified = metadata
+        .modified()
+        .map(|time| {
+            DateTime::<Local>::from(time)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default();
+    let mut lines = vec![format!(
+        "  {}, modified {}",
+        format_size(metadata.len()),
+        modified
+    )];
+    let mut header = Vec::new();
+    // special files like FIFOs could block when read
+    if metadata.is_file() {
+        if let Ok(file) = File::open(path) {
+            let _ = file.take(64 * 1024).read_to_end(&mut header);
+        }
+    }
+    if let Some((format, width, height)) = image_dimensions(&header) {
+        lines.push(format!("  {} image, {}x{} pixels", format, width, height));
+    } else if !header.is_empty() && std::str::from_utf8(&header).is_ok() {
+        let reader = BufReader::new(header.as_slice());
+        lines.extend(
+            reader
+                .lines()
+                .take(PREVIEW_LINES)
+                .map_while(|line| line.ok())
+                .map(|line| format!("  | {}", line)),
+        );
+    }
+    lines.join("\n")
+}
+
+/// The format and dimensions of a PNG, GIF or JPEG image, read from the start of the file
+fn image_dimensions(header: &[u8]) -> Option<(&'static str, u32, u32)> {
+    let be16 = |i: usize| Some(u16::from_be_bytes([*header.get(i)?, *header.get(i + 1)?]) as u32);
+    if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let be32 = |i: usize| Some(u32::from_be_bytes(header.get(i..i + 4)?.try_into().ok()?));
+        return Some(("PNG", be32(16)?, be32(20)?));
+    }
+    if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+        let le16 =
+            |i: usize| Some(u16::from_le_bytes([*header.get(i)?, *header.get(i + 1)?]) as u32);
+        return Some(("GIF", le16(6)?, le16(8)?));
+    }
+    if header.starts_with(b"\xff\xd8") {
+        // the dimensions are part of the start of frame segment, which follows other segments
+        let mut i = 2;
+        while *header.get(i)? == 0xff {
+            let marker = *header.get(i + 1)?;
+            let is_start_of_frame =
+                matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc);
+            if is_start_of_frame {
+                return Some(("JPEG", be16(i + 7)?, be16(i + 5)?));
+            }
+            i += 2 + be16(i + 2)? as usize;
+        }
+    }
+    None
+}